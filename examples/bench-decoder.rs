@@ -1,8 +1,7 @@
 extern crate parity_wasm;
-extern crate time;
 
-use std::fs;
-use time::Instant;
+use parity_wasm::elements::Module;
+use std::{fs, time::Instant};
 
 fn rate(file_name: &'static str, iterations: u64) {
 	let file_size = fs::metadata(file_name)
@@ -15,7 +14,7 @@ fn rate(file_name: &'static str, iterations: u64) {
 		let _module = parity_wasm::deserialize_file(file_name);
 		let end = Instant::now();
 
-		total_ms += (end - start).whole_milliseconds();
+		total_ms += (end - start).as_millis();
 	}
 
 	println!(
@@ -26,8 +25,32 @@ fn rate(file_name: &'static str, iterations: u64) {
 	);
 }
 
+fn lazy_rate(file_name: &'static str, iterations: u64) {
+	let contents = fs::read(file_name).unwrap_or_else(|_| panic!("{} to exist", file_name));
+	let mut total_ms = 0;
+
+	for _ in 0..iterations {
+		let start = Instant::now();
+		let _module = Module::deserialize_lazy(&contents);
+		let end = Instant::now();
+
+		total_ms += (end - start).as_millis();
+	}
+
+	println!(
+		"Lazy rate for {}: {} MB/s",
+		file_name,
+		(contents.len() as f64 * iterations as f64 / (1024*1024) as f64) /  // total work megabytes
+		(total_ms.max(1) as f64 / 1000f64) // total seconds
+	);
+}
+
 fn main() {
 	rate("./res/cases/v1/clang.wasm", 10);
 	rate("./res/cases/v1/hello.wasm", 100);
 	rate("./res/cases/v1/with_names.wasm", 100);
+
+	lazy_rate("./res/cases/v1/clang.wasm", 10);
+	lazy_rate("./res/cases/v1/hello.wasm", 100);
+	lazy_rate("./res/cases/v1/with_names.wasm", 100);
 }
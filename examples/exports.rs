@@ -70,7 +70,7 @@ fn main() {
 			// We are interested only in functions for an example
 			match *entry.internal() {
 				// Return function export name (return by field() function and it's index)
-				Internal::Function(index) => Some((entry.field(), index as usize)),
+				Internal::Function(index) => Some((entry.field(), index.index() as usize)),
 				_ => None
 			})
 		// Another map to resolve function signature index given it's internal index and return
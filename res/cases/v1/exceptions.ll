@@ -0,0 +1,62 @@
+; Compiled with LLVM 14:
+; llc -O2 -mattr=+exception-handling -exception-model=wasm -wasm-enable-eh -filetype=obj
+
+target datalayout = "e-m:e-p:32:32-i64:64-n32:64-S128"
+target triple = "wasm32-unknown-unknown"
+
+; int catcher() { try { may_throw(); } catch (...) { return 1; } return 0; }
+define i32 @catcher() personality i8* bitcast (i32 (...)* @__gxx_wasm_personality_v0 to i8*) {
+entry:
+  invoke void @may_throw()
+          to label %try.cont unwind label %catch.dispatch
+
+catch.dispatch:
+  %0 = catchswitch within none [label %catch.start] unwind to caller
+
+catch.start:
+  %1 = catchpad within %0 [i8* null]
+  %2 = call i8* @llvm.wasm.get.exception(token %1)
+  %3 = call i32 @llvm.wasm.get.ehselector(token %1)
+  %4 = call i8* @__cxa_begin_catch(i8* %2) [ "funclet"(token %1) ]
+  call void @__cxa_end_catch() [ "funclet"(token %1) ]
+  catchret from %1 to label %return
+
+try.cont:
+  br label %return
+
+return:
+  %r = phi i32 [ 1, %catch.start ], [ 0, %try.cont ]
+  ret i32 %r
+}
+
+; void guarded() { Guard guard; may_throw(); }
+define void @guarded() personality i8* bitcast (i32 (...)* @__gxx_wasm_personality_v0 to i8*) {
+entry:
+  invoke void @may_throw()
+          to label %cont unwind label %ehcleanup
+
+cont:
+  call void @release()
+  ret void
+
+ehcleanup:
+  %0 = cleanuppad within none []
+  call void @release() [ "funclet"(token %0) ]
+  cleanupret from %0 unwind to caller
+}
+
+; void raise(void *exception) { __builtin_wasm_throw(0, exception); }
+define void @raise(i8* %exception) {
+entry:
+  call void @llvm.wasm.throw(i32 0, i8* %exception)
+  unreachable
+}
+
+declare void @may_throw()
+declare void @release()
+declare i32 @__gxx_wasm_personality_v0(...)
+declare i8* @llvm.wasm.get.exception(token)
+declare i32 @llvm.wasm.get.ehselector(token)
+declare i8* @__cxa_begin_catch(i8*)
+declare void @__cxa_end_catch()
+declare void @llvm.wasm.throw(i32, i8*)
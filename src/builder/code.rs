@@ -1,4 +1,5 @@
 use super::{
+	instructions::InstructionsBuilder,
 	invoke::{Identity, Invoke},
 	misc::{ValueTypeBuilder, ValueTypesBuilder},
 };
@@ -278,18 +279,52 @@ where
 		self
 	}
 
+	/// Declare `count` locals of the given type.
+	///
+	/// Merged with the previous declaration if it has the same type, so the body is encoded
+	/// with as few declarations as possible.
+	pub fn with_local(mut self, count: u32, value_type: elements::ValueType) -> Self {
+		let locals = self.body.locals_mut();
+		if let Some(last) = locals.last_mut() {
+			if last.value_type() == value_type {
+				if let Some(merged) = last.count().checked_add(count) {
+					*last = elements::Local::new(merged, value_type);
+					return self
+				}
+			}
+		}
+		locals.push(elements::Local::new(count, value_type));
+		self
+	}
+
 	/// Set code of the function
 	pub fn with_instructions(mut self, instructions: elements::Instructions) -> Self {
 		*self.body.code_mut() = instructions;
 		self
 	}
 
+	/// Start builder of the function code
+	pub fn code(self) -> InstructionsBuilder<Self> {
+		InstructionsBuilder::with_callback(self)
+	}
+
 	/// Finish current builder spawning resulting struct
 	pub fn build(self) -> F::Result {
 		self.callback.invoke(self.body)
 	}
 }
 
+impl<F> Invoke<elements::Instructions> for FuncBodyBuilder<F>
+where
+	F: Invoke<elements::FuncBody>,
+{
+	type Result = Self;
+
+	fn invoke(self, instructions: elements::Instructions) -> Self {
+		self.with_instructions(instructions)
+	}
+}
+
 /// Function definition (extended structure to specify function entirely, incl. signature, mainness and code)
 pub struct FunctionDefinition {
 	/// Is this function is start function
@@ -483,4 +518,31 @@ mod tests {
 		assert_eq!(func.code.locals().len(), 0);
 		assert_eq!(func.code.code().elements().len(), 1);
 	}
+
+	#[test]
+	fn merged_locals() {
+		use elements::ValueType::*;
+
+		let func = function()
+			.signature()
+			.build()
+			.body()
+			.with_local(1, I64)
+			.with_local(2, I64)
+			.with_local(1, F32)
+			.with_local(u32::MAX, F32)
+			.build()
+			.build();
+
+		assert_eq!(
+			func.code.locals(),
+			&[
+				elements::Local::new(3, I64),
+				elements::Local::new(1, F32),
+				elements::Local::new(u32::MAX, F32),
+			]
+		);
+		let encoded = elements::serialize(func.code).expect("body to serialize");
+		assert_eq!(&encoded[..8], &[0x0c, 0x03, 0x03, 0x7e, 0x01, 0x7d, 0xff, 0xff]);
+	}
 }
@@ -9,6 +9,8 @@ pub struct DataSegmentBuilder<F = Identity> {
 	mem_index: u32,
 	offset: elements::InitExpr,
 	value: Vec<u8>,
+	#[cfg(feature = "bulk")]
+	passive: bool,
 }
 
 impl DataSegmentBuilder {
@@ -32,6 +34,8 @@ impl<F> DataSegmentBuilder<F> {
 			mem_index: 0,
 			offset: elements::InitExpr::empty(),
 			value: Vec::new(),
+			#[cfg(feature = "bulk")]
+			passive: false,
 		}
 	}
 
@@ -41,11 +45,23 @@ impl<F> DataSegmentBuilder<F> {
 		self
 	}
 
+	/// Set the offset to the value of the given global, usually an imported `__memory_base`.
+	pub fn offset_global(self, global_index: u32) -> Self {
+		self.offset(elements::Instruction::GetGlobal(global_index.into()))
+	}
+
 	/// Set the bytes value of the segment
 	pub fn value(mut self, value: Vec<u8>) -> Self {
 		self.value = value;
 		self
 	}
+
+	/// Make the segment passive, the offset is then ignored.
+	#[cfg(feature = "bulk")]
+	pub fn passive(mut self) -> Self {
+		self.passive = true;
+		self
+	}
 }
 
 impl<F> DataSegmentBuilder<F>
@@ -54,6 +70,12 @@ where
 {
 	/// Finish current builder, spawning resulting struct
 	pub fn build(self) -> F::Result {
+		#[cfg(feature = "bulk")]
+		if self.passive {
+			let mut segment = elements::DataSegment::new(self.mem_index, None, self.value);
+			segment.set_mode(elements::SegmentMode::Passive);
+			return self.callback.invoke(segment)
+		}
 		self.callback.invoke(elements::DataSegment::new(
 			self.mem_index,
 			Some(self.offset),
@@ -25,7 +25,11 @@ impl Default for ExportBuilder {
 impl<F> ExportBuilder<F> {
 	/// New export entry builder in the specified chained context
 	pub fn with_callback(callback: F) -> Self {
-		ExportBuilder { callback, field: String::new(), binding: elements::Internal::Function(0) }
+		ExportBuilder {
+			callback,
+			field: String::new(),
+			binding: elements::Internal::Function(0.into()),
+		}
 	}
 
 	/// Set the field name of the export entry
@@ -75,30 +79,30 @@ where
 {
 	/// New export entry internal mapping for the chained context
 	pub fn with_callback(callback: F) -> Self {
-		ExportInternalBuilder { callback, binding: elements::Internal::Function(0) }
+		ExportInternalBuilder { callback, binding: elements::Internal::Function(0.into()) }
 	}
 
 	/// Map to function by index
 	pub fn func(mut self, index: u32) -> F::Result {
-		self.binding = elements::Internal::Function(index);
+		self.binding = elements::Internal::Function(index.into());
 		self.callback.invoke(self.binding)
 	}
 
 	/// Map to memory
 	pub fn memory(mut self, index: u32) -> F::Result {
-		self.binding = elements::Internal::Memory(index);
+		self.binding = elements::Internal::Memory(index.into());
 		self.callback.invoke(self.binding)
 	}
 
 	/// Map to table
 	pub fn table(mut self, index: u32) -> F::Result {
-		self.binding = elements::Internal::Table(index);
+		self.binding = elements::Internal::Table(index.into());
 		self.callback.invoke(self.binding)
 	}
 
 	/// Map to global
 	pub fn global(mut self, index: u32) -> F::Result {
-		self.binding = elements::Internal::Global(index);
+		self.binding = elements::Internal::Global(index.into());
 		self.callback.invoke(self.binding)
 	}
 }
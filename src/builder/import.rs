@@ -30,7 +30,7 @@ impl<F> ImportBuilder<F> {
 			callback,
 			module: String::new(),
 			field: String::new(),
-			binding: elements::External::Function(0),
+			binding: elements::External::Function(0.into()),
 		}
 	}
 
@@ -93,12 +93,12 @@ where
 {
 	/// New import to external mapping builder with callback (in chained context)
 	pub fn with_callback(callback: F) -> Self {
-		ImportExternalBuilder { callback, binding: elements::External::Function(0) }
+		ImportExternalBuilder { callback, binding: elements::External::Function(0.into()) }
 	}
 
 	/// Function mapping with type reference
 	pub fn func(mut self, index: u32) -> F::Result {
-		self.binding = elements::External::Function(index);
+		self.binding = elements::External::Function(index.into());
 		self.callback.invoke(self.binding)
 	}
 
@@ -0,0 +1,302 @@
+use super::invoke::{Identity, Invoke};
+use crate::elements;
+use alloc::vec::Vec;
+
+/// Instruction sequence builder.
+///
+/// Instructions are appended in order. Blocks, loops and ifs take their bodies as closures
+/// and are closed with `end` automatically, only the final `end` of the sequence has to be
+/// added explicitly.
+///
+/// # Examples
+///
+/// ```
+/// use parity_wasm::{builder, elements::{BlockType, Instruction}};
+///
+/// let instructions = builder::code()
+///     .get_local(0)
+///     .if_(BlockType::NoResult, |code| code.i32_const(1).set_local(1))
+///     .get_local(1)
+///     .end()
+///     .build();
+/// assert_eq!(instructions.elements()[1], Instruction::If(BlockType::NoResult));
+/// assert_eq!(instructions.elements().len(), 7);
+/// ```
+pub struct InstructionsBuilder<F = Identity> {
+	callback: F,
+	instructions: Vec<elements::Instruction>,
+}
+
+impl InstructionsBuilder {
+	/// New instruction sequence builder.
+	pub fn new() -> Self {
+		InstructionsBuilder::with_callback(Identity)
+	}
+}
+
+impl Default for InstructionsBuilder {
+	fn default() -> Self {
+		InstructionsBuilder::new()
+	}
+}
+
+impl<F> InstructionsBuilder<F> {
+	/// New instruction sequence builder with the chain callback.
+	pub fn with_callback(callback: F) -> Self {
+		InstructionsBuilder { callback, instructions: Vec::new() }
+	}
+
+	/// Append an instruction.
+	pub fn with_instruction(mut self, instruction: elements::Instruction) -> Self {
+		self.instructions.push(instruction);
+		self
+	}
+
+	/// Append instructions.
+	pub fn with_instructions<I>(mut self, instructions: I) -> Self
+	where
+		I: IntoIterator<Item = elements::Instruction>,
+	{
+		self.instructions.extend(instructions);
+		self
+	}
+
+	fn with_body<B>(mut self, start: elements::Instruction, body: B) -> Self
+	where
+		B: FnOnce(Self) -> Self,
+	{
+		self.instructions.push(start);
+		self = body(self);
+		self.with_instruction(elements::Instruction::End)
+	}
+
+	/// Append a block with the given body, closed by `end`.
+	pub fn block<B>(self, block_type: elements::BlockType, body: B) -> Self
+	where
+		B: FnOnce(Self) -> Self,
+	{
+		self.with_body(elements::Instruction::Block(block_type), body)
+	}
+
+	/// Append a loop with the given body, closed by `end`.
+	pub fn loop_<B>(self, block_type: elements::BlockType, body: B) -> Self
+	where
+		B: FnOnce(Self) -> Self,
+	{
+		self.with_body(elements::Instruction::Loop(block_type), body)
+	}
+
+	/// Append an if without else branch, closed by `end`.
+	pub fn if_<B>(self, block_type: elements::BlockType, then: B) -> Self
+	where
+		B: FnOnce(Self) -> Self,
+	{
+		self.with_body(elements::Instruction::If(block_type), then)
+	}
+
+	/// Append an if with both branches, closed by `end`.
+	pub fn if_else<T, E>(self, block_type: elements::BlockType, then: T, otherwise: E) -> Self
+	where
+		T: FnOnce(Self) -> Self,
+		E: FnOnce(Self) -> Self,
+	{
+		self.with_body(elements::Instruction::If(block_type), |code| {
+			otherwise(then(code).with_instruction(elements::Instruction::Else))
+		})
+	}
+}
+
+macro_rules! simple_instructions {
+	($($(#[$attr:meta])* $name:ident => $instruction:ident,)*) => {
+		impl<F> InstructionsBuilder<F> {
+			$(
+				$(#[$attr])*
+				pub fn $name(self) -> Self {
+					self.with_instruction(elements::Instruction::$instruction)
+				}
+			)*
+		}
+	};
+}
+
+macro_rules! index_instructions {
+	($($(#[$attr:meta])* $name:ident => $instruction:ident,)*) => {
+		impl<F> InstructionsBuilder<F> {
+			$(
+				$(#[$attr])*
+				pub fn $name(self, index: u32) -> Self {
+					self.with_instruction(elements::Instruction::$instruction(index.into()))
+				}
+			)*
+		}
+	};
+}
+
+simple_instructions! {
+	/// Append `unreachable`.
+	unreachable => Unreachable,
+	/// Append `nop`.
+	nop => Nop,
+	/// Append `end`, closing the sequence.
+	end => End,
+	/// Append `return`.
+	return_ => Return,
+	/// Append `drop`.
+	drop => Drop,
+	/// Append `select`.
+	select => Select,
+	/// Append `i32.eqz`.
+	i32_eqz => I32Eqz,
+	/// Append `i32.eq`.
+	i32_eq => I32Eq,
+	/// Append `i32.lt_s`.
+	i32_lt_s => I32LtS,
+	/// Append `i32.add`.
+	i32_add => I32Add,
+	/// Append `i32.sub`.
+	i32_sub => I32Sub,
+	/// Append `i32.mul`.
+	i32_mul => I32Mul,
+	/// Append `i64.add`.
+	i64_add => I64Add,
+	/// Append `i64.sub`.
+	i64_sub => I64Sub,
+	/// Append `i64.mul`.
+	i64_mul => I64Mul,
+}
+
+index_instructions! {
+	/// Append `br` to the label at the given depth.
+	br => Br,
+	/// Append `br_if` to the label at the given depth.
+	br_if => BrIf,
+	/// Append `call` of the given function.
+	call => Call,
+	/// Append `get_local`.
+	get_local => GetLocal,
+	/// Append `set_local`.
+	set_local => SetLocal,
+	/// Append `tee_local`.
+	tee_local => TeeLocal,
+	/// Append `get_global`.
+	get_global => GetGlobal,
+	/// Append `set_global`.
+	set_global => SetGlobal,
+}
+
+impl<F> InstructionsBuilder<F> {
+	/// Append `i32.const`.
+	pub fn i32_const(self, value: i32) -> Self {
+		self.with_instruction(elements::Instruction::i32_const(value))
+	}
+
+	/// Append `i64.const`.
+	pub fn i64_const(self, value: i64) -> Self {
+		self.with_instruction(elements::Instruction::i64_const(value))
+	}
+
+	/// Append `f32.const`.
+	pub fn f32_const(self, value: f32) -> Self {
+		self.with_instruction(elements::Instruction::f32_const(value))
+	}
+
+	/// Append `f64.const`.
+	pub fn f64_const(self, value: f64) -> Self {
+		self.with_instruction(elements::Instruction::f64_const(value))
+	}
+}
+
+impl<F> InstructionsBuilder<F>
+where
+	F: Invoke<elements::Instructions>,
+{
+	/// Finish the sequence.
+	///
+	/// # Panics
+	///
+	/// Panics if the blocks are not balanced, see [`InstructionsBuilder::try_build`].
+	pub fn build(self) -> F::Result {
+		match self.try_build() {
+			Ok(result) => result,
+			Err(err) => panic!("instructions cannot be built: {}", err),
+		}
+	}
+
+	/// Finish the sequence.
+	///
+	/// Fails with `Error::UnbalancedBlocks` unless the sequence is closed by exactly one
+	/// `end` more than it opens blocks, and nothing follows that final `end`.
+	pub fn try_build(self) -> Result<F::Result, elements::Error> {
+		let mut depth = 1usize;
+		for (position, instruction) in self.instructions.iter().enumerate() {
+			if depth == 0 {
+				return Err(elements::Error::UnbalancedBlocks(position))
+			}
+			if instruction.closes_block() {
+				depth -= 1;
+			} else if instruction.is_block() {
+				depth += 1;
+			}
+		}
+		if depth != 0 {
+			return Err(elements::Error::UnbalancedBlocks(self.instructions.len()))
+		}
+		Ok(self.callback.invoke(elements::Instructions::new(self.instructions)))
+	}
+}
+
+/// New instruction sequence builder.
+pub fn code() -> InstructionsBuilder {
+	InstructionsBuilder::new()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::code;
+	use crate::elements::{BlockType, Error, Instruction::*, ValueType};
+
+	#[test]
+	fn blocks_are_closed() {
+		let instructions = code()
+			.block(BlockType::NoResult, |code| {
+				code.loop_(BlockType::NoResult, |code| code.get_local(0).br_if(0))
+			})
+			.if_else(
+				BlockType::Value(ValueType::I32),
+				|code| code.i32_const(1),
+				|code| code.i32_const(2),
+			)
+			.drop()
+			.end()
+			.build();
+		assert_eq!(
+			instructions.elements(),
+			&[
+				Block(BlockType::NoResult),
+				Loop(BlockType::NoResult),
+				GetLocal(0.into()),
+				BrIf(0.into()),
+				End,
+				End,
+				If(BlockType::Value(ValueType::I32)),
+				I32Const(1),
+				Else,
+				I32Const(2),
+				End,
+				Drop,
+				End,
+			]
+		);
+	}
+
+	#[test]
+	fn unbalanced() {
+		assert!(matches!(code().nop().try_build(), Err(Error::UnbalancedBlocks(1))));
+		assert!(matches!(
+			code().with_instruction(Block(BlockType::NoResult)).end().try_build(),
+			Err(Error::UnbalancedBlocks(2))
+		));
+		assert!(matches!(code().end().nop().try_build(), Err(Error::UnbalancedBlocks(1))));
+		assert!(code().end().try_build().is_ok());
+	}
+}
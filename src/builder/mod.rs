@@ -5,6 +5,7 @@ mod data;
 mod export;
 mod global;
 mod import;
+mod instructions;
 mod invoke;
 mod memory;
 mod misc;
@@ -20,6 +21,7 @@ pub use self::{
 	export::{export, ExportBuilder, ExportInternalBuilder},
 	global::{global, GlobalBuilder},
 	import::{import, ImportBuilder},
+	instructions::{code, InstructionsBuilder},
 	invoke::Identity,
 	memory::MemoryBuilder,
 	module::{from_module, module, CodeLocation, ModuleBuilder},
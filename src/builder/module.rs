@@ -6,12 +6,21 @@ use super::{
 	table::{self, TableBuilder},
 };
 use crate::elements;
-use alloc::vec::Vec;
+use alloc::{collections::BTreeSet, string::String, vec::Vec};
 
 /// Module builder
+///
+/// The `push_*` methods return indices in the index spaces of the module, which count the
+/// imports declared so far. Adding an import of the same kind afterwards shifts them, so
+/// declare imports first. With [`ModuleBuilder::with_strict_indices`],
+/// [`ModuleBuilder::try_build`] fails with `Error::ImportAfterIndices` in that case.
 pub struct ModuleBuilder<F = Identity> {
 	callback: F,
 	module: ModuleScaffold,
+	returned: IndexSpaces,
+	import_after_indices: bool,
+	strict_indices: bool,
+	features: Option<elements::Features>,
 }
 
 /// Location of the internal module function
@@ -20,6 +29,17 @@ pub struct CodeLocation {
 	pub signature: u32,
 	/// Location (index in the 'code' section) of the body
 	pub body: u32,
+	/// Index of the function in the function index space, imported functions included
+	pub function: elements::FuncIdx,
+}
+
+/// Index spaces for which indices were returned.
+#[derive(Default)]
+struct IndexSpaces {
+	functions: bool,
+	tables: bool,
+	memories: bool,
+	globals: bool,
 }
 
 #[derive(Default, PartialEq)]
@@ -53,8 +73,7 @@ impl From<elements::Module> for ModuleScaffold {
 		let mut data: Option<elements::DataSection> = None;
 
 		let mut other = Vec::new();
-		let mut sections = module.into_sections();
-		while let Some(section) = sections.pop() {
+		for section in module.into_sections() {
 			match section {
 				elements::Section::Type(sect) => {
 					types = Some(sect);
@@ -110,55 +129,94 @@ impl From<elements::Module> for ModuleScaffold {
 	}
 }
 
-impl From<ModuleScaffold> for elements::Module {
-	fn from(module: ModuleScaffold) -> Self {
+/// Position of the section with the given id in the module, as required by the spec.
+fn section_rank(id: u8) -> usize {
+	// The tag section (13) of the exception handling proposal goes after memories.
+	const ORDER: [u8; 13] = [1, 2, 3, 4, 5, 13, 6, 7, 8, 9, 12, 10, 11];
+	ORDER.iter().position(|&order| order == id).unwrap_or(ORDER.len())
+}
+
+impl ModuleScaffold {
+	/// Assemble the module, placing the additional sections at their positions.
+	///
+	/// Additional sections which duplicate a section the builder generated itself (or
+	/// each other) are reported as `Error::DuplicatedSections`. Custom sections are
+	/// placed after all other sections.
+	fn into_module(self) -> Result<elements::Module, elements::Error> {
 		let mut sections = Vec::new();
 
-		let types = module.types;
+		let types = self.types;
 		if !types.types().is_empty() {
 			sections.push(elements::Section::Type(types));
 		}
-		let import = module.import;
+		let import = self.import;
 		if !import.entries().is_empty() {
 			sections.push(elements::Section::Import(import));
 		}
-		let functions = module.functions;
+		let functions = self.functions;
 		if !functions.entries().is_empty() {
 			sections.push(elements::Section::Function(functions));
 		}
-		let table = module.table;
+		let table = self.table;
 		if !table.entries().is_empty() {
 			sections.push(elements::Section::Table(table));
 		}
-		let memory = module.memory;
+		let memory = self.memory;
 		if !memory.entries().is_empty() {
 			sections.push(elements::Section::Memory(memory));
 		}
-		let global = module.global;
+		let global = self.global;
 		if !global.entries().is_empty() {
 			sections.push(elements::Section::Global(global));
 		}
-		let export = module.export;
+		let export = self.export;
+		let mut names = BTreeSet::new();
+		if let Some(entry) = export.entries().iter().find(|entry| !names.insert(entry.field())) {
+			return Err(elements::Error::DuplicatedExport(entry.field().into()))
+		}
 		if !export.entries().is_empty() {
 			sections.push(elements::Section::Export(export));
 		}
-		if let Some(start) = module.start {
+		if let Some(start) = self.start {
 			sections.push(elements::Section::Start(start));
 		}
-		let element = module.element;
+		let element = self.element;
 		if !element.entries().is_empty() {
 			sections.push(elements::Section::Element(element));
 		}
-		let code = module.code;
+		let code = self.code;
 		if !code.bodies().is_empty() {
 			sections.push(elements::Section::Code(code));
 		}
-		let data = module.data;
+		let data = self.data;
 		if !data.entries().is_empty() {
 			sections.push(elements::Section::Data(data));
 		}
-		sections.extend(module.other);
-		elements::Module::new(sections)
+
+		let mut custom = Vec::new();
+		for section in self.other {
+			let id = match section.key() {
+				elements::SectionKey::Id(id) if id != 0x00 => id,
+				_ => {
+					custom.push(section);
+					continue
+				},
+			};
+			if sections.iter().any(|existing| existing.key() == elements::SectionKey::Id(id)) {
+				return Err(elements::Error::DuplicatedSections(id))
+			}
+			let position = sections
+				.iter()
+				.position(|existing| match existing.key() {
+					elements::SectionKey::Id(existing) => section_rank(existing) > section_rank(id),
+					elements::SectionKey::Custom(_) => true,
+				})
+				.unwrap_or(sections.len());
+			sections.insert(position, section);
+		}
+		sections.extend(custom);
+
+		Ok(elements::Module::new(sections))
 	}
 }
 
@@ -181,7 +239,27 @@ where
 {
 	/// New module builder with bound callback
 	pub fn with_callback(callback: F) -> Self {
-		ModuleBuilder { callback, module: Default::default() }
+		ModuleBuilder {
+			callback,
+			module: Default::default(),
+			returned: Default::default(),
+			import_after_indices: false,
+			strict_indices: false,
+			features: None,
+		}
+	}
+
+	/// Reject imports declared after indices they shift were returned, checked by
+	/// [`ModuleBuilder::try_build`].
+	pub fn with_strict_indices(mut self) -> Self {
+		self.strict_indices = true;
+		self
+	}
+
+	/// Restrict the module to the given proposals, checked by [`ModuleBuilder::try_build`].
+	pub fn with_features(mut self, features: elements::Features) -> Self {
+		self.features = Some(features);
+		self
 	}
 
 	/// Builder from raw module
@@ -200,11 +278,58 @@ where
 	}
 
 	/// Add additional section
+	///
+	/// The section is placed at its position in the module when building. It must not
+	/// duplicate a section the builder generates itself, see [`ModuleBuilder::try_build`].
 	pub fn with_section(mut self, section: elements::Section) -> Self {
 		self.module.other.push(section);
 		self
 	}
 
+	/// Add custom section with the given name and payload
+	pub fn with_raw_custom_section(self, name: impl Into<String>, payload: Vec<u8>) -> Self {
+		self.with_section(elements::Section::Custom(elements::CustomSection::new(
+			name.into(),
+			payload,
+		)))
+	}
+
+	/// Add a producer to the producers section, creating the section when required.
+	///
+	/// An existing `producers` custom section is parsed and extended, failing if it cannot
+	/// be parsed.
+	pub fn with_producer(
+		mut self,
+		field: impl Into<String>,
+		name: impl Into<String>,
+		version: impl Into<String>,
+	) -> Result<Self, elements::Error> {
+		let existing = self.module.other.iter().position(|section| match *section {
+			elements::Section::Producers(_) => true,
+			elements::Section::Custom(ref custom) => custom.name() == "producers",
+			_ => false,
+		});
+
+		let index = match existing {
+			Some(index) => index,
+			None => {
+				self.module.other.push(elements::Section::Producers(Default::default()));
+				self.module.other.len() - 1
+			},
+		};
+
+		let section = &mut self.module.other[index];
+		if let elements::Section::Custom(ref custom) = *section {
+			let producers = elements::deserialize_buffer(custom.payload())?;
+			*section = elements::Section::Producers(producers);
+		}
+		if let elements::Section::Producers(ref mut producers) = *section {
+			producers.add(field, name, version);
+		}
+
+		Ok(self)
+	}
+
 	/// Binds to the type section, creates additional types when required
 	pub fn with_signatures(mut self, bindings: code::SignatureBindings) -> Self {
 		self.push_signatures(bindings);
@@ -215,6 +340,13 @@ where
 	/// in corresponding sections.
 	/// `FunctionDefinition` can be build using `builder::function` builder
 	pub fn push_function(&mut self, func: code::FunctionDefinition) -> CodeLocation {
+		self.returned.functions = true;
+		self.define_function(func)
+	}
+
+	fn define_function(&mut self, func: code::FunctionDefinition) -> CodeLocation {
+		let imported = self.module.import.functions() as u32;
+		let function = elements::FuncIdx(imported + self.module.functions.entries().len() as u32);
 		let signature = func.signature;
 		let body = func.code;
 
@@ -229,14 +361,20 @@ where
 			self.module.start = Some(body_index);
 		}
 
-		CodeLocation { signature: signature_index, body: body_index }
+		CodeLocation { signature: signature_index, body: body_index, function }
+	}
+
+	/// Push linear memory region, returning its index in the memory index space
+	pub fn push_memory(&mut self, memory: memory::MemoryDefinition) -> u32 {
+		self.returned.memories = true;
+		self.define_memory(memory)
 	}
 
-	/// Push linear memory region
-	pub fn push_memory(&mut self, mut memory: memory::MemoryDefinition) -> u32 {
+	fn define_memory(&mut self, mut memory: memory::MemoryDefinition) -> u32 {
+		let imported = self.imported(|external| matches!(external, elements::External::Memory(_)));
 		let entries = self.module.memory.entries_mut();
 		entries.push(elements::MemoryType::new(memory.min, memory.max));
-		let memory_index = (entries.len() - 1) as u32;
+		let memory_index = imported + entries.len() as u32 - 1;
 		for data in memory.data.drain(..) {
 			self.module.data.entries_mut().push(elements::DataSegment::new(
 				memory_index,
@@ -247,11 +385,17 @@ where
 		memory_index
 	}
 
-	/// Push table
-	pub fn push_table(&mut self, mut table: table::TableDefinition) -> u32 {
+	/// Push table, returning its index in the table index space
+	pub fn push_table(&mut self, table: table::TableDefinition) -> u32 {
+		self.returned.tables = true;
+		self.define_table(table)
+	}
+
+	fn define_table(&mut self, mut table: table::TableDefinition) -> u32 {
+		let imported = self.imported(|external| matches!(external, elements::External::Table(_)));
 		let entries = self.module.table.entries_mut();
 		entries.push(elements::TableType::new(table.min, table.max));
-		let table_index = (entries.len() - 1) as u32;
+		let table_index = imported + entries.len() as u32 - 1;
 		for entry in table.elements.drain(..) {
 			self.module.element.entries_mut().push(elements::ElementSegment::new(
 				table_index,
@@ -262,11 +406,33 @@ where
 		table_index
 	}
 
-	/// Push global.
+	/// Push global, returning its index in the global index space.
 	pub fn push_global(&mut self, global: elements::GlobalEntry) -> u32 {
+		self.returned.globals = true;
 		let entries = self.module.global.entries_mut();
 		entries.push(global);
-		entries.len() as u32 - 1
+		(self.module.import.globals() + entries.len()) as u32 - 1
+	}
+
+	fn imported<P>(&self, is_kind: P) -> u32
+	where
+		P: Fn(&elements::External) -> bool,
+	{
+		let entries = self.module.import.entries();
+		entries.iter().filter(|entry| is_kind(entry.external())).count() as u32
+	}
+
+	fn add_import(&mut self, import: elements::ImportEntry) {
+		let shifts_returned = match *import.external() {
+			elements::External::Function(_) => self.returned.functions,
+			elements::External::Table(_) => self.returned.tables,
+			elements::External::Memory(_) => self.returned.memories,
+			elements::External::Global(_) => self.returned.globals,
+			#[cfg(feature = "exceptions")]
+			elements::External::Tag(_) => false,
+		};
+		self.import_after_indices |= shifts_returned;
+		self.module.import.entries_mut().push(import);
 	}
 
 	fn resolve_type_ref(&mut self, signature: code::Signature) -> u32 {
@@ -300,7 +466,7 @@ where
 	/// Push import entry to module. Note that this does not update calling indices in
 	/// function bodies.
 	pub fn push_import(&mut self, import: elements::ImportEntry) -> u32 {
-		self.module.import.entries_mut().push(import);
+		self.add_import(import);
 		// todo: actually update calling addresses in function bodies
 		// todo: also batch push
 
@@ -341,7 +507,7 @@ where
 
 	/// With inserted import entry
 	pub fn with_import(mut self, entry: elements::ImportEntry) -> Self {
-		self.module.import.entries_mut().push(entry);
+		self.add_import(entry);
 		self
 	}
 
@@ -430,8 +596,37 @@ where
 	}
 
 	/// Build module (final step)
+	///
+	/// # Panics
+	///
+	/// Panics if an additional section conflicts with the generated ones, export names are
+	/// duplicated, or a check enabled on the builder fails, see [`ModuleBuilder::try_build`].
 	pub fn build(self) -> F::Result {
-		self.callback.invoke(self.module.into())
+		match self.try_build() {
+			Ok(result) => result,
+			Err(err) => panic!("module cannot be built: {}", err),
+		}
+	}
+
+	/// Build module (final step)
+	///
+	/// Fails if a section added with [`ModuleBuilder::with_section`] duplicates another
+	/// one, for example a type section is given while functions are also added with
+	/// [`ModuleBuilder::function`], or if two exports have the same name. The same item can
+	/// be exported under several names. With [`ModuleBuilder::with_strict_indices`], an
+	/// import declared after indices it shifts were returned fails with
+	/// `Error::ImportAfterIndices`. With [`ModuleBuilder::with_features`], constructs of
+	/// other proposals fail with `Error::UnsupportedFeature`, see
+	/// [`Module::check_features`](elements::Module::check_features).
+	pub fn try_build(self) -> Result<F::Result, elements::Error> {
+		if self.strict_indices && self.import_after_indices {
+			return Err(elements::Error::ImportAfterIndices)
+		}
+		let module = self.module.into_module()?;
+		if let Some(ref features) = self.features {
+			module.check_features(features)?;
+		}
+		Ok(self.callback.invoke(module))
 	}
 }
 
@@ -465,7 +660,7 @@ where
 
 	fn invoke(self, def: code::FunctionDefinition) -> Self {
 		let mut b = self;
-		b.push_function(def);
+		b.define_function(def);
 		b
 	}
 }
@@ -478,7 +673,7 @@ where
 
 	fn invoke(self, def: memory::MemoryDefinition) -> Self {
 		let mut b = self;
-		b.push_memory(def);
+		b.define_memory(def);
 		b
 	}
 }
@@ -491,7 +686,7 @@ where
 
 	fn invoke(self, def: table::TableDefinition) -> Self {
 		let mut b = self;
-		b.push_table(def);
+		b.define_table(def);
 		b
 	}
 }
@@ -655,4 +850,179 @@ mod tests {
 
 		assert_eq!(module.type_section().expect("type section failed").types().len(), 1);
 	}
+
+	#[test]
+	fn producers() {
+		let module = module()
+			.with_producer("language", "Rust", "")
+			.and_then(|builder| builder.with_producer("processed-by", "rustc", "1.70.0"))
+			.and_then(|builder| builder.with_producer("processed-by", "wasm-opt", "113"))
+			.expect("producers to be added")
+			.build();
+
+		let producers = module.producers_section().expect("producers section to exist");
+		assert_eq!(producers.fields().len(), 2);
+		assert_eq!(producers.processed_by().expect("processed-by field to exist").len(), 2);
+
+		let malformed = super::module().with_raw_custom_section("producers", vec![0x01, 0x05]);
+		assert!(malformed.with_producer("language", "Rust", "").is_err());
+	}
+
+	#[test]
+	fn with_sections_in_order() {
+		use elements::SectionKey::*;
+
+		let module = module()
+			.with_raw_custom_section("sourceMappingURL", b"\x0dmain.wasm.map".to_vec())
+			.with_section(elements::Section::DataCount(1))
+			.function()
+			.signature()
+			.build()
+			.body()
+			.build()
+			.build()
+			.data()
+			.offset(elements::Instruction::I32Const(16))
+			.value(vec![0u8, 15, 10, 5, 25])
+			.build()
+			.build();
+
+		let bytes = module.into_bytes().expect("module to serialize");
+		let module = elements::Module::from_bytes(bytes).expect("module to deserialize");
+		let keys: Vec<_> = module.sections().iter().map(|section| section.key()).collect();
+		assert_eq!(
+			keys,
+			vec![Id(0x01), Id(0x03), Id(0x0c), Id(0x0a), Id(0x0b), Custom("sourceMappingURL")]
+		);
+	}
+
+	#[test]
+	fn with_section_conflict() {
+		let types = elements::TypeSection::with_types(vec![elements::Type::Function(
+			elements::FunctionType::new(vec![elements::ValueType::I32], vec![]),
+		)]);
+		let result = module()
+			.with_section(elements::Section::Type(types.clone()))
+			.function()
+			.signature()
+			.build()
+			.body()
+			.build()
+			.build()
+			.try_build();
+		assert!(matches!(result, Err(elements::Error::DuplicatedSections(0x01))));
+
+		let module = module()
+			.with_section(elements::Section::Type(types))
+			.try_build()
+			.expect("type section alone is fine");
+		assert_eq!(module.type_section().expect("type section to exist").types().len(), 1);
+	}
+
+	#[test]
+	fn indices_count_imports() {
+		use crate::builder::{function, import};
+		use elements::{FuncIdx, Instruction::*, Instructions};
+
+		let mut builder = module()
+			.import()
+			.path("env", "a")
+			.external()
+			.func(0)
+			.build()
+			.import()
+			.path("env", "b")
+			.external()
+			.func(0)
+			.build();
+		let first = builder.push_function(function().signature().build().body().build().build());
+		let calling = function()
+			.signature()
+			.build()
+			.body()
+			.with_instructions(Instructions::new(vec![Call(first.function), End]))
+			.build()
+			.build();
+		let second = builder.push_function(calling);
+		assert_eq!((first.function, second.function), (FuncIdx(2), FuncIdx(3)));
+		assert_eq!((second.signature, second.body), (1, 1));
+
+		let global = elements::GlobalEntry::new(
+			elements::GlobalType::new(elements::ValueType::I32, false),
+			elements::InitExpr::new(vec![I32Const(0), End]),
+		);
+		assert_eq!(builder.push_global(global), 0);
+
+		let bytes = elements::serialize(builder.build()).expect("module to serialize");
+		let built: elements::Module =
+			elements::deserialize_buffer(&bytes).expect("module to deserialize");
+		let bodies = built.code_section().expect("code section to exist").bodies();
+		assert_eq!(bodies[1].code().elements()[0], Call(FuncIdx(2)));
+
+		// Function imports now would shift the returned indices, global imports wouldn't.
+		let shifted = |strict: bool| {
+			let mut builder = module().function().signature().build().body().build().build();
+			if strict {
+				builder = builder.with_strict_indices();
+			}
+			let function = function().signature().build().body().build().build();
+			assert_eq!(builder.push_function(function).function, FuncIdx(1));
+			let global =
+				import().path("env", "g").external().global(elements::ValueType::I32, false);
+			builder.push_import(global.build());
+			builder.import().path("env", "c").external().func(0).build().try_build()
+		};
+		assert!(shifted(false).is_ok());
+		assert!(matches!(shifted(true), Err(elements::Error::ImportAfterIndices)));
+	}
+
+	#[test]
+	fn export_under_several_names() {
+		let exported = |names: &[&str]| {
+			let mut builder = module().function().signature().build().body().build().build();
+			for name in names {
+				builder = builder.export().field(name).internal().func(0).build();
+			}
+			builder.try_build()
+		};
+
+		let module = exported(&["add", "plus"]).expect("module to build");
+		let functions: Vec<_> = module.exported_functions().collect();
+		assert_eq!(functions, [("add", 0.into()), ("plus", 0.into())]);
+		assert_eq!(
+			module.export_by_name("plus").unwrap().kind,
+			elements::Internal::Function(0.into())
+		);
+		assert!(module.export_by_name("sub").is_none());
+
+		let result = exported(&["add", "plus", "add"]);
+		assert!(matches!(result, Err(elements::Error::DuplicatedExport(name)) if name == "add"));
+	}
+
+	#[test]
+	fn target_features() {
+		let builder = || {
+			module()
+				.function()
+				.signature()
+				.build()
+				.body()
+				.with_instructions(elements::Instructions::new(vec![
+					elements::Instruction::I32Const(0),
+					elements::Instruction::CallIndirect(0.into(), 1),
+					elements::Instruction::End,
+				]))
+				.build()
+				.build()
+		};
+
+		let result = builder().with_features(elements::Features::mvp()).try_build();
+		assert!(matches!(
+			result,
+			Err(elements::Error::UnsupportedFeature { proposal: "reference types", .. })
+		));
+		let features = elements::Features { reference_types: true, ..elements::Features::mvp() };
+		assert!(builder().with_features(features).try_build().is_ok());
+		assert!(builder().try_build().is_ok());
+	}
 }
@@ -74,6 +74,19 @@ where
 		self
 	}
 
+	/// Generate element values at the offset held by the given global, usually an imported
+	/// `__table_base`
+	pub fn with_element_global(mut self, global_index: u32, values: Vec<u32>) -> Self {
+		self.table.elements.push(TableEntryDefinition {
+			offset: elements::InitExpr::new(vec![
+				elements::Instruction::GetGlobal(global_index.into()),
+				elements::Instruction::End,
+			]),
+			values,
+		});
+		self
+	}
+
 	/// Finalize current builder spawning resulting struct
 	pub fn build(self) -> F::Result {
 		self.callback.invoke(self.table)
@@ -0,0 +1,266 @@
+use super::{Error, External, GlobalIdx, InitExpr, Instruction, Module, ValueType};
+use alloc::vec::Vec;
+
+/// Value of a constant expression.
+///
+/// Floats are kept as their bit patterns, like in [`Instruction::F32Const`] and
+/// [`Instruction::F64Const`], so NaN payloads are preserved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConstValue {
+	/// 32-bit integer.
+	I32(i32),
+	/// 64-bit integer.
+	I64(i64),
+	/// 32-bit float, as bits.
+	F32(u32),
+	/// 64-bit float, as bits.
+	F64(u64),
+}
+
+impl ConstValue {
+	/// Type of the value.
+	pub fn value_type(&self) -> ValueType {
+		match self {
+			ConstValue::I32(_) => ValueType::I32,
+			ConstValue::I64(_) => ValueType::I64,
+			ConstValue::F32(_) => ValueType::F32,
+			ConstValue::F64(_) => ValueType::F64,
+		}
+	}
+}
+
+/// Values of the globals which constant expressions can refer to.
+pub trait GlobalResolver {
+	/// Value of the global, `None` if it can't be used in constant expressions.
+	///
+	/// Only imported immutable globals can be.
+	fn resolve_global(&self, index: GlobalIdx) -> Option<ConstValue>;
+}
+
+/// Values of the imported immutable globals, in index order.
+impl GlobalResolver for [ConstValue] {
+	fn resolve_global(&self, index: GlobalIdx) -> Option<ConstValue> {
+		self.get(index.index() as usize).copied()
+	}
+}
+
+impl InitExpr {
+	/// Evaluate the expression.
+	///
+	/// Supports `i32.const`, `i64.const`, `f32.const`, `f64.const` and `get_global` of
+	/// globals known to `globals`. Any other instruction is rejected with
+	/// [`Error::NonConstantInstruction`], and the expression must produce exactly one value.
+	pub fn evaluate<G: GlobalResolver + ?Sized>(&self, globals: &G) -> Result<ConstValue, Error> {
+		let mut stack = Vec::new();
+		for instruction in self.code() {
+			let value = match *instruction {
+				Instruction::GetGlobal(index) =>
+					globals.resolve_global(index).ok_or(Error::UnresolvedGlobal(index.index()))?,
+				Instruction::End => break,
+				ref other => other
+					.const_value()
+					.ok_or_else(|| Error::NonConstantInstruction(other.clone()))?,
+			};
+			stack.push(value);
+		}
+
+		match stack[..] {
+			[value] => Ok(value),
+			_ => Err(Error::InvalidConstantExpression),
+		}
+	}
+}
+
+impl Module {
+	/// Check that the offsets of the active segments are constant `i32` expressions.
+	///
+	/// Offsets may only refer to imported immutable globals, like `__memory_base` and
+	/// `__table_base` in relocatable modules, which are then resolved on instantiation. Any
+	/// other global is reported as [`Error::UnresolvedGlobal`].
+	pub fn validate_segment_offsets(&self) -> Result<(), Error> {
+		let globals = ImportedGlobals(
+			self.import_section()
+				.map(|section| section.entries())
+				.unwrap_or_default()
+				.iter()
+				.filter_map(|entry| match *entry.external() {
+					External::Global(ref global) if global.is_mutable() => Some(None),
+					External::Global(ref global) => Some(Some(global.content_type())),
+					_ => None,
+				})
+				.collect(),
+		);
+
+		let elements = self.elements_section().map(|section| section.entries()).unwrap_or_default();
+		let data = self.data_section().map(|section| section.entries()).unwrap_or_default();
+		let offsets = elements
+			.iter()
+			.filter_map(|segment| segment.offset().as_ref())
+			.chain(data.iter().filter_map(|segment| segment.offset().as_ref()));
+		for offset in offsets {
+			if offset.evaluate(&globals)?.value_type() != ValueType::I32 {
+				return Err(Error::InvalidConstantExpression)
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Types of the imported globals, `None` for mutable ones, resolved to zero.
+struct ImportedGlobals(Vec<Option<ValueType>>);
+
+impl GlobalResolver for ImportedGlobals {
+	fn resolve_global(&self, index: GlobalIdx) -> Option<ConstValue> {
+		match (*self.0.get(index.index() as usize)?)? {
+			ValueType::I32 => Some(ConstValue::I32(0)),
+			ValueType::I64 => Some(ConstValue::I64(0)),
+			ValueType::F32 => Some(ConstValue::F32(0)),
+			ValueType::F64 => Some(ConstValue::F64(0)),
+			#[cfg(feature = "simd")]
+			ValueType::V128 => None,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{GlobalIdx, InitExpr, Instruction, ValueType},
+		ConstValue,
+	};
+	use crate::{builder, elements::Error};
+
+	fn evaluate(code: Vec<Instruction>, globals: &[ConstValue]) -> Result<ConstValue, Error> {
+		InitExpr::new(code).evaluate(globals)
+	}
+
+	#[test]
+	fn constants() {
+		use self::Instruction::*;
+
+		assert_eq!(evaluate(vec![I32Const(-5), End], &[]).unwrap(), ConstValue::I32(-5));
+		assert_eq!(evaluate(vec![I64Const(1 << 40), End], &[]).unwrap(), ConstValue::I64(1 << 40));
+		let value = evaluate(vec![F32Const(0x7fc0_0001), End], &[]).unwrap();
+		assert_eq!(value, ConstValue::F32(0x7fc0_0001));
+		assert_eq!(value.value_type(), ValueType::F32);
+		let value = evaluate(vec![F64Const(2.5f64.to_bits()), End], &[]).unwrap();
+		assert_eq!(value, ConstValue::F64(2.5f64.to_bits()));
+	}
+
+	#[test]
+	fn const_value_keeps_bits() {
+		let mut f32_bits = vec![0x7f80_0000, 0xff80_0000, 0x7fc0_0000, 0x7f80_0001, 0xffbf_ffff];
+		let mut f64_bits =
+			vec![0x7ff0_0000_0000_0000, 0x7ff8_0000_0000_0001, 0xfff7_ffff_ffff_ffff];
+		let mut state = 0x2545_f491_4f6c_dd1du64;
+		for _ in 0..256 {
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			// NaN payloads and infinities, with either sign.
+			f32_bits.push(state as u32 | 0x7f80_0000);
+			f64_bits.push(state | 0x7ff0_0000_0000_0000);
+		}
+
+		for bits in f32_bits {
+			let instruction = Instruction::f32_const(f32::from_bits(bits));
+			assert_eq!(instruction, Instruction::F32Const(bits));
+			assert_eq!(instruction.const_value(), Some(ConstValue::F32(bits)));
+		}
+		for bits in f64_bits {
+			let instruction = Instruction::f64_const(f64::from_bits(bits));
+			assert_eq!(instruction, Instruction::F64Const(bits));
+			assert_eq!(instruction.const_value(), Some(ConstValue::F64(bits)));
+		}
+		assert_eq!(Instruction::i32_const(-1).const_value(), Some(ConstValue::I32(-1)));
+		assert_eq!(Instruction::i64_const(i64::MIN).const_value(), Some(ConstValue::I64(i64::MIN)));
+		assert_eq!(Instruction::Nop.const_value(), None);
+	}
+
+	#[test]
+	fn get_global() {
+		use self::Instruction::*;
+
+		let imported = [ConstValue::I32(10), ConstValue::I64(20)];
+		assert_eq!(
+			evaluate(vec![GetGlobal(GlobalIdx(1)), End], &imported).unwrap(),
+			ConstValue::I64(20)
+		);
+		assert!(matches!(
+			evaluate(vec![GetGlobal(GlobalIdx(2)), End], &imported),
+			Err(Error::UnresolvedGlobal(2))
+		));
+
+		// Globals initialized from imported ones can in turn initialize segment offsets.
+		let global = evaluate(vec![GetGlobal(GlobalIdx(0)), End], &imported).unwrap();
+		let offset =
+			evaluate(vec![GetGlobal(GlobalIdx(2)), End], &[imported[0], imported[1], global]);
+		assert_eq!(offset.unwrap(), ConstValue::I32(10));
+	}
+
+	#[test]
+	fn rejects_non_constant() {
+		use self::Instruction::*;
+
+		let error = evaluate(vec![I32Const(1), I32Const(2), I32Add, End], &[]).unwrap_err();
+		assert!(matches!(error, Error::NonConstantInstruction(I32Add)));
+		assert_eq!(error.to_string(), "Instruction not allowed in constant expression: i32.add");
+
+		assert!(matches!(evaluate(vec![End], &[]), Err(Error::InvalidConstantExpression)));
+		assert!(matches!(
+			evaluate(vec![I32Const(1), I32Const(2), End], &[]),
+			Err(Error::InvalidConstantExpression)
+		));
+	}
+
+	#[test]
+	fn segment_offsets() {
+		use self::Instruction::*;
+
+		let relocatable = |mutable| {
+			builder::module()
+				.import()
+				.path("env", "__memory_base")
+				.external()
+				.global(ValueType::I32, mutable)
+				.build()
+				.memory()
+				.with_min(1)
+				.build()
+				.data()
+				.offset_global(0)
+				.value(b"hello".to_vec())
+				.build()
+				.table()
+				.with_min(1)
+				.with_element_global(0, vec![0])
+				.build()
+				.build()
+		};
+
+		let module = relocatable(false);
+		module.validate_segment_offsets().expect("offsets to be valid");
+		let segment = &module.data_section().unwrap().entries()[0];
+		let offset = segment.offset().as_ref().unwrap();
+		assert_eq!(offset.code(), [GetGlobal(GlobalIdx(0)), End]);
+		// The host supplies `__memory_base` on instantiation.
+		assert_eq!(offset.evaluate(&[ConstValue::I32(1024)][..]).unwrap(), ConstValue::I32(1024));
+		let segment = &module.elements_section().unwrap().entries()[0];
+		assert_eq!(segment.offset().as_ref().unwrap().code(), offset.code());
+
+		assert!(matches!(
+			relocatable(true).validate_segment_offsets(),
+			Err(Error::UnresolvedGlobal(0))
+		));
+		let mut module = relocatable(false);
+		*module.data_section_mut().unwrap().entries_mut()[0].offset_mut() =
+			Some(InitExpr::new(vec![I64Const(0), End]));
+		assert!(matches!(module.validate_segment_offsets(), Err(Error::InvalidConstantExpression)));
+		*module.data_section_mut().unwrap().entries_mut()[0].offset_mut() =
+			Some(InitExpr::new(vec![I32Const(1), I32Const(2), I32Add, End]));
+		assert!(matches!(
+			module.validate_segment_offsets(),
+			Err(Error::NonConstantInstruction(I32Add))
+		));
+	}
+}
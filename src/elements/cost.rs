@@ -0,0 +1,137 @@
+use super::Instruction;
+
+/// Cost of individual instructions, used for static cost estimates.
+pub trait CostModel {
+	/// Cost of executing the instruction once.
+	fn cost(&self, instruction: &Instruction) -> u64;
+}
+
+/// Cost model charging the same cost for every instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UniformCostModel {
+	cost: u64,
+}
+
+impl UniformCostModel {
+	/// New model charging `cost` for every instruction.
+	pub fn new(cost: u64) -> Self {
+		UniformCostModel { cost }
+	}
+}
+
+impl Default for UniformCostModel {
+	/// Every instruction costs `1`.
+	fn default() -> Self {
+		UniformCostModel::new(1)
+	}
+}
+
+impl CostModel for UniformCostModel {
+	fn cost(&self, _instruction: &Instruction) -> u64 {
+		self.cost
+	}
+}
+
+/// Static cost estimate of a function body.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CostSummary {
+	cost: u64,
+	loops: usize,
+	calls: usize,
+}
+
+impl CostSummary {
+	pub(crate) fn of(instructions: &[Instruction], model: &dyn CostModel) -> Self {
+		instructions.iter().fold(CostSummary::default(), |mut summary, instruction| {
+			summary.cost = summary.cost.saturating_add(model.cost(instruction));
+			match instruction {
+				Instruction::Loop(_) => summary.loops += 1,
+				Instruction::Call(_) | Instruction::CallIndirect(..) => summary.calls += 1,
+				_ => {},
+			}
+			summary
+		})
+	}
+
+	/// Cost of executing every instruction of the body exactly once.
+	///
+	/// This is the exact cost of the body only if it is [bounded](CostSummary::is_bounded).
+	pub fn cost(&self) -> u64 {
+		self.cost
+	}
+
+	/// Number of loops in the body.
+	pub fn loops(&self) -> usize {
+		self.loops
+	}
+
+	/// Number of direct and indirect calls in the body.
+	pub fn calls(&self) -> usize {
+		self.calls
+	}
+
+	/// Whether the cost of the body is bounded by [`CostSummary::cost`].
+	///
+	/// Loops can run any number of times and the cost of callees is not known
+	/// statically, so bodies with loops or calls are unbounded.
+	pub fn is_bounded(&self) -> bool {
+		self.loops == 0 && self.calls == 0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{BlockType, FuncBody, Instruction, Instructions},
+		CostModel, UniformCostModel,
+	};
+
+	struct ConstsAreFree;
+
+	impl CostModel for ConstsAreFree {
+		fn cost(&self, instruction: &Instruction) -> u64 {
+			match instruction {
+				Instruction::I32Const(_) => 0,
+				Instruction::I32Mul => 5,
+				_ => 1,
+			}
+		}
+	}
+
+	#[test]
+	fn straight_line_cost() {
+		use self::Instruction::*;
+
+		let code = vec![I32Const(2), I32Const(3), I32Mul, I32Const(1), I32Add, Drop, End];
+		let body = FuncBody::new(vec![], Instructions::new(code));
+
+		let uniform = body.static_cost(&UniformCostModel::default());
+		assert_eq!(uniform.cost(), 7);
+		assert!(uniform.is_bounded());
+
+		let summary = body.static_cost(&ConstsAreFree);
+		let expected: u64 = body.code().elements().iter().map(|i| ConstsAreFree.cost(i)).sum();
+		assert_eq!(summary.cost(), expected);
+		assert_eq!(summary.cost(), 8);
+		assert!(summary.is_bounded());
+	}
+
+	#[test]
+	fn loops_and_calls_are_unbounded() {
+		use self::Instruction::*;
+
+		let with_loop = FuncBody::new(
+			vec![],
+			Instructions::new(vec![Loop(BlockType::NoResult), Br(0.into()), End, End]),
+		);
+		let summary = with_loop.static_cost(&UniformCostModel::new(2));
+		assert_eq!(summary.cost(), 8);
+		assert_eq!(summary.loops(), 1);
+		assert!(!summary.is_bounded());
+
+		let with_call = FuncBody::new(vec![], Instructions::new(vec![Call(0.into()), End]));
+		let summary = with_call.static_cost(&UniformCostModel::default());
+		assert_eq!(summary.calls(), 1);
+		assert!(!summary.is_bounded());
+	}
+}
@@ -0,0 +1,265 @@
+use super::{FuncBody, Instruction, Module, Section, SectionKey};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Differences between two modules, see [`diff`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ModuleDiff<'a> {
+	/// Sections which differ, in the order of the first module, then sections only in the
+	/// second one.
+	pub sections: Vec<SectionDiff<'a>>,
+}
+
+impl ModuleDiff<'_> {
+	/// Whether the modules are equal.
+	pub fn is_empty(&self) -> bool {
+		self.sections.is_empty()
+	}
+}
+
+/// Differences between the entries of a section.
+///
+/// Entries are compared by index. Sections which are not lists, such as the start section and
+/// custom sections, have a single entry.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SectionDiff<'a> {
+	/// Identity of the section.
+	pub key: SectionKey<'a>,
+	/// Indices of the entries only in the second module.
+	pub added: Range<usize>,
+	/// Indices of the entries only in the first module.
+	pub removed: Range<usize>,
+	/// Entries in both modules which differ.
+	pub changed: Vec<ChangedEntry>,
+}
+
+impl SectionDiff<'_> {
+	/// Whether the section is the same in both modules.
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+	}
+}
+
+/// Entry of a section which differs between the modules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChangedEntry {
+	/// Index of the entry in the section.
+	pub index: usize,
+	/// Position of the first instruction which differs, for function bodies.
+	///
+	/// `None` if only the locals differ, and for bodies which cannot be decoded.
+	pub instruction: Option<usize>,
+}
+
+/// Structural differences between two modules.
+///
+/// Sections are matched with the first section of the other module with the same
+/// [`SectionKey`], so parsed custom sections only match parsed ones. Entries are matched by
+/// index, so an entry inserted in a section changes all the following ones.
+pub fn diff<'a>(a: &'a Module, b: &'a Module) -> ModuleDiff<'a> {
+	let find = |module: &'a Module, key| module.sections().iter().find(|s| s.key() == key);
+
+	let mut sections = Vec::new();
+	for section in a.sections() {
+		let key = section.key();
+		sections.push(section_diff(key, Some(section), find(b, key)));
+	}
+	for section in b.sections() {
+		let key = section.key();
+		if find(a, key).is_none() {
+			sections.push(section_diff(key, None, Some(section)));
+		}
+	}
+	sections.retain(|section| !section.is_empty());
+	ModuleDiff { sections }
+}
+
+fn section_diff<'a>(
+	key: SectionKey<'a>,
+	a: Option<&Section>,
+	b: Option<&Section>,
+) -> SectionDiff<'a> {
+	let a_len = a.map_or(0, entry_count);
+	let b_len = b.map_or(0, entry_count);
+	let common = a_len.min(b_len);
+	let changed = match (a, b) {
+		(Some(a), Some(b)) => changed_entries(a, b),
+		_ => Vec::new(),
+	};
+	SectionDiff { key, added: common..b_len, removed: common..a_len, changed }
+}
+
+fn entry_count(section: &Section) -> usize {
+	match *section {
+		Section::Type(ref section) => section.types().len(),
+		Section::Import(ref section) => section.entries().len(),
+		Section::Function(ref section) => section.entries().len(),
+		Section::Table(ref section) => section.entries().len(),
+		Section::Memory(ref section) => section.entries().len(),
+		#[cfg(feature = "exceptions")]
+		Section::Tag(ref section) => section.entries().len(),
+		Section::Global(ref section) => section.entries().len(),
+		Section::Export(ref section) => section.entries().len(),
+		Section::Element(ref section) => section.entries().len(),
+		Section::Code(ref section) => section.bodies().len(),
+		Section::Data(ref section) => section.entries().len(),
+		_ => 1,
+	}
+}
+
+fn changed_entries(a: &Section, b: &Section) -> Vec<ChangedEntry> {
+	match (a, b) {
+		(Section::Type(a), Section::Type(b)) => changed_in(a.types(), b.types()),
+		(Section::Import(a), Section::Import(b)) => changed_in(a.entries(), b.entries()),
+		(Section::Function(a), Section::Function(b)) => changed_in(a.entries(), b.entries()),
+		(Section::Table(a), Section::Table(b)) => changed_in(a.entries(), b.entries()),
+		(Section::Memory(a), Section::Memory(b)) => changed_in(a.entries(), b.entries()),
+		#[cfg(feature = "exceptions")]
+		(Section::Tag(a), Section::Tag(b)) => changed_in(a.entries(), b.entries()),
+		(Section::Global(a), Section::Global(b)) => changed_in(a.entries(), b.entries()),
+		(Section::Export(a), Section::Export(b)) => changed_in(a.entries(), b.entries()),
+		(Section::Element(a), Section::Element(b)) => changed_in(a.entries(), b.entries()),
+		(Section::Data(a), Section::Data(b)) => changed_in(a.entries(), b.entries()),
+		(Section::Code(a), Section::Code(b)) => a
+			.bodies()
+			.iter()
+			.zip(b.bodies())
+			.enumerate()
+			.filter(|(_, (a, b))| a != b)
+			.map(|(index, (a, b))| ChangedEntry { index, instruction: first_difference(a, b) })
+			.collect(),
+		_ if a == b => Vec::new(),
+		_ => vec![ChangedEntry { index: 0, instruction: None }],
+	}
+}
+
+fn changed_in<T: PartialEq>(a: &[T], b: &[T]) -> Vec<ChangedEntry> {
+	a.iter()
+		.zip(b)
+		.enumerate()
+		.filter(|(_, (a, b))| a != b)
+		.map(|(index, _)| ChangedEntry { index, instruction: None })
+		.collect()
+}
+
+fn first_difference(a: &FuncBody, b: &FuncBody) -> Option<usize> {
+	let (a, b): (&[Instruction], &[Instruction]) = match (a.try_code(), b.try_code()) {
+		(Ok(a), Ok(b)) => (a.elements(), b.elements()),
+		_ => return None,
+	};
+	match a.iter().zip(b).position(|(a, b)| a != b) {
+		Some(position) => Some(position),
+		None if a.len() != b.len() => Some(a.len().min(b.len())),
+		None => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{Instruction::*, Module, SectionKey},
+		diff, ChangedEntry,
+	};
+	use crate::builder;
+	use std::{
+		collections::hash_map::DefaultHasher,
+		hash::{Hash, Hasher},
+	};
+
+	fn module(constant: i32, nan: u32) -> Module {
+		builder::module()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.with_instructions(builder::code().f32_const(0.0).drop().end().build())
+			.build()
+			.build()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.with_instructions(
+				builder::code()
+					.with_instruction(F32Const(nan))
+					.drop()
+					.i32_const(constant)
+					.drop()
+					.end()
+					.build(),
+			)
+			.build()
+			.build()
+			.build()
+	}
+
+	fn hash(module: &Module) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		module.hash(&mut hasher);
+		hasher.finish()
+	}
+
+	#[test]
+	fn same_module() {
+		let module = module(1, 0x7fc0_0001);
+		assert!(diff(&module, &module).is_empty());
+		// NaN constants compare by their bits.
+		let other = self::module(1, 0x7fc0_0001);
+		assert!(diff(&module, &other).is_empty());
+		assert_eq!(module, other);
+		assert_eq!(hash(&module), hash(&other));
+		assert!(!diff(&module, &self::module(1, 0x7fc0_0002)).is_empty());
+	}
+
+	#[test]
+	fn changed_constant() {
+		let a = module(1, 0x7fc0_0000);
+		let b = module(2, 0x7fc0_0000);
+		let diff = diff(&a, &b);
+		assert_eq!(diff.sections.len(), 1);
+		let code = &diff.sections[0];
+		assert_eq!(code.key, SectionKey::Id(0x0a));
+		assert!(code.added.is_empty() && code.removed.is_empty());
+		assert_eq!(code.changed, [ChangedEntry { index: 1, instruction: Some(2) }]);
+	}
+
+	#[test]
+	fn added_entries() {
+		let a = module(1, 0);
+		let mut b = builder::from_module(a.clone())
+			.function()
+			.signature()
+			.build()
+			.body()
+			.build()
+			.build()
+			.export()
+			.field("main")
+			.internal()
+			.func(2)
+			.build()
+			.build();
+		b.set_custom_section("custom", vec![1]);
+
+		let diff = diff(&a, &b);
+		let sections: Vec<_> = diff
+			.sections
+			.iter()
+			.map(|s| (s.key, s.added.clone(), s.removed.clone()))
+			.collect();
+		assert_eq!(
+			sections,
+			[
+				(SectionKey::Id(0x03), 2..3, 2..2),
+				(SectionKey::Id(0x0a), 2..3, 2..2),
+				(SectionKey::Id(0x07), 0..1, 0..0),
+				(SectionKey::Custom("custom"), 0..1, 0..0),
+			]
+		);
+		assert!(diff.sections.iter().all(|s| s.changed.is_empty()));
+		// Sections are in the order of the first module.
+		let reversed = super::diff(&b, &a);
+		assert_eq!(reversed.sections[1].key, SectionKey::Id(0x07));
+		assert_eq!(reversed.sections[1].removed, 0..1);
+	}
+}
@@ -0,0 +1,275 @@
+use crate::io;
+use alloc::{string::String, vec::Vec};
+
+use super::{
+	section::ENTRIES_BUFFER_LENGTH, CountedList, CountedListWriter, CountedWriter, Deserialize,
+	Error, Serialize, VarUint32, VarUint7,
+};
+
+const WASM_DYLINK_MEM_INFO: u8 = 1;
+const WASM_DYLINK_NEEDED: u8 = 2;
+
+/// Dynamic linking metadata of a side module (the `dylink.0` custom section, or the
+/// legacy `dylink` one).
+///
+/// See the [tool conventions](https://github.com/WebAssembly/tool-conventions/blob/main/DynamicLinking.md).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DylinkSection {
+	format: DylinkFormat,
+	mem_info: DylinkMemInfo,
+	needed: Vec<String>,
+	other: Vec<(u8, Vec<u8>)>,
+}
+
+/// Encoding of the dynamic linking section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DylinkFormat {
+	/// Fixed layout of the `dylink` section, emitted by older toolchains.
+	Legacy,
+	/// Subsections of the `dylink.0` section.
+	Subsections,
+}
+
+/// Memory and table requirements of a side module.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DylinkMemInfo {
+	/// Size of the static data of the module in the memory, in bytes.
+	pub mem_size: u32,
+	/// Alignment of the static data, as a power of two.
+	pub mem_align: u32,
+	/// Number of table elements used by the module.
+	pub table_size: u32,
+	/// Alignment of the table elements, as a power of two.
+	pub table_align: u32,
+}
+
+impl DylinkSection {
+	/// Creates a new `dylink.0` section.
+	pub fn new(mem_info: DylinkMemInfo, needed: Vec<String>) -> Self {
+		DylinkSection { format: DylinkFormat::Subsections, mem_info, needed, other: Vec::new() }
+	}
+
+	/// Name of the custom section, depending on the format.
+	pub fn name(&self) -> &'static str {
+		match self.format {
+			DylinkFormat::Legacy => "dylink",
+			DylinkFormat::Subsections => "dylink.0",
+		}
+	}
+
+	/// Format the section is encoded in.
+	pub fn format(&self) -> DylinkFormat {
+		self.format
+	}
+
+	/// Memory and table requirements.
+	pub fn mem_info(&self) -> &DylinkMemInfo {
+		&self.mem_info
+	}
+
+	/// Memory and table requirements (mutable).
+	pub fn mem_info_mut(&mut self) -> &mut DylinkMemInfo {
+		&mut self.mem_info
+	}
+
+	/// Shared libraries the module depends on.
+	pub fn needed(&self) -> &[String] {
+		&self.needed
+	}
+
+	/// Shared libraries the module depends on (mutable).
+	pub fn needed_mut(&mut self) -> &mut Vec<String> {
+		&mut self.needed
+	}
+
+	/// Other subsections of a `dylink.0` section, such as export and import information,
+	/// kept verbatim as `(kind, payload)`.
+	pub fn other_subsections(&self) -> &[(u8, Vec<u8>)] {
+		&self.other
+	}
+
+	/// Decode the payload of the custom section with the given name, `None` if the name is
+	/// not the one of a dynamic linking section.
+	pub(crate) fn parse(name: &str, payload: &[u8]) -> Result<Option<Self>, Error> {
+		let mut rdr = io::Cursor::new(payload);
+		let section = match name {
+			"dylink" => DylinkSection {
+				format: DylinkFormat::Legacy,
+				mem_info: DylinkMemInfo::deserialize(&mut rdr)?,
+				needed: CountedList::<String>::deserialize(&mut rdr)?.into_inner(),
+				other: Vec::new(),
+			},
+			"dylink.0" => DylinkSection::deserialize(&mut rdr)?,
+			_ => return Ok(None),
+		};
+		if rdr.position() != payload.len() {
+			return Err(io::Error::InvalidData.into())
+		}
+		Ok(Some(section))
+	}
+}
+
+impl Deserialize for DylinkSection {
+	type Error = Error;
+
+	/// Deserialize the payload of a `dylink.0` section.
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		let mut section = DylinkSection::new(DylinkMemInfo::default(), Vec::new());
+		while let Ok(kind) = VarUint7::deserialize(rdr) {
+			let kind: u8 = kind.into();
+			let size: usize = VarUint32::deserialize(rdr)?.into();
+			let payload = buffered_read!(ENTRIES_BUFFER_LENGTH, size, rdr);
+
+			let mut payload_reader = io::Cursor::new(&payload[..]);
+			match kind {
+				WASM_DYLINK_MEM_INFO =>
+					section.mem_info = DylinkMemInfo::deserialize(&mut payload_reader)?,
+				WASM_DYLINK_NEEDED =>
+					section.needed =
+						CountedList::<String>::deserialize(&mut payload_reader)?.into_inner(),
+				_ => {
+					section.other.push((kind, payload));
+					continue
+				},
+			}
+			if payload_reader.position() != payload.len() {
+				return Err(io::Error::InvalidData.into())
+			}
+		}
+		Ok(section)
+	}
+}
+
+impl Serialize for DylinkSection {
+	type Error = Error;
+
+	/// Serialize the payload of the section, in its format.
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		let has_needed = !self.needed.is_empty();
+		let needed = CountedListWriter(self.needed.len(), self.needed.into_iter());
+		if self.format == DylinkFormat::Legacy {
+			self.mem_info.serialize(wtr)?;
+			return needed.serialize(wtr)
+		}
+
+		VarUint7::from(WASM_DYLINK_MEM_INFO).serialize(wtr)?;
+		let mut counted_writer = CountedWriter::new(wtr);
+		self.mem_info.serialize(&mut counted_writer)?;
+		counted_writer.done()?;
+
+		if has_needed {
+			VarUint7::from(WASM_DYLINK_NEEDED).serialize(wtr)?;
+			let mut counted_writer = CountedWriter::new(wtr);
+			needed.serialize(&mut counted_writer)?;
+			counted_writer.done()?;
+		}
+
+		for (kind, payload) in self.other {
+			VarUint7::from(kind).serialize(wtr)?;
+			VarUint32::from(payload.len()).serialize(wtr)?;
+			wtr.write(&payload)?;
+		}
+		Ok(())
+	}
+}
+
+impl Deserialize for DylinkMemInfo {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(DylinkMemInfo {
+			mem_size: VarUint32::deserialize(rdr)?.into(),
+			mem_align: VarUint32::deserialize(rdr)?.into(),
+			table_size: VarUint32::deserialize(rdr)?.into(),
+			table_align: VarUint32::deserialize(rdr)?.into(),
+		})
+	}
+}
+
+impl Serialize for DylinkMemInfo {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		VarUint32::from(self.mem_size).serialize(wtr)?;
+		VarUint32::from(self.mem_align).serialize(wtr)?;
+		VarUint32::from(self.table_size).serialize(wtr)?;
+		VarUint32::from(self.table_align).serialize(wtr)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{Module, Section},
+		DylinkFormat, DylinkMemInfo, DylinkSection,
+	};
+
+	/// `dylink.0` payload laid out like the one of an emscripten side module.
+	fn payload() -> Vec<u8> {
+		let mut payload = Vec::new();
+		// Memory info: 1024 bytes aligned to 16, 2 table elements.
+		payload.extend_from_slice(&[1, 5, 0x80, 0x08, 4, 2, 0]);
+		// Needed libraries.
+		payload.extend_from_slice(&[2, 9, 1, 7]);
+		payload.extend_from_slice(b"libc.so");
+		// Export info of `foo`, with the TLS flag.
+		payload.extend_from_slice(&[3, 6, 1, 3, b'f', b'o', b'o', 0x04]);
+		payload
+	}
+
+	fn module(name: &str, payload: Vec<u8>) -> Module {
+		let mut module = Module::default();
+		module.set_custom_section(name, payload);
+		module
+	}
+
+	#[test]
+	fn dylink_section() {
+		let module = module("dylink.0", payload()).parse_dylink().expect("section to parse");
+		let dylink = module.dylink_section().expect("section was parsed");
+		assert_eq!(dylink.format(), DylinkFormat::Subsections);
+		assert_eq!(
+			*dylink.mem_info(),
+			DylinkMemInfo { mem_size: 1024, mem_align: 4, table_size: 2, table_align: 0 }
+		);
+		assert_eq!(dylink.needed(), ["libc.so"]);
+		assert_eq!(dylink.other_subsections(), [(3, vec![1, 3, b'f', b'o', b'o', 0x04])]);
+
+		let bytes = module.clone().into_bytes().expect("module to serialize");
+		assert_eq!(bytes, self::module("dylink.0", payload()).into_bytes().unwrap());
+		let module = Module::from_bytes(bytes).unwrap().parse_dylink().unwrap();
+		assert_eq!(module.dylink_section().map(DylinkSection::name), Some("dylink.0"));
+	}
+
+	#[test]
+	fn legacy_dylink_section() {
+		let payload = vec![0x80, 0x08, 4, 2, 0, 1, 3, b'l', b'i', b'b'];
+		let module = module("dylink", payload.clone()).parse_dylink().expect("section to parse");
+		let dylink = module.dylink_section().expect("section was parsed");
+		assert_eq!(dylink.format(), DylinkFormat::Legacy);
+		assert_eq!(dylink.mem_info().mem_size, 1024);
+		assert_eq!(dylink.needed(), ["lib"]);
+		assert_eq!(
+			module.into_bytes().unwrap(),
+			self::module("dylink", payload).into_bytes().unwrap()
+		);
+
+		// Trailing bytes are rejected.
+		let (errors, module) = self::module("dylink", vec![0; 6]).parse_dylink().unwrap_err();
+		assert_eq!(errors.len(), 1);
+		assert!(matches!(module.sections()[0], Section::Custom(_)));
+	}
+
+	#[test]
+	fn new_dylink_section() {
+		let mut dylink = DylinkSection::new(DylinkMemInfo::default(), vec![]);
+		dylink.mem_info_mut().table_size = 1;
+		dylink.needed_mut().push("libm.so".into());
+		let mut module = Module::default();
+		module.sections_mut().push(Section::Dylink(dylink.clone()));
+
+		let module = Module::from_bytes(module.into_bytes().unwrap()).unwrap();
+		let module = module.parse_dylink().expect("section to parse");
+		assert_eq!(module.dylink_section(), Some(&dylink));
+	}
+}
@@ -0,0 +1,333 @@
+use super::{Error, Instruction, Instructions};
+use alloc::vec::Vec;
+
+impl Instruction {
+	/// Whether this instruction splits the innermost block, i.e. `Else`, `Catch` or `CatchAll`.
+	fn splits_block(&self) -> bool {
+		match self {
+			Instruction::Else => true,
+			#[cfg(feature = "exceptions")]
+			Instruction::Exceptions(super::ExceptionsInstruction::Catch(_)) |
+			Instruction::Exceptions(super::ExceptionsInstruction::CatchAll) => true,
+			_ => false,
+		}
+	}
+}
+
+/// Check that every block opened by the instructions is closed by them, and that they don't
+/// close or split an enclosing block.
+fn check_balanced(instructions: &[Instruction]) -> Result<(), Error> {
+	let mut depth = 0usize;
+	for (position, instruction) in instructions.iter().enumerate() {
+		if instruction.is_block() {
+			depth += 1;
+		} else if instruction.closes_block() {
+			depth = depth.checked_sub(1).ok_or(Error::UnbalancedBlocks(position))?;
+		} else if instruction.splits_block() && depth == 0 {
+			return Err(Error::UnbalancedBlocks(position))
+		}
+	}
+	match depth {
+		0 => Ok(()),
+		_ => Err(Error::UnbalancedBlocks(instructions.len())),
+	}
+}
+
+/// Number of instructions of the item starting at `start`: the whole block for a block, up to
+/// its closing `end`.
+fn item_len(instructions: &[Instruction], start: usize) -> Result<usize, Error> {
+	if !instructions[start].is_block() {
+		return Ok(1)
+	}
+	let mut depth = 0usize;
+	for (offset, instruction) in instructions[start..].iter().enumerate() {
+		if instruction.is_block() {
+			depth += 1;
+		} else if instruction.closes_block() {
+			depth -= 1;
+			if depth == 0 {
+				return Ok(offset + 1)
+			}
+		}
+	}
+	Err(Error::UnbalancedBlocks(instructions.len()))
+}
+
+/// Position of the instruction at `path`, see [`Instructions::insert`].
+///
+/// If `allow_end` is set, the last index of the path may also address the `end` closing the
+/// block.
+fn resolve(instructions: &[Instruction], path: &[usize], allow_end: bool) -> Result<usize, Error> {
+	let (&last, blocks) = path.split_last().ok_or(Error::InvalidInstructionPath)?;
+	let mut position = 0;
+	for (level, &index) in blocks.iter().chain(Some(&last)).enumerate() {
+		for _ in 0..index {
+			match instructions.get(position) {
+				Some(instruction) if !instruction.closes_block() =>
+					position += item_len(instructions, position)?,
+				_ => return Err(Error::InvalidInstructionPath),
+			}
+		}
+		let instruction = instructions.get(position).ok_or(Error::InvalidInstructionPath)?;
+		if level < blocks.len() {
+			if !instruction.is_block() {
+				return Err(Error::InvalidInstructionPath)
+			}
+			position += 1;
+		} else if instruction.closes_block() && !allow_end {
+			return Err(Error::InvalidInstructionPath)
+		}
+	}
+	Ok(position)
+}
+
+impl Instructions {
+	/// Insert instructions before the one at `path`.
+	///
+	/// The path addresses into nested blocks: each index but the last picks a block among the
+	/// instructions of the enclosing one, where a nested block counts as a single instruction,
+	/// and the last index picks the instruction within the innermost block. An `else` counts as
+	/// an instruction of its `if`. The last index may also be the number of instructions of
+	/// the block, to insert them before its closing `end`.
+	///
+	/// The inserted instructions must close the blocks they open, and not contain an `end`
+	/// or an `else` of the enclosing blocks, so that blocks stay balanced. Fails with
+	/// [`Error::UnbalancedBlocks`] otherwise, and with [`Error::InvalidInstructionPath`] if
+	/// the path doesn't address an instruction.
+	///
+	/// ```
+	/// use parity_wasm::elements::{BlockType, Instruction::*, Instructions};
+	///
+	/// // Instructions 0 and 1 of the function, then instruction 0 of the loop.
+	/// let mut code = Instructions::new(vec![
+	///     I32Const(1),
+	///     If(BlockType::NoResult),
+	///     Loop(BlockType::NoResult),
+	///     Nop,
+	///     End,
+	///     End,
+	///     End,
+	/// ]);
+	/// code.insert(&[1, 0, 0], &[I32Const(2), Drop]).unwrap();
+	/// assert_eq!(code.elements()[3..6], [I32Const(2), Drop, Nop]);
+	/// ```
+	pub fn insert(&mut self, path: &[usize], instructions: &[Instruction]) -> Result<(), Error> {
+		check_balanced(instructions)?;
+		let position = resolve(self.elements(), path, true)?;
+		self.elements_mut().splice(position..position, instructions.iter().cloned());
+		Ok(())
+	}
+
+	/// Remove `count` instructions from the one at `path`, nested blocks counting as one
+	/// instruction, see [`Instructions::insert`].
+	///
+	/// Returns the removed instructions. The closing `end` of the enclosing block can't be
+	/// removed, and neither can an `else` or `catch` splitting it, which fails with
+	/// [`Error::UnbalancedBlocks`].
+	pub fn remove(&mut self, path: &[usize], count: usize) -> Result<Vec<Instruction>, Error> {
+		let code = self.elements_mut();
+		let start = resolve(code, path, true)?;
+		let mut end = start;
+		for _ in 0..count {
+			match code.get(end) {
+				Some(instruction) if !instruction.closes_block() => end += item_len(code, end)?,
+				_ => return Err(Error::InvalidInstructionPath),
+			}
+		}
+		check_balanced(&code[start..end])?;
+		Ok(code.drain(start..end).collect())
+	}
+
+	/// Replace the instruction at `path`, or the whole block if it starts one, with the given
+	/// instructions, see [`Instructions::insert`].
+	///
+	/// Returns the replaced instructions. An `else` or `catch` splitting the enclosing block
+	/// can't be replaced, which fails with [`Error::UnbalancedBlocks`].
+	pub fn replace(
+		&mut self,
+		path: &[usize],
+		instructions: &[Instruction],
+	) -> Result<Vec<Instruction>, Error> {
+		check_balanced(instructions)?;
+		let code = self.elements_mut();
+		let start = resolve(code, path, false)?;
+		let end = start + item_len(code, start)?;
+		check_balanced(&code[start..end])?;
+		Ok(code.splice(start..end, instructions.iter().cloned()).collect())
+	}
+
+	/// Cursor at the first instruction, for sequential edits.
+	pub fn cursor(&mut self) -> Cursor<'_> {
+		Cursor::new(self.elements_mut())
+	}
+}
+
+/// Position in a list of instructions, kept across the edits made through it.
+///
+/// Instructions are visited in order, nested blocks included. Edits keep blocks balanced like
+/// those of [`Instructions::insert`].
+pub struct Cursor<'a> {
+	instructions: &'a mut Vec<Instruction>,
+	position: usize,
+}
+
+impl<'a> Cursor<'a> {
+	/// Cursor at the first of the instructions.
+	pub fn new(instructions: &'a mut Vec<Instruction>) -> Self {
+		Cursor { instructions, position: 0 }
+	}
+
+	/// Index of the current instruction.
+	pub fn position(&self) -> usize {
+		self.position
+	}
+
+	/// Current instruction, `None` past the last one.
+	pub fn current(&self) -> Option<&Instruction> {
+		self.instructions.get(self.position)
+	}
+
+	/// Instruction `offset` positions after the current one, `None` past the last one.
+	pub fn peek(&self, offset: usize) -> Option<&Instruction> {
+		self.instructions.get(self.position.checked_add(offset)?)
+	}
+
+	/// Move to the next instruction.
+	pub fn advance(&mut self) {
+		self.position = (self.position + 1).min(self.instructions.len());
+	}
+
+	/// Move to the previous instruction, staying at the first one.
+	pub fn retreat(&mut self) {
+		self.position = self.position.saturating_sub(1);
+	}
+
+	/// Insert instructions before the current one, which stays current.
+	pub fn insert(&mut self, instructions: &[Instruction]) -> Result<(), Error> {
+		check_balanced(instructions)?;
+		if self.position == self.instructions.len() {
+			return Err(Error::InvalidInstructionPath)
+		}
+		let position = self.position;
+		self.instructions.splice(position..position, instructions.iter().cloned());
+		self.position += instructions.len();
+		Ok(())
+	}
+
+	/// Remove `count` instructions from the current one, the next one becomes current.
+	///
+	/// Returns the removed instructions, which must not close or split a block they don't
+	/// open.
+	pub fn remove(&mut self, count: usize) -> Result<Vec<Instruction>, Error> {
+		let end = self.position.checked_add(count).filter(|&end| end <= self.instructions.len());
+		let end = end.ok_or(Error::InvalidInstructionPath)?;
+		check_balanced(&self.instructions[self.position..end])?;
+		Ok(self.instructions.drain(self.position..end).collect())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::{BlockType, Error, Instruction::*, Instructions};
+
+	fn code() -> Instructions {
+		Instructions::new(vec![
+			I32Const(1),
+			If(BlockType::NoResult),
+			Nop,
+			Loop(BlockType::NoResult),
+			Br(0.into()),
+			End,
+			Else,
+			Unreachable,
+			End,
+			End,
+		])
+	}
+
+	#[test]
+	fn insert_in_nested_loop() {
+		let mut code = code();
+		code.insert(&[1, 1, 0], &[Block(BlockType::NoResult), Nop, End]).unwrap();
+		assert_eq!(
+			code.elements()[3..8],
+			[Loop(BlockType::NoResult), Block(BlockType::NoResult), Nop, End, Br(0.into())]
+		);
+		// Before the `end` of the loop, and at the end of the function.
+		code.insert(&[1, 1, 2], &[Nop]).unwrap();
+		assert_eq!(code.elements()[8..10], [Nop, End]);
+		code.insert(&[2], &[Drop]).unwrap();
+		assert_eq!(code.elements()[code.elements().len() - 2..], [Drop, End]);
+	}
+
+	#[test]
+	fn unbalanced_edits() {
+		let mut code = code();
+		let original = code.clone();
+		assert!(matches!(code.insert(&[1, 0], &[End]), Err(Error::UnbalancedBlocks(0))));
+		assert!(matches!(code.insert(&[1, 0], &[Nop, Else]), Err(Error::UnbalancedBlocks(1))));
+		assert!(matches!(
+			code.insert(&[0], &[Block(BlockType::NoResult)]),
+			Err(Error::UnbalancedBlocks(1))
+		));
+		// Not a block, past the end of the block, and the `end` of the function.
+		assert!(matches!(code.insert(&[0, 0], &[Nop]), Err(Error::InvalidInstructionPath)));
+		assert!(matches!(code.insert(&[3], &[Nop]), Err(Error::InvalidInstructionPath)));
+		assert!(matches!(code.remove(&[2], 1), Err(Error::InvalidInstructionPath)));
+		// The `else` of the `if`.
+		assert!(matches!(code.remove(&[1, 2], 1), Err(Error::UnbalancedBlocks(0))));
+		assert!(matches!(code.remove(&[1, 1], 2), Err(Error::UnbalancedBlocks(3))));
+		assert!(matches!(code.replace(&[1, 2], &[Nop]), Err(Error::UnbalancedBlocks(0))));
+		assert!(matches!(code.replace(&[2], &[]), Err(Error::InvalidInstructionPath)));
+		assert_eq!(code, original);
+	}
+
+	#[test]
+	fn remove_and_replace() {
+		let mut code = code();
+		// The loop goes as a whole.
+		assert_eq!(
+			code.remove(&[1, 1], 1).unwrap(),
+			[Loop(BlockType::NoResult), Br(0.into()), End]
+		);
+		assert_eq!(code.replace(&[1, 2], &[Nop, Nop]).unwrap(), [Unreachable]);
+		assert_eq!(
+			code.elements(),
+			[I32Const(1), If(BlockType::NoResult), Nop, Else, Nop, Nop, End, End]
+		);
+		assert_eq!(code.remove(&[0], 2).unwrap().len(), 7);
+		assert_eq!(code.elements(), [End]);
+	}
+
+	#[test]
+	fn cursor() {
+		let mut code = code();
+		let mut cursor = code.cursor();
+		while let Some(instruction) = cursor.current() {
+			match *instruction {
+				Nop => {
+					cursor.remove(1).unwrap();
+				},
+				Br(_) => {
+					cursor.insert(&[I32Const(0), Drop]).unwrap();
+					cursor.advance();
+				},
+				_ => cursor.advance(),
+			}
+		}
+		assert!(matches!(cursor.insert(&[Nop]), Err(Error::InvalidInstructionPath)));
+		assert_eq!(
+			code.elements()[2..6],
+			[Loop(BlockType::NoResult), I32Const(0), Drop, Br(0.into())]
+		);
+
+		let mut cursor = code.cursor();
+		cursor.retreat();
+		cursor.advance();
+		assert_eq!(cursor.peek(1), Some(&Loop(BlockType::NoResult)));
+		assert_eq!(cursor.peek(usize::MAX), None);
+		assert!(matches!(cursor.remove(1), Err(Error::UnbalancedBlocks(1))));
+		assert_eq!(cursor.remove(9).unwrap().len(), 9);
+		assert_eq!(code.elements(), [I32Const(1), End]);
+	}
+}
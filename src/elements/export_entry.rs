@@ -1,18 +1,25 @@
-use super::{Deserialize, Error, Serialize, VarUint32, VarUint7};
+#[cfg(feature = "exceptions")]
+use super::TagIdx;
+use super::{
+	Deserialize, Error, FuncIdx, GlobalIdx, MemIdx, Serialize, TableIdx, VarUint32, VarUint7,
+};
 use crate::io;
 use alloc::string::String;
 
 /// Internal reference of the exported entry.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Internal {
 	/// Function reference.
-	Function(u32),
+	Function(FuncIdx),
 	/// Table reference.
-	Table(u32),
+	Table(TableIdx),
 	/// Memory reference.
-	Memory(u32),
+	Memory(MemIdx),
 	/// Global reference.
-	Global(u32),
+	Global(GlobalIdx),
+	/// Exception tag reference.
+	#[cfg(feature = "exceptions")]
+	Tag(TagIdx),
 }
 
 impl Deserialize for Internal {
@@ -25,6 +32,8 @@ impl Deserialize for Internal {
 			0x01 => Ok(Internal::Table(VarUint32::deserialize(reader)?.into())),
 			0x02 => Ok(Internal::Memory(VarUint32::deserialize(reader)?.into())),
 			0x03 => Ok(Internal::Global(VarUint32::deserialize(reader)?.into())),
+			#[cfg(feature = "exceptions")]
+			0x04 => Ok(Internal::Tag(VarUint32::deserialize(reader)?.into())),
 			_ => Err(Error::UnknownInternalKind(kind.into())),
 		}
 	}
@@ -34,11 +43,13 @@ impl Serialize for Internal {
 	type Error = Error;
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
-		let (bt, arg) = match self {
-			Internal::Function(arg) => (0x00, arg),
-			Internal::Table(arg) => (0x01, arg),
-			Internal::Memory(arg) => (0x02, arg),
-			Internal::Global(arg) => (0x03, arg),
+		let (bt, arg): (u8, u32) = match self {
+			Internal::Function(arg) => (0x00, arg.into()),
+			Internal::Table(arg) => (0x01, arg.into()),
+			Internal::Memory(arg) => (0x02, arg.into()),
+			Internal::Global(arg) => (0x03, arg.into()),
+			#[cfg(feature = "exceptions")]
+			Internal::Tag(arg) => (0x04, arg.into()),
 		};
 
 		VarUint7::from(bt).serialize(writer)?;
@@ -49,7 +60,7 @@ impl Serialize for Internal {
 }
 
 /// Export entry.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ExportEntry {
 	field_str: String,
 	internal: Internal,
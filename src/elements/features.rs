@@ -0,0 +1,332 @@
+use super::{
+	proposals::{
+		BULK, EXCEPTIONS, MULTI_VALUE, REFERENCE_TYPES, SATURATING_FLOAT_TO_INT, SIGN_EXT, SIMD,
+		THREADS,
+	},
+	Error, External, ImportCountType, Instruction, Module, Proposal, Section, Type,
+};
+use alloc::{borrow::ToOwned, string::String};
+
+#[cfg(feature = "multi_value")]
+use super::BlockType;
+
+/// Post-MVP proposals a module may use, see [`Module::check_features`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Features {
+	/// Sign-extension operators.
+	pub sign_ext: bool,
+	/// Non-trapping float-to-int conversions.
+	pub saturating_float_to_int: bool,
+	/// Bulk memory operations, passive segments included.
+	pub bulk_memory: bool,
+	/// Reference types, several tables included.
+	pub reference_types: bool,
+	/// Several function results and block types referring to signatures.
+	pub multi_value: bool,
+	/// Fixed-width SIMD.
+	pub simd: bool,
+	/// Threads: atomic instructions and shared memories.
+	pub threads: bool,
+	/// Exception handling.
+	pub exceptions: bool,
+}
+
+impl Features {
+	/// No proposal at all.
+	pub fn mvp() -> Self {
+		Features {
+			sign_ext: false,
+			saturating_float_to_int: false,
+			bulk_memory: false,
+			reference_types: false,
+			multi_value: false,
+			simd: false,
+			threads: false,
+			exceptions: false,
+		}
+	}
+
+	/// Every proposal.
+	pub fn all() -> Self {
+		Features {
+			sign_ext: true,
+			saturating_float_to_int: true,
+			bulk_memory: true,
+			reference_types: true,
+			multi_value: true,
+			simd: true,
+			threads: true,
+			exceptions: true,
+		}
+	}
+
+	fn supports(&self, proposal: Proposal) -> bool {
+		let features = [
+			(SIGN_EXT, self.sign_ext),
+			(SATURATING_FLOAT_TO_INT, self.saturating_float_to_int),
+			(BULK, self.bulk_memory),
+			(REFERENCE_TYPES, self.reference_types),
+			(MULTI_VALUE, self.multi_value),
+			(SIMD, self.simd),
+			(THREADS, self.threads),
+			(EXCEPTIONS, self.exceptions),
+		];
+		features.iter().any(|&(feature, enabled)| enabled && feature == proposal)
+	}
+
+	fn require(&self, proposal: Proposal, construct: impl FnOnce() -> String) -> Result<(), Error> {
+		if self.supports(proposal) {
+			return Ok(())
+		}
+		Err(Error::UnsupportedFeature { construct: construct(), proposal: proposal.name })
+	}
+}
+
+impl Default for Features {
+	/// Every proposal.
+	fn default() -> Self {
+		Features::all()
+	}
+}
+
+impl Module {
+	/// Check that the module only uses the given proposals.
+	///
+	/// Instructions are checked by opcode, along with `call_indirect` of another table than
+	/// the first one and blocks typed by a signature. Function types with several results,
+	/// several tables, shared memories, tags, non-active segments and the data count section
+	/// are also checked. The first construct of a missing proposal is reported as
+	/// [`Error::UnsupportedFeature`]. Lazily deserialized bodies are decoded on the side.
+	pub fn check_features(&self, features: &Features) -> Result<(), Error> {
+		let imports = self.import_section().map_or(&[][..], |section| section.entries());
+		let imported_functions = self.import_count(ImportCountType::Function);
+		let mut tables = 0;
+		for entry in imports {
+			match *entry.external() {
+				External::Table(_) => {
+					if tables > 0 {
+						features
+							.require(REFERENCE_TYPES, || format!("table `{}`", entry.field()))?;
+					}
+					tables += 1;
+				},
+				#[cfg(feature = "atomics")]
+				External::Memory(ref memory) if memory.limits().shared() =>
+					features.require(THREADS, || format!("shared memory `{}`", entry.field()))?,
+				#[cfg(feature = "exceptions")]
+				External::Tag(_) => features.require(EXCEPTIONS, || format!("tag `{}`", entry.field()))?,
+				_ => {},
+			}
+		}
+
+		for section in self.sections() {
+			match *section {
+				Section::Type(ref types) =>
+					for (index, Type::Function(func_type)) in types.types().iter().enumerate() {
+						if func_type.results().len() > 1 {
+							features.require(MULTI_VALUE, || {
+								format!("type {} with {} results", index, func_type.results().len())
+							})?;
+						}
+					},
+				Section::Table(ref section) =>
+					for _ in section.entries() {
+						if tables > 0 {
+							features.require(REFERENCE_TYPES, || format!("table {}", tables))?;
+						}
+						tables += 1;
+					},
+				#[cfg(feature = "atomics")]
+				Section::Memory(ref section) =>
+					for (index, memory) in section.entries().iter().enumerate() {
+						if memory.limits().shared() {
+							features.require(THREADS, || format!("shared memory {}", index))?;
+						}
+					},
+				#[cfg(feature = "exceptions")]
+				Section::Tag(_) => features.require(EXCEPTIONS, || "tag section".to_owned())?,
+				#[cfg(feature = "bulk")]
+				Section::Element(ref section) =>
+					for (index, segment) in section.entries().iter().enumerate() {
+						if segment.mode() != super::SegmentMode::Active {
+							features.require(BULK, || format!("element segment {}", index))?;
+						}
+					},
+				#[cfg(feature = "bulk")]
+				Section::Data(ref section) =>
+					for (index, segment) in section.entries().iter().enumerate() {
+						if segment.mode() != super::SegmentMode::Active {
+							features.require(BULK, || format!("data segment {}", index))?;
+						}
+					},
+				Section::DataCount(_) =>
+					features.require(BULK, || "data count section".to_owned())?,
+				Section::Code(ref section) =>
+					for (defined, body) in section.bodies().iter().enumerate() {
+						let index = imported_functions + defined;
+						for instruction in body.try_code()?.elements() {
+							check_instruction(instruction, index, features)?;
+						}
+					},
+				_ => {},
+			}
+		}
+		Ok(())
+	}
+}
+
+fn check_instruction(
+	instruction: &Instruction,
+	function: usize,
+	features: &Features,
+) -> Result<(), Error> {
+	let construct = || format!("`{}` in function {}", instruction, function);
+	if let Some(proposal) = Proposal::of(instruction) {
+		features.require(proposal, construct)?;
+	}
+	match *instruction {
+		Instruction::CallIndirect(_, table_ref) if table_ref != 0 =>
+			features.require(REFERENCE_TYPES, construct),
+		#[cfg(feature = "multi_value")]
+		Instruction::Block(BlockType::TypeIndex(_)) |
+		Instruction::Loop(BlockType::TypeIndex(_)) |
+		Instruction::If(BlockType::TypeIndex(_)) => features.require(MULTI_VALUE, construct),
+		_ => Ok(()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{Error, Instruction, Module, ValueType},
+		Features,
+	};
+	use crate::builder;
+
+	fn function(instruction: Instruction) -> Module {
+		builder::module()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.code()
+			.with_instruction(instruction)
+			.end()
+			.build()
+			.build()
+			.build()
+			.build()
+	}
+
+	fn rejected(module: &Module, features: &Features) -> Option<(String, &'static str)> {
+		match module.check_features(features) {
+			Ok(()) => None,
+			Err(Error::UnsupportedFeature { construct, proposal }) => Some((construct, proposal)),
+			Err(err) => panic!("unexpected error: {}", err),
+		}
+	}
+
+	#[test]
+	fn mvp_module() {
+		let module = function(Instruction::Nop);
+		assert_eq!(rejected(&module, &Features::mvp()), None);
+		assert_eq!(rejected(&Module::default(), &Features::mvp()), None);
+	}
+
+	#[test]
+	fn instructions() {
+		let module = function(Instruction::CallIndirect(0.into(), 1));
+		assert_eq!(
+			rejected(&module, &Features::mvp()),
+			Some(("`call_indirect 0` in function 0".into(), "reference types"))
+		);
+		let features = Features { reference_types: true, ..Features::mvp() };
+		assert_eq!(rejected(&module, &features), None);
+	}
+
+	#[cfg(feature = "sign_ext")]
+	#[test]
+	fn sign_ext() {
+		use super::super::SignExtInstruction;
+
+		let module = function(Instruction::SignExt(SignExtInstruction::I32Extend8S));
+		let error = module.check_features(&Features::mvp()).unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"`i32.extend8_s` in function 0 requires the sign-extension operators proposal"
+		);
+		let features = Features { sign_ext: true, ..Features::mvp() };
+		assert_eq!(rejected(&module, &features), None);
+	}
+
+	#[test]
+	fn multi_value() {
+		let module = builder::module()
+			.function()
+			.signature()
+			.with_results(vec![ValueType::I32, ValueType::I64])
+			.build()
+			.body()
+			.build()
+			.build()
+			.build();
+		assert_eq!(
+			rejected(&module, &Features::mvp()),
+			Some(("type 0 with 2 results".into(), "multi-value"))
+		);
+		let features = Features { multi_value: true, ..Features::mvp() };
+		assert_eq!(rejected(&module, &features), None);
+	}
+
+	#[test]
+	fn tables() {
+		let module = builder::module().table().build().table().build().build();
+		let error = Some(("table 1".into(), "reference types"));
+		assert_eq!(rejected(&module, &Features::mvp()), error);
+		assert_eq!(rejected(&module, &Features::all()), None);
+
+		let module = builder::module()
+			.import()
+			.path("env", "a")
+			.external()
+			.table(1, None)
+			.build()
+			.import()
+			.path("env", "b")
+			.external()
+			.table(1, None)
+			.build()
+			.build();
+		let error = Some(("table `b`".into(), "reference types"));
+		assert_eq!(rejected(&module, &Features::mvp()), error);
+		assert_eq!(rejected(&module, &Features::all()), None);
+	}
+
+	#[cfg(feature = "bulk")]
+	#[test]
+	fn passive_segments() {
+		let module = builder::module().data().passive().value(vec![1]).build().build();
+		assert_eq!(
+			rejected(&module, &Features::mvp()),
+			Some(("data segment 0".into(), "bulk memory operations"))
+		);
+		let features = Features { bulk_memory: true, ..Features::mvp() };
+		assert_eq!(rejected(&module, &features), None);
+	}
+
+	#[cfg(feature = "atomics")]
+	#[test]
+	fn shared_memory() {
+		use super::super::{MemorySection, MemoryType, Section};
+
+		let mut memory = MemoryType::new(1, Some(1));
+		memory.set_shared(true);
+		let module = Module::new(vec![Section::Memory(MemorySection::with_entries(vec![memory]))]);
+		assert_eq!(
+			rejected(&module, &Features::mvp()),
+			Some(("shared memory 0".into(), "threads"))
+		);
+		let features = Features { threads: true, ..Features::mvp() };
+		assert_eq!(rejected(&module, &features), None);
+	}
+}
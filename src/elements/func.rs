@@ -1,12 +1,17 @@
 use super::{
-	CountedList, CountedListWriter, CountedWriter, Deserialize, Error, Instructions, Serialize,
-	ValueType, VarUint32,
+	serialize, CostModel, CostSummary, CountedList, CountedListWriter, CountedWriter, Deserialize,
+	Error, FunctionType, Instruction, Instructions, Serialize, ValueType, VarUint32,
 };
 use crate::{elements::section::SectionReader, io};
-use alloc::vec::Vec;
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+	hash::{Hash, Hasher},
+	iter,
+};
+use once_cell::race::OnceBox;
 
 /// Function signature (type reference)
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Func(u32);
 
 impl Func {
@@ -43,7 +48,7 @@ impl Deserialize for Func {
 }
 
 /// Local definition inside the function body.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Local {
 	count: u32,
 	value_type: ValueType,
@@ -87,21 +92,174 @@ impl Serialize for Local {
 }
 
 /// Function body definition.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct FuncBody {
 	locals: Vec<Local>,
-	instructions: Instructions,
+	instructions: FuncCode,
+	padding: Option<Box<Padding>>,
+}
+
+impl PartialEq for FuncBody {
+	/// The original encoding of padded integers is left out.
+	fn eq(&self, other: &Self) -> bool {
+		self.locals == other.locals && self.instructions == other.instructions
+	}
+}
+
+impl Eq for FuncBody {}
+
+impl Hash for FuncBody {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.locals.hash(state);
+		self.instructions.hash(state);
+	}
+}
+
+/// Original encoding of the parts of a body with padded integers, kept for
+/// [`LebMode::Preserve`](super::limits::LebMode::Preserve).
+///
+/// Locals and instructions are written back from their original bytes as long as they are
+/// unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Padding {
+	/// Width of the body size.
+	size_width: Option<usize>,
+	/// Declared locals and their bytes.
+	locals: Option<(Vec<Local>, Vec<u8>)>,
+	/// Position, value and bytes of the padded instructions.
+	instructions: Vec<(usize, Instruction, Vec<u8>)>,
+}
+
+impl Padding {
+	/// Whether a body, taking `encoded_len` bytes with its size, has padded integers.
+	///
+	/// Cheaper than [`Padding::find`], the body is only serialized once.
+	pub(crate) fn is_padded(body: &FuncBody, encoded_len: usize) -> Result<bool, Error> {
+		Ok(encoded_len > serialize(body.clone())?.len())
+	}
+
+	/// Padded parts of a body decoded from `payload`, whose size took `size_width` bytes.
+	pub(crate) fn find(
+		body: &FuncBody,
+		payload: &[u8],
+		size_width: usize,
+	) -> Result<Option<Self>, Error> {
+		let mut padding = Padding::default();
+		if size_width > serialize(VarUint32::from(payload.len()))?.len() {
+			padding.size_width = Some(size_width);
+		}
+
+		let mut reader = io::Cursor::new(payload);
+		FuncBody::deserialize_locals(&mut reader)?;
+		let locals_end = reader.position();
+		let locals = CountedListWriter::<Local, _>(body.locals.len(), body.locals.iter().copied());
+		if locals_end > serialize(locals)?.len() {
+			padding.locals = Some((body.locals.clone(), payload[..locals_end].to_vec()));
+		}
+
+		let code = body.code();
+		if payload.len() - locals_end > serialize(code.clone())?.len() {
+			for (position, instruction) in code.elements().iter().enumerate() {
+				let start = reader.position();
+				Instruction::deserialize(&mut reader)?;
+				let bytes = &payload[start..reader.position()];
+				if bytes.len() > serialize(instruction.clone())?.len() {
+					padding.instructions.push((position, instruction.clone(), bytes.to_vec()));
+				}
+			}
+		}
+		Ok(Some(padding).filter(|padding| *padding != Padding::default()))
+	}
+}
+
+/// Instructions of a function body, decoded or as found in the binary. Encoded instructions
+/// are decoded on first access and the result is cached alongside them.
+#[derive(Debug)]
+enum FuncCode {
+	Decoded(Instructions),
+	Raw(Vec<u8>, OnceBox<Result<Instructions, Error>>),
+}
+
+impl Clone for FuncCode {
+	fn clone(&self) -> Self {
+		match self {
+			FuncCode::Decoded(instructions) => FuncCode::Decoded(instructions.clone()),
+			FuncCode::Raw(raw, cache) => {
+				let cloned = OnceBox::new();
+				if let Some(decoded) = cache.get() {
+					let _ = cloned.set(Box::new(decoded.clone()));
+				}
+				FuncCode::Raw(raw.clone(), cloned)
+			},
+		}
+	}
+}
+
+impl FuncCode {
+	fn decode(raw: &[u8]) -> Result<Instructions, Error> {
+		let mut reader = io::Cursor::new(raw);
+		let instructions = Instructions::deserialize(&mut reader)?;
+		if reader.position() != raw.len() {
+			return Err(io::Error::InvalidData.into())
+		}
+		Ok(instructions)
+	}
+
+	fn get(&self) -> Result<&Instructions, &Error> {
+		match self {
+			FuncCode::Decoded(instructions) => Ok(instructions),
+			FuncCode::Raw(raw, cache) =>
+				cache.get_or_init(|| Box::new(FuncCode::decode(raw))).as_ref(),
+		}
+	}
+
+	fn canonical(&self) -> Canonical<'_> {
+		match self {
+			FuncCode::Decoded(instructions) => Canonical::Decoded(instructions),
+			FuncCode::Raw(raw, _) =>
+				self.get().map_or(Canonical::Malformed(raw), Canonical::Decoded),
+		}
+	}
+}
+
+/// Form in which code is compared and hashed: the decoded instructions, or the bytes of code
+/// which can't be decoded.
+#[derive(PartialEq, Eq, Hash)]
+enum Canonical<'a> {
+	Decoded(&'a Instructions),
+	Malformed(&'a [u8]),
+}
+
+impl PartialEq for FuncCode {
+	/// Raw code is equal to decoded instructions if it decodes to them. It is only decoded
+	/// when it differs from the code it is compared to.
+	fn eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(FuncCode::Decoded(a), FuncCode::Decoded(b)) => a == b,
+			(FuncCode::Raw(a, _), FuncCode::Raw(b, _)) if a == b => true,
+			_ => self.canonical() == other.canonical(),
+		}
+	}
+}
+
+impl Eq for FuncCode {}
+
+impl Hash for FuncCode {
+	/// Raw code is hashed like the instructions it decodes to, so it is decoded first.
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.canonical().hash(state)
+	}
 }
 
 impl FuncBody {
 	/// New function body with given `locals` and `instructions`.
 	pub fn new(locals: Vec<Local>, instructions: Instructions) -> Self {
-		FuncBody { locals, instructions }
+		FuncBody { locals, instructions: FuncCode::Decoded(instructions), padding: None }
 	}
 
 	/// List of individual instructions.
 	pub fn empty() -> Self {
-		FuncBody { locals: Vec::new(), instructions: Instructions::empty() }
+		FuncBody::new(Vec::new(), Instructions::empty())
 	}
 
 	/// Locals declared in function body.
@@ -112,8 +270,24 @@ impl FuncBody {
 	/// Instruction list of the function body. Minimal instruction list
 	///
 	/// is just `&[Instruction::End]`
+	///
+	/// # Panics
+	///
+	/// Panics if the body was deserialized lazily and its instructions are malformed, see
+	/// [`FuncBody::try_code`].
 	pub fn code(&self) -> &Instructions {
-		&self.instructions
+		match self.try_code() {
+			Ok(instructions) => instructions,
+			Err(err) => panic!("function body cannot be decoded: {}", err),
+		}
+	}
+
+	/// Instruction list of the function body, decoded from the original bytes on first access
+	/// if the body was deserialized lazily.
+	///
+	/// The decoded instructions are cached, so the body is decoded at most once.
+	pub fn try_code(&self) -> Result<&Instructions, Error> {
+		self.instructions.get().map_err(Clone::clone)
 	}
 
 	/// Locals declared in function body (mutable).
@@ -121,18 +295,114 @@ impl FuncBody {
 		&mut self.locals
 	}
 
-	/// Instruction list of the function body (mutable).
+	/// Types of the declared locals, one per local, parameters excluded.
+	pub fn locals_expanded(&self) -> impl Iterator<Item = ValueType> + '_ {
+		self.locals
+			.iter()
+			.flat_map(|local| iter::repeat(local.value_type).take(local.count as usize))
+	}
+
+	/// Type of the local at `index`, where parameters of the function signature `params` come
+	/// first, or `None` if there's no such local.
+	pub fn local_type(&self, params: &FunctionType, index: u32) -> Option<ValueType> {
+		let index = index as usize;
+		if let Some(&param) = params.params().get(index) {
+			return Some(param)
+		}
+		let mut remaining = (index - params.params().len()) as u64;
+		for local in &self.locals {
+			if remaining < local.count as u64 {
+				return Some(local.value_type)
+			}
+			remaining -= local.count as u64;
+		}
+		None
+	}
+
+	/// Instruction list of the function body (mutable), decoding it first if the body was
+	/// deserialized lazily, see [`FuncBody::decode`].
+	///
+	/// # Panics
+	///
+	/// Panics if the body was deserialized lazily and its instructions are malformed.
 	pub fn code_mut(&mut self) -> &mut Instructions {
-		&mut self.instructions
+		match self.decode() {
+			Ok(instructions) => instructions,
+			Err(err) => panic!("function body cannot be decoded: {}", err),
+		}
 	}
-}
 
-impl Deserialize for FuncBody {
-	type Error = Error;
+	/// Instruction list of the function body, decoding it first if the body was
+	/// deserialized lazily.
+	///
+	/// The original bytes are dropped, so the body is serialized from its instructions.
+	pub fn decode(&mut self) -> Result<&mut Instructions, Error> {
+		if let FuncCode::Raw(ref raw, ref cache) = self.instructions {
+			let instructions = match cache.get() {
+				Some(decoded) => decoded.clone()?,
+				None => FuncCode::decode(raw)?,
+			};
+			self.instructions = FuncCode::Decoded(instructions);
+		}
+		match self.instructions {
+			FuncCode::Decoded(ref mut instructions) => Ok(instructions),
+			FuncCode::Raw(..) => unreachable!("the body was decoded above"),
+		}
+	}
 
-	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+	/// Static cost estimate of the function body under the given cost model.
+	///
+	/// # Panics
+	///
+	/// Panics if the body was deserialized lazily and its instructions are malformed, see
+	/// [`FuncBody::try_code`].
+	pub fn static_cost(&self, model: &dyn CostModel) -> CostSummary {
+		CostSummary::of(self.code().elements(), model)
+	}
+
+	/// Whether the body holds its instructions rather than their encoding.
+	///
+	/// Only bodies coming from [`Module::deserialize_lazy`](super::Module::deserialize_lazy)
+	/// can be undecoded. Reading their instructions keeps the encoding, only
+	/// [`FuncBody::decode`] and [`FuncBody::code_mut`] drop it.
+	pub fn is_decoded(&self) -> bool {
+		matches!(self.instructions, FuncCode::Decoded(_))
+	}
+
+	/// Encoded instructions of the function body, if it was not decoded yet.
+	pub fn raw_code(&self) -> Option<&[u8]> {
+		match self.instructions {
+			FuncCode::Decoded(_) => None,
+			FuncCode::Raw(ref raw, _) => Some(raw),
+		}
+	}
+
+	/// Whether the body keeps the original encoding of its padded integers, see
+	/// [`LebMode::Preserve`](super::limits::LebMode::Preserve).
+	pub fn is_padded(&self) -> bool {
+		self.padding.is_some()
+	}
+
+	/// Drop the original encoding of padded integers, so that the body is serialized in the
+	/// minimal encoding.
+	pub fn normalize(&mut self) {
+		self.padding = None;
+	}
+
+	pub(crate) fn set_padding(&mut self, padding: Padding) {
+		self.padding = Some(Box::new(padding));
+	}
+
+	/// Deserialize function body, keeping the instructions undecoded.
+	pub(crate) fn deserialize_lazy<R: io::Read>(reader: &mut R) -> Result<Self, Error> {
 		let mut body_reader = SectionReader::new(reader)?;
-		let locals: Vec<Local> = CountedList::<Local>::deserialize(&mut body_reader)?.into_inner();
+		let locals = FuncBody::deserialize_locals(&mut body_reader)?;
+		let instructions = FuncCode::Raw(body_reader.into_remaining(), OnceBox::new());
+		Ok(FuncBody { locals, instructions, padding: None })
+	}
+
+	pub(crate) fn deserialize_locals<R: io::Read>(reader: &mut R) -> Result<Vec<Local>, Error> {
+		let locals: Vec<Local> = CountedList::<Local>::deserialize(reader)?.into_inner();
 
 		// The specification obliges us to count the total number of local variables while
 		// decoding the binary format.
@@ -141,9 +411,19 @@ impl Deserialize for FuncBody {
 			.try_fold(0u32, |acc, &Local { count, .. }| acc.checked_add(count))
 			.ok_or(Error::TooManyLocals)?;
 
+		Ok(locals)
+	}
+}
+
+impl Deserialize for FuncBody {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		let mut body_reader = SectionReader::new(reader)?;
+		let locals = FuncBody::deserialize_locals(&mut body_reader)?;
 		let instructions = Instructions::deserialize(&mut body_reader)?;
 		body_reader.close()?;
-		Ok(FuncBody { locals, instructions })
+		Ok(FuncBody::new(locals, instructions))
 	}
 }
 
@@ -152,17 +432,107 @@ impl Serialize for FuncBody {
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		let mut counted_writer = CountedWriter::new(writer);
+		let padding = self.padding.map_or_else(Padding::default, |padding| *padding);
+
+		match padding.locals {
+			Some((ref locals, ref bytes)) if *locals == self.locals =>
+				io::Write::write(&mut counted_writer, bytes)?,
+			_ => {
+				let data = self.locals;
+				let counted_list = CountedListWriter::<Local, _>(data.len(), data.into_iter());
+				counted_list.serialize(&mut counted_writer)?;
+			},
+		}
+
+		match self.instructions {
+			FuncCode::Decoded(mut code) if !padding.instructions.is_empty() => {
+				let code = core::mem::take(code.elements_mut());
+				let mut padded = padding.instructions.iter().peekable();
+				for (position, instruction) in code.into_iter().enumerate() {
+					match padded.next_if(|&&(padded_at, _, _)| padded_at == position) {
+						Some((_, original, bytes)) if *original == instruction =>
+							io::Write::write(&mut counted_writer, bytes)?,
+						_ => instruction.serialize(&mut counted_writer)?,
+					}
+				}
+			},
+			FuncCode::Decoded(code) => code.serialize(&mut counted_writer)?,
+			FuncCode::Raw(raw, _) => io::Write::write(&mut counted_writer, &raw)?,
+		}
+
+		match padding.size_width {
+			Some(width) => counted_writer.done_padded(width)?,
+			None => counted_writer.done()?,
+		}
 
-		let data = self.locals;
-		let counted_list =
-			CountedListWriter::<Local, _>(data.len(), data.into_iter().map(Into::into));
-		counted_list.serialize(&mut counted_writer)?;
+		Ok(())
+	}
+}
 
-		let code = self.instructions;
-		code.serialize(&mut counted_writer)?;
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{FunctionType, Instruction, Instructions, ValueType},
+		FuncBody, Local,
+	};
+	use crate::io;
+	use core::hash::{Hash, Hasher};
+	use std::collections::hash_map::DefaultHasher;
+
+	fn hash(body: &FuncBody) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		body.hash(&mut hasher);
+		hasher.finish()
+	}
 
-		counted_writer.done()?;
+	#[test]
+	fn lazy_code_comparison() {
+		let lazy = |bytes: &[u8]| FuncBody::deserialize_lazy(&mut io::Cursor::new(bytes)).unwrap();
+		let minimal = lazy(&[0x04, 0x00, 0x41, 0x01, 0x0b]);
+		// `i32.const 1` with a padded immediate.
+		let padded = lazy(&[0x05, 0x00, 0x41, 0x81, 0x00, 0x0b]);
+		let decoded = FuncBody::new(
+			vec![],
+			Instructions::new(vec![Instruction::I32Const(1), Instruction::End]),
+		);
+		assert_eq!(minimal, padded);
+		assert_eq!(minimal, decoded);
+		assert_eq!(padded, decoded);
+		assert_eq!(hash(&minimal), hash(&padded));
+		assert_eq!(hash(&minimal), hash(&decoded));
+
+		let malformed = lazy(&[0x03, 0x00, 0xff, 0x0b]);
+		assert_eq!(malformed, malformed.clone());
+		assert_ne!(malformed, decoded);
+	}
 
-		Ok(())
+	#[test]
+	fn local_types() {
+		let params = FunctionType::new(vec![ValueType::I32, ValueType::I32], vec![]);
+		let body = FuncBody::new(
+			vec![
+				Local::new(3, ValueType::I64),
+				Local::new(0, ValueType::F64),
+				Local::new(1, ValueType::F32),
+			],
+			Instructions::empty(),
+		);
+
+		let expanded: Vec<_> = body.locals_expanded().collect();
+		assert_eq!(expanded, [ValueType::I64, ValueType::I64, ValueType::I64, ValueType::F32]);
+		let types: Vec<_> = (0..7).map(|index| body.local_type(&params, index)).collect();
+		assert_eq!(
+			types,
+			[
+				Some(ValueType::I32),
+				Some(ValueType::I32),
+				Some(ValueType::I64),
+				Some(ValueType::I64),
+				Some(ValueType::I64),
+				Some(ValueType::F32),
+				None,
+			]
+		);
+		assert_eq!(body.local_type(&params, u32::MAX), None);
 	}
 }
@@ -1,16 +1,17 @@
 use super::{
-	Deserialize, Error, Serialize, TableElementType, Uint8, ValueType, VarInt7, VarUint1,
+	Deserialize, Error, Serialize, TableElementType, TypeIdx, Uint8, ValueType, VarInt7, VarUint1,
 	VarUint32, VarUint7,
 };
 use crate::io;
 use alloc::string::String;
+use core::fmt;
 
 const FLAG_HAS_MAX: u8 = 0x01;
 #[cfg(feature = "atomics")]
 const FLAG_SHARED: u8 = 0x02;
 
 /// Global definition struct
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct GlobalType {
 	content_type: ValueType,
 	is_mutable: bool,
@@ -53,8 +54,60 @@ impl Serialize for GlobalType {
 	}
 }
 
+/// Exception tag definition.
+///
+/// Tags are typed by a function signature, whose params are the values carried by the
+/// exception.
+#[cfg(feature = "exceptions")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TagType {
+	type_ref: TypeIdx,
+}
+
+#[cfg(feature = "exceptions")]
+impl TagType {
+	/// New exception tag with the signature at `type_ref` in the type section.
+	pub fn new(type_ref: TypeIdx) -> Self {
+		TagType { type_ref }
+	}
+
+	/// Signature type reference.
+	pub fn type_ref(&self) -> TypeIdx {
+		self.type_ref
+	}
+
+	/// Signature type reference (mutable).
+	pub fn type_ref_mut(&mut self) -> &mut TypeIdx {
+		&mut self.type_ref
+	}
+}
+
+#[cfg(feature = "exceptions")]
+impl Deserialize for TagType {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		// Exceptions are the only kind of tags.
+		let attribute: u8 = Uint8::deserialize(reader)?.into();
+		if attribute != 0 {
+			return Err(Error::InvalidTagAttribute(attribute))
+		}
+		Ok(TagType { type_ref: VarUint32::deserialize(reader)?.into() })
+	}
+}
+
+#[cfg(feature = "exceptions")]
+impl Serialize for TagType {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		Uint8::from(0).serialize(writer)?;
+		VarUint32::from(self.type_ref).serialize(writer)
+	}
+}
+
 /// Table entry
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct TableType {
 	elem_type: TableElementType,
 	limits: ResizableLimits,
@@ -75,6 +128,14 @@ impl TableType {
 	pub fn elem_type(&self) -> TableElementType {
 		self.elem_type
 	}
+
+	/// Check that a table of this type can be provided for an import of the `required` type.
+	///
+	/// Tables only hold function references, so their limits must match, see
+	/// [`ResizableLimits::matches`].
+	pub fn matches(&self, required: &TableType) -> Result<(), Error> {
+		self.limits.matches(&required.limits)
+	}
 }
 
 impl Deserialize for TableType {
@@ -97,7 +158,7 @@ impl Serialize for TableType {
 }
 
 /// Memory and table limits.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct ResizableLimits {
 	initial: u32,
 	maximum: Option<u32>,
@@ -129,6 +190,46 @@ impl ResizableLimits {
 	pub fn shared(&self) -> bool {
 		self.shared
 	}
+
+	/// Check that a memory or table with these limits can be provided for an import with the
+	/// `required` limits, failing with [`Error::IncompatibleLimits`] otherwise.
+	///
+	/// This is the subtyping rule of the spec: the provided minimum must be at least the
+	/// required one, and if a maximum is required, the provided maximum must exist and not
+	/// exceed it. With the `atomics` feature, the `shared` flags must also be equal.
+	pub fn matches(&self, required: &ResizableLimits) -> Result<(), Error> {
+		#[cfg(feature = "atomics")]
+		let shared = self.shared == required.shared;
+		#[cfg(not(feature = "atomics"))]
+		let shared = true;
+		let maximum = match (self.maximum, required.maximum) {
+			(_, None) => true,
+			(Some(provided), Some(required)) => provided <= required,
+			(None, Some(_)) => false,
+		};
+		if shared && maximum && self.initial >= required.initial {
+			Ok(())
+		} else {
+			Err(Error::IncompatibleLimits { required: *required, provided: *self })
+		}
+	}
+}
+
+impl fmt::Display for ResizableLimits {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "initial {}", self.initial)?;
+		match self.maximum {
+			Some(maximum) => write!(f, ", maximum {}", maximum)?,
+			None => write!(f, ", no maximum")?,
+		}
+		#[cfg(feature = "atomics")]
+		{
+			if self.shared {
+				write!(f, ", shared")?;
+			}
+		}
+		Ok(())
+	}
 }
 
 impl Deserialize for ResizableLimits {
@@ -192,7 +293,7 @@ impl Serialize for ResizableLimits {
 }
 
 /// Memory entry.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct MemoryType(ResizableLimits);
 
 impl MemoryType {
@@ -214,6 +315,12 @@ impl MemoryType {
 	pub fn limits(&self) -> &ResizableLimits {
 		&self.0
 	}
+
+	/// Check that a memory of this type can be provided for an import of the `required` type,
+	/// see [`ResizableLimits::matches`].
+	pub fn matches(&self, required: &MemoryType) -> Result<(), Error> {
+		self.0.matches(&required.0)
+	}
 }
 
 impl Deserialize for MemoryType {
@@ -233,17 +340,20 @@ impl Serialize for MemoryType {
 }
 
 /// External to local binding.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum External {
 	/// Binds to a function whose type is associated with the given index in the
 	/// type section.
-	Function(u32),
+	Function(TypeIdx),
 	/// Describes local table definition to be imported as.
 	Table(TableType),
 	/// Describes local memory definition to be imported as.
 	Memory(MemoryType),
 	/// Describes local global entry to be imported as.
 	Global(GlobalType),
+	/// Describes exception tag to be imported as.
+	#[cfg(feature = "exceptions")]
+	Tag(TagType),
 }
 
 impl Deserialize for External {
@@ -256,6 +366,8 @@ impl Deserialize for External {
 			0x01 => Ok(External::Table(TableType::deserialize(reader)?)),
 			0x02 => Ok(External::Memory(MemoryType::deserialize(reader)?)),
 			0x03 => Ok(External::Global(GlobalType::deserialize(reader)?)),
+			#[cfg(feature = "exceptions")]
+			0x04 => Ok(External::Tag(TagType::deserialize(reader)?)),
 			_ => Err(Error::UnknownExternalKind(kind.into())),
 		}
 	}
@@ -284,6 +396,11 @@ impl Serialize for External {
 				VarInt7::from(0x03).serialize(writer)?;
 				gt.serialize(writer)?;
 			},
+			#[cfg(feature = "exceptions")]
+			Tag(tt) => {
+				VarInt7::from(0x04).serialize(writer)?;
+				tt.serialize(writer)?;
+			},
 		}
 
 		Ok(())
@@ -291,7 +408,7 @@ impl Serialize for External {
 }
 
 /// Import entry.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ImportEntry {
 	module_str: String,
 	field_str: String,
@@ -356,3 +473,54 @@ impl Serialize for ImportEntry {
 		self.external.serialize(writer)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::{Error, MemoryType, TableType};
+
+	#[test]
+	fn memory_limits_match() {
+		let required = MemoryType::new(2, Some(10));
+		// Larger minimum and smaller maximum.
+		assert!(MemoryType::new(3, Some(8)).matches(&required).is_ok());
+		assert!(MemoryType::new(2, Some(10)).matches(&required).is_ok());
+		// Minimum too small.
+		assert!(MemoryType::new(1, Some(10)).matches(&required).is_err());
+		// No maximum, or a larger one.
+		assert!(MemoryType::new(3, None).matches(&required).is_err());
+		assert!(MemoryType::new(3, Some(11)).matches(&required).is_err());
+		// Any maximum is fine if none is required.
+		assert!(MemoryType::new(3, None).matches(&MemoryType::new(2, None)).is_ok());
+		assert!(MemoryType::new(3, Some(3)).matches(&MemoryType::new(2, None)).is_ok());
+
+		let error = MemoryType::new(1, None).matches(&required).unwrap_err();
+		assert!(matches!(error, Error::IncompatibleLimits { .. }));
+		assert_eq!(
+			error.to_string(),
+			"Limits (initial 1, no maximum) don't match the required limits \
+			 (initial 2, maximum 10)"
+		);
+	}
+
+	#[test]
+	fn table_limits_match() {
+		let required = TableType::new(1, Some(4));
+		assert!(TableType::new(1, Some(4)).matches(&required).is_ok());
+		assert!(TableType::new(2, Some(3)).matches(&required).is_ok());
+		assert!(TableType::new(0, Some(4)).matches(&required).is_err());
+		assert!(TableType::new(2, None).matches(&required).is_err());
+		assert!(TableType::new(2, Some(5)).matches(&required).is_err());
+		assert!(TableType::new(2, None).matches(&TableType::new(0, None)).is_ok());
+	}
+
+	#[cfg(feature = "atomics")]
+	#[test]
+	fn shared_memory_match() {
+		let mut shared = MemoryType::new(1, Some(2));
+		shared.set_shared(true);
+		assert!(shared.matches(&shared).is_ok());
+		let error = shared.matches(&MemoryType::new(1, Some(2))).unwrap_err();
+		assert!(error.to_string().starts_with("Limits (initial 1, maximum 2, shared) don't"));
+		assert!(MemoryType::new(1, Some(2)).matches(&shared).is_err());
+	}
+}
@@ -6,6 +6,7 @@ use super::{Deserialize, Error, Serialize, VarUint32};
 use alloc::vec;
 use core::{
 	cmp::min,
+	hash::{Hash, Hasher},
 	iter::{FromIterator, IntoIterator},
 	mem, slice,
 };
@@ -43,8 +44,8 @@ impl<T> IndexMap<T> {
 	/// Return the name for the specified index, if it exists.
 	pub fn get(&self, idx: u32) -> Option<&T> {
 		match self.entries.get(idx as usize) {
-			Some(&Some(ref value)) => Some(value),
-			Some(&None) | None => None,
+			Some(Some(value)) => Some(value),
+			Some(None) | None => None,
 		}
 	}
 
@@ -85,7 +86,7 @@ impl<T> IndexMap<T> {
 			existing
 		};
 		if mem::size_of::<usize>() > 4 {
-			debug_assert!(self.entries.len() <= (u32::max_value() as usize) + 1);
+			debug_assert!(self.entries.len() <= (u32::MAX as usize) + 1);
 		}
 		#[cfg(slow_assertions)]
 		debug_assert_eq!(self.len, self.slow_len());
@@ -130,7 +131,7 @@ impl<T> IndexMap<T> {
 	}
 
 	/// Create a non-consuming iterator over this `IndexMap`'s keys and values.
-	pub fn iter(&self) -> Iter<T> {
+	pub fn iter(&self) -> Iter<'_, T> {
 		// Note that this does the right thing because we use `&self`.
 		self.into_iter()
 	}
@@ -200,6 +201,19 @@ impl<T: PartialEq> PartialEq<IndexMap<T>> for IndexMap<T> {
 
 impl<T: Eq> Eq for IndexMap<T> {}
 
+impl<T: Hash> Hash for IndexMap<T> {
+	/// Trailing empty entries are ignored, like by comparisons.
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.len.hash(state);
+		for (index, entry) in self.entries.iter().enumerate() {
+			if let Some(value) = entry {
+				index.hash(state);
+				value.hash(state);
+			}
+		}
+	}
+}
+
 impl<T> FromIterator<(u32, T)> for IndexMap<T> {
 	/// Create an `IndexMap` from an iterator.
 	///
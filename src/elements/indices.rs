@@ -0,0 +1,157 @@
+use super::{Deserialize, Error, Serialize, VarUint32};
+use crate::io;
+use core::fmt;
+
+macro_rules! index_type {
+	($(#[$attr:meta])* $name:ident) => {
+		$(#[$attr])*
+		///
+		/// Encoded as a `varuint32`, exactly like a bare `u32` index.
+		#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+		#[repr(transparent)]
+		pub struct $name(pub u32);
+
+		impl $name {
+			/// Raw value of the index.
+			pub fn index(self) -> u32 {
+				self.0
+			}
+		}
+
+		impl From<u32> for $name {
+			fn from(index: u32) -> Self {
+				$name(index)
+			}
+		}
+
+		impl From<$name> for u32 {
+			fn from(index: $name) -> u32 {
+				index.0
+			}
+		}
+
+		impl From<VarUint32> for $name {
+			fn from(index: VarUint32) -> Self {
+				$name(index.into())
+			}
+		}
+
+		impl From<$name> for VarUint32 {
+			fn from(index: $name) -> VarUint32 {
+				index.0.into()
+			}
+		}
+
+		impl fmt::Display for $name {
+			fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+				fmt::Display::fmt(&self.0, f)
+			}
+		}
+
+		impl Deserialize for $name {
+			type Error = Error;
+
+			fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+				Ok(VarUint32::deserialize(reader)?.into())
+			}
+		}
+
+		impl Serialize for $name {
+			type Error = Error;
+
+			fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+				VarUint32::from(self).serialize(writer)
+			}
+		}
+	};
+}
+
+index_type! {
+	/// Index in the function index space (imported functions first).
+	FuncIdx
+}
+
+index_type! {
+	/// Index of a signature in the type section.
+	TypeIdx
+}
+
+index_type! {
+	/// Index in the table index space.
+	TableIdx
+}
+
+index_type! {
+	/// Index in the memory index space.
+	MemIdx
+}
+
+index_type! {
+	/// Index in the global index space (imported globals first).
+	GlobalIdx
+}
+
+index_type! {
+	/// Index of a local variable, parameters included.
+	LocalIdx
+}
+
+index_type! {
+	/// Relative depth of the targeted label in the control stack.
+	LabelIdx
+}
+
+index_type! {
+	/// Index in the exception tag index space (imported tags first).
+	TagIdx
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{
+			serialize, BrTableData, ExportEntry, External, ImportEntry, Instruction, Instructions,
+			Internal,
+		},
+		FuncIdx, GlobalIdx, LabelIdx, LocalIdx, TypeIdx,
+	};
+	use alloc::boxed::Box;
+
+	#[test]
+	fn serialization_is_unchanged() {
+		let instructions = Instructions::new(vec![
+			Instruction::Call(FuncIdx(300)),
+			Instruction::CallIndirect(TypeIdx(2), 0),
+			Instruction::GetLocal(LocalIdx(1)),
+			Instruction::SetGlobal(GlobalIdx(128)),
+			Instruction::BrTable(Box::new(BrTableData {
+				table: vec![LabelIdx(0), LabelIdx(1)].into_boxed_slice(),
+				default: LabelIdx(2),
+			})),
+			Instruction::End,
+		]);
+		assert_eq!(
+			serialize(instructions).expect("Instructions should be serialized"),
+			vec![
+				0x10, 0xac, 0x02, // call 300
+				0x11, 0x02, 0x00, // call_indirect 2
+				0x20, 0x01, // get_local 1
+				0x24, 0x80, 0x01, // set_global 128
+				0x0e, 0x02, 0x00, 0x01, 0x02, // br_table [0, 1] 2
+				0x0b, // end
+			]
+		);
+
+		let export = ExportEntry::new("f".into(), Internal::Function(FuncIdx(129)));
+		assert_eq!(
+			serialize(export).expect("Export entry should be serialized"),
+			vec![0x01, b'f', 0x00, 0x81, 0x01]
+		);
+
+		let import = ImportEntry::new("m".into(), "f".into(), External::Function(TypeIdx(3)));
+		assert_eq!(
+			serialize(import).expect("Import entry should be serialized"),
+			vec![0x01, b'm', 0x01, b'f', 0x00, 0x03]
+		);
+	}
+}
@@ -0,0 +1,299 @@
+use super::{
+	Error, External, FuncIdx, FunctionType, ImportEntry, Internal, Module, ResizableLimits,
+	Section, Type, TypeIdx, TypeSection, ValueType,
+};
+use alloc::string::String;
+
+/// Import of a module, with its type resolved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImportRef<'a> {
+	/// Name of the module the import is from.
+	pub module: &'a str,
+	/// Name of the imported field.
+	pub field: &'a str,
+	/// What is imported.
+	pub kind: ImportKind<'a>,
+}
+
+/// Kind and type of an import.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImportKind<'a> {
+	/// Function with the given signature.
+	Func(&'a FunctionType),
+	/// Table with the given limits.
+	Table(&'a ResizableLimits),
+	/// Linear memory with the given limits.
+	Memory(&'a ResizableLimits),
+	/// Global of the given type, mutable or not.
+	Global(ValueType, bool),
+	/// Exception tag with the given signature.
+	#[cfg(feature = "exceptions")]
+	Tag(&'a FunctionType),
+}
+
+/// Export of a module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ExportRef<'a> {
+	/// Name of the export.
+	pub field: &'a str,
+	/// What is exported, with its index in the index space of its kind.
+	pub kind: Internal,
+}
+
+impl Module {
+	/// Imports of the module, in order.
+	///
+	/// Function and tag signatures are looked up in the type section, an import referring
+	/// to a missing type yields `Error::UnresolvedType` and iteration can go on.
+	pub fn imports(&self) -> impl Iterator<Item = Result<ImportRef<'_>, Error>> {
+		let entries = self.import_section().map_or(&[][..], |section| section.entries());
+		entries.iter().map(move |entry| {
+			let kind = match *entry.external() {
+				External::Function(type_ref) => ImportKind::Func(self.resolve_type(type_ref)?),
+				External::Table(ref table) => ImportKind::Table(table.limits()),
+				External::Memory(ref memory) => ImportKind::Memory(memory.limits()),
+				External::Global(ref global) =>
+					ImportKind::Global(global.content_type(), global.is_mutable()),
+				#[cfg(feature = "exceptions")]
+				External::Tag(ref tag) => ImportKind::Tag(self.resolve_type(tag.type_ref())?),
+			};
+			Ok(ImportRef { module: entry.module(), field: entry.field(), kind })
+		})
+	}
+
+	/// Exports of the module, in order.
+	pub fn exports(&self) -> impl Iterator<Item = ExportRef<'_>> {
+		let entries = self.export_section().map_or(&[][..], |section| section.entries());
+		entries
+			.iter()
+			.map(|entry| ExportRef { field: entry.field(), kind: *entry.internal() })
+	}
+
+	/// Export with the given name.
+	pub fn export_by_name(&self, name: &str) -> Option<ExportRef<'_>> {
+		self.exports().find(|export| export.field == name)
+	}
+
+	/// Exported functions, once per export name.
+	///
+	/// A function exported under several names is listed once for each of them.
+	pub fn exported_functions(&self) -> impl Iterator<Item = (&str, FuncIdx)> {
+		self.exports().filter_map(|export| match export.kind {
+			Internal::Function(index) => Some((export.field, index)),
+			_ => None,
+		})
+	}
+
+	/// Move every import of module `from` to module `to`.
+	///
+	/// Returns the number of renamed imports. Index spaces are left untouched.
+	pub fn rename_import_module(&mut self, from: &str, to: &str) -> usize {
+		let mut renamed = 0;
+		for entry in self.import_entries_mut() {
+			if entry.module() == from {
+				*entry.module_mut() = String::from(to);
+				renamed += 1;
+			}
+		}
+		renamed
+	}
+
+	/// Rename the import `module.field` to `new_module.new_field`.
+	///
+	/// Returns whether there was such an import, only the first one is renamed if there are
+	/// duplicates.
+	pub fn rename_import(
+		&mut self,
+		module: &str,
+		field: &str,
+		new_module: &str,
+		new_field: &str,
+	) -> bool {
+		let entry = self
+			.import_entries_mut()
+			.iter_mut()
+			.find(|entry| entry.module() == module && entry.field() == field);
+		match entry {
+			Some(entry) => {
+				*entry.module_mut() = String::from(new_module);
+				*entry.field_mut() = String::from(new_field);
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Change the signature of the imported function `module.field`.
+	///
+	/// An identical type from the type section is reused, otherwise `new_type` is appended to it.
+	/// The previous type is kept, as removing it would shift the type indices. Returns whether
+	/// there was such a function import.
+	pub fn retype_import_function(
+		&mut self,
+		module: &str,
+		field: &str,
+		new_type: FunctionType,
+	) -> bool {
+		let position = self.import_entries_mut().iter().position(|entry| {
+			entry.module() == module &&
+				entry.field() == field &&
+				matches!(entry.external(), External::Function(_))
+		});
+		let position = match position {
+			Some(position) => position,
+			None => return false,
+		};
+
+		if self.type_section().is_none() {
+			self.insert_section(Section::Type(TypeSection::default()))
+				.expect("there is no type section yet");
+		}
+		let types = self.type_section_mut().expect("type section was just ensured").types_mut();
+		let new_type = Type::Function(new_type);
+		let type_ref = match types.iter().position(|ty| *ty == new_type) {
+			Some(type_ref) => type_ref,
+			None => {
+				types.push(new_type);
+				types.len() - 1
+			},
+		};
+
+		let entry = &mut self.import_entries_mut()[position];
+		*entry.external_mut() = External::Function((type_ref as u32).into());
+		true
+	}
+
+	fn import_entries_mut(&mut self) -> &mut [ImportEntry] {
+		self.import_section_mut().map_or(&mut [][..], |section| section.entries_mut())
+	}
+
+	fn resolve_type(&self, type_ref: TypeIdx) -> Result<&FunctionType, Error> {
+		let type_ref = type_ref.index();
+		let types = self.type_section().map_or(&[][..], |section| section.types());
+		match types.get(type_ref as usize) {
+			Some(Type::Function(func_type)) => Ok(func_type),
+			None => Err(Error::UnresolvedType(type_ref)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{
+			deserialize_buffer, serialize, Error, ExportEntry, ExportSection, External,
+			FunctionType, GlobalType, ImportCountType, ImportEntry, ImportSection, Internal,
+			MemoryType, Module, ResizableLimits, Section, TableType, Type, TypeSection, ValueType,
+		},
+		ImportKind, ImportRef,
+	};
+
+	fn module() -> Module {
+		let import = |field: &str, external| ImportEntry::new("env".into(), field.into(), external);
+		let export = |field: &str, internal| ExportEntry::new(field.into(), internal);
+		Module::new(vec![
+			Section::Type(TypeSection::with_types(vec![Type::Function(FunctionType::new(
+				vec![ValueType::I32],
+				vec![],
+			))])),
+			Section::Import(ImportSection::with_entries(vec![
+				import("print", External::Function(0.into())),
+				import("table", External::Table(TableType::new(1, None))),
+				import("memory", External::Memory(MemoryType::new(1, Some(2)))),
+				import("broken", External::Function(3.into())),
+				import("sp", External::Global(GlobalType::new(ValueType::I32, true))),
+			])),
+			Section::Export(ExportSection::with_entries(vec![
+				export("main", Internal::Function(1.into())),
+				export("memory", Internal::Memory(0.into())),
+			])),
+		])
+	}
+
+	#[test]
+	fn imports() {
+		let module = module();
+		let imports: Vec<_> = module.imports().collect();
+		assert_eq!(imports.len(), 5);
+
+		let print = imports[0].as_ref().expect("type 0 to resolve");
+		assert_eq!((print.module, print.field), ("env", "print"));
+		assert_eq!(print.kind, ImportKind::Func(&FunctionType::new(vec![ValueType::I32], vec![])));
+		let table = imports[1].as_ref().expect("table import");
+		assert_eq!(table.kind, ImportKind::Table(&ResizableLimits::new(1, None)));
+		let memory = imports[2].as_ref().expect("memory import");
+		assert_eq!(memory.kind, ImportKind::Memory(&ResizableLimits::new(1, Some(2))));
+		assert!(matches!(imports[3], Err(Error::UnresolvedType(3))));
+		let global = imports[4].as_ref().expect("global import");
+		assert_eq!(global.kind, ImportKind::Global(ValueType::I32, true));
+	}
+
+	#[test]
+	fn exports() {
+		let module = module();
+		let exports: Vec<_> = module.exports().map(|export| (export.field, export.kind)).collect();
+		assert_eq!(
+			exports,
+			[("main", Internal::Function(1.into())), ("memory", Internal::Memory(0.into()))]
+		);
+		assert_eq!(Module::default().exports().count(), 0);
+		assert_eq!(Module::default().imports().count(), 0);
+	}
+
+	#[test]
+	fn rename_imports() {
+		let mut module = module();
+		assert_eq!(module.rename_import_module("env", "wasi_snapshot_preview1"), 5);
+		assert_eq!(module.rename_import_module("env", "wasi_snapshot_preview1"), 0);
+		assert!(module.rename_import("wasi_snapshot_preview1", "print", "wasi", "fd_write"));
+		assert!(!module.rename_import("env", "sp", "wasi", "sp"));
+
+		let module: Module = deserialize_buffer(&serialize(module).expect("module to serialize"))
+			.expect("module to deserialize");
+		let names: Vec<_> = module
+			.imports()
+			.filter_map(Result::ok)
+			.map(|import| (import.module, import.field))
+			.collect();
+		assert_eq!(
+			names,
+			[
+				("wasi", "fd_write"),
+				("wasi_snapshot_preview1", "table"),
+				("wasi_snapshot_preview1", "memory"),
+				("wasi_snapshot_preview1", "sp"),
+			]
+		);
+		let print = module.imports().next().expect("first import").expect("type 0 to resolve");
+		assert_eq!(print.kind, ImportKind::Func(&FunctionType::new(vec![ValueType::I32], vec![])));
+		assert_eq!(module.import_count(ImportCountType::Function), 2);
+	}
+
+	#[test]
+	fn retype_import_function() {
+		let mut module = module();
+		let print = FunctionType::new(vec![ValueType::I32], vec![]);
+		let binary = FunctionType::new(vec![ValueType::I32; 2], vec![ValueType::I32]);
+		assert!(module.retype_import_function("env", "broken", print.clone()));
+		assert!(module.retype_import_function("env", "print", binary.clone()));
+		assert!(!module.retype_import_function("env", "memory", binary.clone()));
+		assert!(!module.retype_import_function("env", "missing", binary.clone()));
+
+		assert_eq!(module.type_section().expect("type section").types().len(), 2);
+		let kinds: Vec<_> =
+			module.imports().map(|import| import.ok().map(|import| import.kind)).collect();
+		assert_eq!(kinds[0], Some(ImportKind::Func(&binary)));
+		assert_eq!(kinds[3], Some(ImportKind::Func(&print)));
+
+		// A type section is added if there was none.
+		let import = ImportEntry::new("env".into(), "f".into(), External::Function(0.into()));
+		let mut module =
+			Module::new(vec![Section::Import(ImportSection::with_entries(vec![import]))]);
+		assert!(module.retype_import_function("env", "f", binary.clone()));
+		assert!(matches!(module.sections()[0], Section::Type(_)));
+		assert_eq!(
+			module.imports().next().map(|import| import.ok()),
+			Some(Some(ImportRef { module: "env", field: "f", kind: ImportKind::Func(&binary) }))
+		);
+	}
+}
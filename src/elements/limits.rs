@@ -0,0 +1,348 @@
+//! Implementation limits.
+//!
+//! The constants below are the default [`Limits`]. Plain deserialization doesn't enforce
+//! them: modules are checked against limits with [`Limits::check`], or when deserialized with
+//! a [`DeserializeConfig`], which also bounds the code while it is decoded.
+
+use super::{Error, External, Module, Type};
+
+/// Maximum number of 64KiB pages of a memory.
+pub const MAX_MEMORY_PAGES: u32 = 65536;
+
+/// Maximum number of locals of a function, parameters excluded.
+pub const MAX_FUNCTION_LOCALS: u32 = 1_000_000;
+
+/// Maximum number of parameters of a function type.
+pub const MAX_FUNCTION_PARAMS: u32 = 1000;
+
+/// Maximum number of functions defined in a module.
+pub const MAX_FUNCTIONS: u32 = 100_000;
+
+/// Configurable implementation limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Limits {
+	/// Maximum number of 64KiB pages of a memory.
+	pub memory_pages: u32,
+	/// Maximum number of locals of a function, parameters excluded.
+	pub function_locals: u32,
+	/// Maximum number of parameters of a function type.
+	pub function_params: u32,
+	/// Maximum number of functions defined in a module.
+	pub functions: u32,
+}
+
+impl Default for Limits {
+	fn default() -> Self {
+		Limits {
+			memory_pages: MAX_MEMORY_PAGES,
+			function_locals: MAX_FUNCTION_LOCALS,
+			function_params: MAX_FUNCTION_PARAMS,
+			functions: MAX_FUNCTIONS,
+		}
+	}
+}
+
+/// Caps on the code section, checked while decoding it, see
+/// [`Module::deserialize_with_config`].
+///
+/// Exceeding a cap aborts deserialization with [`Error::LimitExceeded`], naming the field of
+/// the cap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DeserializeConfig {
+	/// Maximum size of a function body in bytes, locals included, checked before reading it.
+	pub max_body_size: u32,
+	/// Maximum number of instructions of a function.
+	pub max_function_instructions: u32,
+	/// Maximum number of instructions of all the functions.
+	pub max_module_instructions: u32,
+	/// Maximum number of function bodies.
+	pub max_functions: u32,
+	/// Treatment of integers of function bodies not in the minimal LEB128 encoding.
+	pub leb_mode: LebMode,
+	/// Limits the deserialized module is checked against, if any, see [`Limits::check`].
+	pub limits: Option<Limits>,
+}
+
+/// How [`Module::deserialize_with_config`] treats integers of function bodies which are not in
+/// the minimal LEB128 encoding, as padded by some toolchains.
+///
+/// Body sizes, local declarations and instruction immediates are covered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LebMode {
+	/// Fail with [`Error::NonMinimalEncoding`].
+	Strict,
+	/// Re-encode them minimally, which [`Module::normalized_encoding`] reports.
+	Permissive,
+	/// Serialize them back in their original width, as long as the instructions they belong
+	/// to are not changed.
+	Preserve,
+}
+
+impl Default for DeserializeConfig {
+	/// 10MiB bodies of at most 1M instructions, 16M instructions and 100k functions in total,
+	/// with padded integers re-encoded and the default limits.
+	fn default() -> Self {
+		DeserializeConfig {
+			max_body_size: 10 << 20,
+			max_function_instructions: 1_000_000,
+			max_module_instructions: 16_000_000,
+			max_functions: MAX_FUNCTIONS,
+			leb_mode: LebMode::Permissive,
+			limits: Some(Limits::default()),
+		}
+	}
+}
+
+impl Limits {
+	/// Check the module against the limits.
+	///
+	/// Bodies of lazily deserialized modules are checked too, their locals are always decoded.
+	pub fn check(&self, module: &Module) -> Result<(), Error> {
+		let imported_memories = module.import_section().into_iter().flat_map(|section| {
+			section.entries().iter().filter_map(|entry| match *entry.external() {
+				External::Memory(ref memory) => Some(memory),
+				_ => None,
+			})
+		});
+		let defined_memories = module.memory_section().into_iter().flat_map(|s| s.entries());
+		for memory in imported_memories.chain(defined_memories) {
+			let limits = memory.limits();
+			let pages = limits.maximum().unwrap_or(0).max(limits.initial());
+			if pages > self.memory_pages {
+				return Err(Error::TooManyMemoryPages(pages))
+			}
+		}
+
+		let functions = module.function_section().map_or(0, |s| s.entries().len());
+		if functions > self.functions as usize {
+			return Err(Error::TooManyFunctions)
+		}
+
+		for Type::Function(func_type) in module.type_section().map_or(&[][..], |s| s.types()) {
+			if func_type.params().len() > self.function_params as usize {
+				return Err(Error::TooManyParams)
+			}
+		}
+
+		for body in module.code_section().map_or(&[][..], |s| s.bodies()) {
+			let locals = body
+				.locals()
+				.iter()
+				.try_fold(0u32, |acc, local| acc.checked_add(local.count()))
+				.filter(|&locals| locals <= self.function_locals);
+			if locals.is_none() {
+				return Err(Error::TooManyLocals)
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{serialize, Error, Instruction, Instructions, Local, Module, ValueType},
+		DeserializeConfig, LebMode, Limits, MAX_FUNCTION_LOCALS, MAX_FUNCTION_PARAMS,
+		MAX_MEMORY_PAGES,
+	};
+	use core::hash::{Hash, Hasher};
+	use std::collections::hash_map::DefaultHasher;
+
+	fn hash(module: &Module) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		module.hash(&mut hasher);
+		hasher.finish()
+	}
+
+	/// Whether the module deserializes as is, and with the default config.
+	fn deserializes(module: Module) -> (bool, Result<Module, Error>) {
+		let bytes = serialize(module).expect("module to serialize");
+		let plain = Module::from_bytes(&bytes).is_ok();
+		(plain, Module::deserialize_with_config(&bytes, &DeserializeConfig::default()))
+	}
+
+	fn function(params: usize, locals: u32) -> Module {
+		crate::builder::module()
+			.function()
+			.signature()
+			.with_params(vec![ValueType::I32; params])
+			.build()
+			.body()
+			.with_locals(vec![Local::new(locals, ValueType::I32)])
+			.build()
+			.build()
+			.build()
+	}
+
+	#[test]
+	fn too_many_locals() {
+		assert!(matches!(deserializes(function(0, MAX_FUNCTION_LOCALS)), (true, Ok(_))));
+		assert!(matches!(
+			deserializes(function(0, MAX_FUNCTION_LOCALS + 1)),
+			(true, Err(Error::TooManyLocals))
+		));
+	}
+
+	#[test]
+	fn too_many_params() {
+		let max = MAX_FUNCTION_PARAMS as usize;
+		assert!(matches!(deserializes(function(max, 0)), (true, Ok(_))));
+		assert!(matches!(deserializes(function(max + 1, 0)), (true, Err(Error::TooManyParams))));
+	}
+
+	#[test]
+	fn too_many_memory_pages() {
+		let memory = |initial, maximum| {
+			crate::builder::module()
+				.memory()
+				.with_min(initial)
+				.with_max(maximum)
+				.build()
+				.build()
+		};
+		assert!(matches!(deserializes(memory(1, Some(MAX_MEMORY_PAGES))), (true, Ok(_))));
+		assert!(matches!(
+			deserializes(memory(1, Some(MAX_MEMORY_PAGES + 1))),
+			(true, Err(Error::TooManyMemoryPages(65537)))
+		));
+		assert!(matches!(
+			deserializes(memory(MAX_MEMORY_PAGES + 1, None)),
+			(true, Err(Error::TooManyMemoryPages(65537)))
+		));
+
+		// Limits are only checked when asked to.
+		let bytes = serialize(memory(MAX_MEMORY_PAGES + 1, None)).expect("module to serialize");
+		let config = DeserializeConfig { limits: None, ..DeserializeConfig::default() };
+		assert!(Module::deserialize_with_config(&bytes, &config).is_ok());
+	}
+
+	#[test]
+	fn stricter_limits() {
+		let module = crate::builder::module()
+			.memory()
+			.with_min(20)
+			.build()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.with_locals(vec![Local::new(100, ValueType::I64)])
+			.build()
+			.build()
+			.build();
+		assert!(Limits::default().check(&module).is_ok());
+
+		let limits = Limits { memory_pages: 16, ..Limits::default() };
+		assert!(matches!(limits.check(&module), Err(Error::TooManyMemoryPages(20))));
+
+		let limits = Limits { function_locals: 64, ..Limits::default() };
+		assert!(matches!(limits.check(&module), Err(Error::TooManyLocals)));
+
+		let limits = Limits { functions: 0, ..Limits::default() };
+		assert!(matches!(limits.check(&module), Err(Error::TooManyFunctions)));
+	}
+
+	#[test]
+	fn deserialize_config() {
+		// Bodies of 3, 4 and 5 bytes, with 2, 3 and 4 instructions.
+		let mut module = crate::builder::module();
+		for nops in 1..=3 {
+			let mut code = vec![Instruction::Nop; nops];
+			code.push(Instruction::End);
+			module = module
+				.function()
+				.signature()
+				.build()
+				.body()
+				.with_instructions(Instructions::new(code))
+				.build()
+				.build();
+		}
+		let bytes = serialize(module.build()).expect("module to serialize");
+		let deserialize = |config| Module::deserialize_with_config(&bytes, &config);
+
+		let config = DeserializeConfig {
+			max_body_size: 5,
+			max_function_instructions: 4,
+			max_module_instructions: 9,
+			max_functions: 3,
+			leb_mode: LebMode::Permissive,
+			limits: None,
+		};
+		assert_eq!(deserialize(config).unwrap(), Module::from_bytes(&bytes).unwrap());
+		assert!(deserialize(DeserializeConfig::default()).is_ok());
+
+		let exceeded = |config, cap, body| match deserialize(config) {
+			Err(Error::LimitExceeded { limit, function }) => limit == cap && function == body,
+			_ => false,
+		};
+		assert!(exceeded(DeserializeConfig { max_functions: 2, ..config }, "max_functions", 2));
+		assert!(exceeded(DeserializeConfig { max_body_size: 4, ..config }, "max_body_size", 2));
+		assert!(exceeded(
+			DeserializeConfig { max_function_instructions: 3, ..config },
+			"max_function_instructions",
+			2
+		));
+		assert!(exceeded(
+			DeserializeConfig { max_module_instructions: 8, ..config },
+			"max_module_instructions",
+			2
+		));
+		assert!(exceeded(
+			DeserializeConfig { max_module_instructions: 4, ..config },
+			"max_module_instructions",
+			1
+		));
+	}
+
+	#[test]
+	fn leb_modes() {
+		// A function with a body size, a local count and `i32.const 1` padded to five bytes,
+		// then `i64.const -1` padded to two.
+		let padded = [
+			0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+			0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+			0x03, 0x02, 0x01, 0x00, // function section
+			0x0a, 0x16, 0x01, 0x90, 0x80, 0x80, 0x80, 0x00, // code section, body size
+			0x01, 0x81, 0x00, 0x7f, // locals
+			0x41, 0x81, 0x80, 0x80, 0x80, 0x00, 0x1a, 0x42, 0xff, 0x7f, 0x1a, 0x0b,
+		];
+		let minimal = serialize(Module::from_bytes(padded).unwrap()).unwrap();
+		assert_eq!(minimal.len(), padded.len() - 10);
+		let deserialize = |bytes: &[u8], leb_mode| {
+			let config = DeserializeConfig { leb_mode, ..DeserializeConfig::default() };
+			Module::deserialize_with_config(bytes, &config)
+		};
+
+		assert!(matches!(deserialize(&padded, LebMode::Strict), Err(Error::NonMinimalEncoding(0))));
+		assert!(deserialize(&minimal, LebMode::Strict).is_ok());
+
+		let module = deserialize(&padded, LebMode::Permissive).unwrap();
+		assert!(module.normalized_encoding());
+		assert_eq!(serialize(module.clone()).unwrap(), minimal);
+		let reencoded = deserialize(&minimal, LebMode::Permissive).unwrap();
+		assert!(!reencoded.normalized_encoding());
+		assert_eq!(module, reencoded);
+		assert_eq!(hash(&module), hash(&reencoded));
+
+		let mut module = deserialize(&padded, LebMode::Preserve).unwrap();
+		assert!(!module.normalized_encoding());
+		assert_eq!(serialize(module.clone()).unwrap(), padded);
+		// The original encoding doesn't take part in comparisons.
+		assert_eq!(module, deserialize(&minimal, LebMode::Preserve).unwrap());
+		// Changed instructions are written minimally, the others keep their width.
+		let body = &mut module.code_section_mut().unwrap().bodies_mut()[0];
+		body.code_mut().elements_mut()[0] = Instruction::I32Const(2);
+		let bytes = serialize(module.clone()).unwrap();
+		assert_eq!(bytes[30..], [0x41, 0x02, 0x1a, 0x42, 0xff, 0x7f, 0x1a, 0x0b]);
+		assert_eq!(bytes[21..26], [0x8c, 0x80, 0x80, 0x80, 0x00]);
+
+		let body = &mut module.code_section_mut().unwrap().bodies_mut()[0];
+		assert!(body.is_padded());
+		body.normalize();
+		let bytes = serialize(module).unwrap();
+		assert_eq!(bytes.len(), minimal.len());
+		assert_eq!(bytes[bytes.len() - 7..], [0x41, 0x02, 0x1a, 0x42, 0x7f, 0x1a, 0x0b]);
+	}
+}
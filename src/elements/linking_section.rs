@@ -0,0 +1,610 @@
+use crate::io;
+use alloc::{string::String, vec::Vec};
+
+use super::{
+	section::ENTRIES_BUFFER_LENGTH, CountedList, CountedListWriter, CountedWriter, Deserialize,
+	Error, Serialize, Uint8, VarUint32, VarUint7,
+};
+
+/// The only version of the linking metadata this crate understands.
+const LINKING_VERSION: u32 = 2;
+
+const WASM_SEGMENT_INFO: u8 = 5;
+const WASM_INIT_FUNCS: u8 = 6;
+const WASM_COMDAT_INFO: u8 = 7;
+const WASM_SYMBOL_TABLE: u8 = 8;
+
+const SYMTAB_FUNCTION: u8 = 0;
+const SYMTAB_DATA: u8 = 1;
+const SYMTAB_GLOBAL: u8 = 2;
+const SYMTAB_SECTION: u8 = 3;
+const SYMTAB_TAG: u8 = 4;
+const SYMTAB_TABLE: u8 = 5;
+
+const WASM_SYM_UNDEFINED: u32 = 0x10;
+const WASM_SYM_EXPLICIT_NAME: u32 = 0x40;
+
+/// Linking metadata of a relocatable object file (the `linking` custom section).
+///
+/// See the [tool conventions](https://github.com/WebAssembly/tool-conventions/blob/main/Linking.md)
+/// for the meaning of the individual subsections.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LinkingSection {
+	/// Subsections, in the order they appear in the binary.
+	subsections: Vec<LinkingSubsection>,
+}
+
+impl LinkingSection {
+	/// Creates a new linking section.
+	pub fn new(subsections: Vec<LinkingSubsection>) -> Self {
+		LinkingSection { subsections }
+	}
+
+	/// List of subsections.
+	pub fn subsections(&self) -> &[LinkingSubsection] {
+		&self.subsections
+	}
+
+	/// List of subsections (mutable).
+	pub fn subsections_mut(&mut self) -> &mut Vec<LinkingSubsection> {
+		&mut self.subsections
+	}
+
+	/// Symbol table, if present and parsed.
+	pub fn symbol_table(&self) -> Option<&[SymbolInfo]> {
+		self.subsections.iter().find_map(|subsection| match *subsection {
+			LinkingSubsection::SymbolTable(ref symbols) => Some(&symbols[..]),
+			_ => None,
+		})
+	}
+
+	/// Data segment information, if present.
+	pub fn segment_info(&self) -> Option<&[SegmentInfo]> {
+		self.subsections.iter().find_map(|subsection| match *subsection {
+			LinkingSubsection::SegmentInfo(ref segments) => Some(&segments[..]),
+			_ => None,
+		})
+	}
+
+	/// Initialization functions, if present.
+	pub fn init_funcs(&self) -> Option<&[InitFunc]> {
+		self.subsections.iter().find_map(|subsection| match *subsection {
+			LinkingSubsection::InitFuncs(ref funcs) => Some(&funcs[..]),
+			_ => None,
+		})
+	}
+
+	/// COMDAT groups, if present.
+	pub fn comdats(&self) -> Option<&[Comdat]> {
+		self.subsections.iter().find_map(|subsection| match *subsection {
+			LinkingSubsection::ComdatInfo(ref comdats) => Some(&comdats[..]),
+			_ => None,
+		})
+	}
+}
+
+impl Deserialize for LinkingSection {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		let version: u32 = VarUint32::deserialize(rdr)?.into();
+		if version != LINKING_VERSION {
+			return Err(Error::UnsupportedVersion(version))
+		}
+
+		let mut subsections = Vec::new();
+		while let Ok(kind) = VarUint7::deserialize(rdr) {
+			let kind: u8 = kind.into();
+			let size: usize = VarUint32::deserialize(rdr)?.into();
+			let payload = buffered_read!(ENTRIES_BUFFER_LENGTH, size, rdr);
+
+			let subsection = match LinkingSubsection::parse(kind, &payload)? {
+				Some(subsection) => subsection,
+				None => LinkingSubsection::Raw { kind, payload },
+			};
+			subsections.push(subsection);
+		}
+
+		Ok(LinkingSection { subsections })
+	}
+}
+
+impl Serialize for LinkingSection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		VarUint32::from(LINKING_VERSION).serialize(wtr)?;
+		for subsection in self.subsections {
+			subsection.serialize(wtr)?;
+		}
+		Ok(())
+	}
+}
+
+/// Subsection of the linking section.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LinkingSubsection {
+	/// Extra metadata about the data segments.
+	SegmentInfo(Vec<SegmentInfo>),
+	/// Functions to be called at startup.
+	InitFuncs(Vec<InitFunc>),
+	/// COMDAT groups of symbols.
+	ComdatInfo(Vec<Comdat>),
+	/// Symbol table.
+	SymbolTable(Vec<SymbolInfo>),
+	/// Subsection kept verbatim.
+	///
+	/// Used for unknown subsection kinds, and for symbol tables containing
+	/// symbol kinds this crate does not know how to decode.
+	Raw {
+		/// Kind of the subsection.
+		kind: u8,
+		/// Undecoded payload of the subsection.
+		payload: Vec<u8>,
+	},
+}
+
+impl LinkingSubsection {
+	/// Parse subsection payload, returning `None` if it should be kept verbatim.
+	fn parse(kind: u8, payload: &[u8]) -> Result<Option<Self>, Error> {
+		let mut rdr = io::Cursor::new(payload);
+		let subsection = match kind {
+			WASM_SEGMENT_INFO => LinkingSubsection::SegmentInfo(
+				CountedList::<SegmentInfo>::deserialize(&mut rdr)?.into_inner(),
+			),
+			WASM_INIT_FUNCS => LinkingSubsection::InitFuncs(
+				CountedList::<InitFunc>::deserialize(&mut rdr)?.into_inner(),
+			),
+			WASM_COMDAT_INFO => LinkingSubsection::ComdatInfo(
+				CountedList::<Comdat>::deserialize(&mut rdr)?.into_inner(),
+			),
+			WASM_SYMBOL_TABLE => {
+				let count: usize = VarUint32::deserialize(&mut rdr)?.into();
+				let mut symbols = Vec::new();
+				for _ in 0..count {
+					let kind: u8 = Uint8::deserialize(&mut rdr)?.into();
+					match SymbolInfo::deserialize_kind(kind, &mut rdr)? {
+						Some(symbol) => symbols.push(symbol),
+						None => return Ok(None),
+					}
+				}
+				LinkingSubsection::SymbolTable(symbols)
+			},
+			_ => return Ok(None),
+		};
+
+		if rdr.position() != payload.len() {
+			return Err(io::Error::InvalidData.into())
+		}
+
+		Ok(Some(subsection))
+	}
+}
+
+impl Serialize for LinkingSubsection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		let kind = match self {
+			LinkingSubsection::SegmentInfo(_) => WASM_SEGMENT_INFO,
+			LinkingSubsection::InitFuncs(_) => WASM_INIT_FUNCS,
+			LinkingSubsection::ComdatInfo(_) => WASM_COMDAT_INFO,
+			LinkingSubsection::SymbolTable(_) => WASM_SYMBOL_TABLE,
+			LinkingSubsection::Raw { kind, .. } => kind,
+		};
+		VarUint7::from(kind).serialize(wtr)?;
+
+		let mut counted_writer = CountedWriter::new(wtr);
+		match self {
+			LinkingSubsection::SegmentInfo(segments) => {
+				CountedListWriter(segments.len(), segments.into_iter())
+					.serialize(&mut counted_writer)?;
+			},
+			LinkingSubsection::InitFuncs(funcs) => {
+				CountedListWriter(funcs.len(), funcs.into_iter()).serialize(&mut counted_writer)?;
+			},
+			LinkingSubsection::ComdatInfo(comdats) => {
+				CountedListWriter(comdats.len(), comdats.into_iter())
+					.serialize(&mut counted_writer)?;
+			},
+			LinkingSubsection::SymbolTable(symbols) => {
+				CountedListWriter(symbols.len(), symbols.into_iter())
+					.serialize(&mut counted_writer)?;
+			},
+			LinkingSubsection::Raw { payload, .. } => {
+				io::Write::write(&mut counted_writer, &payload)?;
+			},
+		}
+		counted_writer.done()?;
+
+		Ok(())
+	}
+}
+
+/// Extra metadata about a data segment.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SegmentInfo {
+	/// Name of the segment.
+	pub name: String,
+	/// Alignment of the segment, as a power of two.
+	pub alignment: u32,
+	/// Segment flags.
+	pub flags: u32,
+}
+
+impl Deserialize for SegmentInfo {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(SegmentInfo {
+			name: String::deserialize(rdr)?,
+			alignment: VarUint32::deserialize(rdr)?.into(),
+			flags: VarUint32::deserialize(rdr)?.into(),
+		})
+	}
+}
+
+impl Serialize for SegmentInfo {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		self.name.serialize(wtr)?;
+		VarUint32::from(self.alignment).serialize(wtr)?;
+		VarUint32::from(self.flags).serialize(wtr)?;
+		Ok(())
+	}
+}
+
+/// Function to be called at startup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InitFunc {
+	/// Priority of the call, lower goes first.
+	pub priority: u32,
+	/// Index of the function symbol in the symbol table.
+	pub symbol_index: u32,
+}
+
+impl Deserialize for InitFunc {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(InitFunc {
+			priority: VarUint32::deserialize(rdr)?.into(),
+			symbol_index: VarUint32::deserialize(rdr)?.into(),
+		})
+	}
+}
+
+impl Serialize for InitFunc {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		VarUint32::from(self.priority).serialize(wtr)?;
+		VarUint32::from(self.symbol_index).serialize(wtr)?;
+		Ok(())
+	}
+}
+
+/// COMDAT group: set of items of which the linker keeps only one copy.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Comdat {
+	/// Name of the group.
+	pub name: String,
+	/// Group flags.
+	pub flags: u32,
+	/// Items of the group.
+	pub symbols: Vec<ComdatSymbol>,
+}
+
+impl Deserialize for Comdat {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(Comdat {
+			name: String::deserialize(rdr)?,
+			flags: VarUint32::deserialize(rdr)?.into(),
+			symbols: CountedList::<ComdatSymbol>::deserialize(rdr)?.into_inner(),
+		})
+	}
+}
+
+impl Serialize for Comdat {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		self.name.serialize(wtr)?;
+		VarUint32::from(self.flags).serialize(wtr)?;
+		CountedListWriter(self.symbols.len(), self.symbols.into_iter()).serialize(wtr)?;
+		Ok(())
+	}
+}
+
+/// Item of a COMDAT group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ComdatSymbol {
+	/// Kind of the item (data segment, function, global, ...).
+	pub kind: u8,
+	/// Index of the item in its index space.
+	pub index: u32,
+}
+
+impl Deserialize for ComdatSymbol {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(ComdatSymbol {
+			kind: Uint8::deserialize(rdr)?.into(),
+			index: VarUint32::deserialize(rdr)?.into(),
+		})
+	}
+}
+
+impl Serialize for ComdatSymbol {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		Uint8::from(self.kind).serialize(wtr)?;
+		VarUint32::from(self.index).serialize(wtr)?;
+		Ok(())
+	}
+}
+
+/// Location of a defined data symbol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DataSymbolDefinition {
+	/// Index of the data segment.
+	pub segment: u32,
+	/// Offset within the segment.
+	pub offset: u32,
+	/// Size of the data.
+	pub size: u32,
+}
+
+/// Entry of the symbol table.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SymbolInfo {
+	/// Function symbol.
+	Function {
+		/// Symbol flags.
+		flags: u32,
+		/// Index of the function.
+		index: u32,
+		/// Symbol name, absent for undefined symbols without explicit name.
+		name: Option<String>,
+	},
+	/// Data symbol.
+	Data {
+		/// Symbol flags.
+		flags: u32,
+		/// Symbol name.
+		name: String,
+		/// Location of the data, absent for undefined symbols.
+		definition: Option<DataSymbolDefinition>,
+	},
+	/// Global symbol.
+	Global {
+		/// Symbol flags.
+		flags: u32,
+		/// Index of the global.
+		index: u32,
+		/// Symbol name, absent for undefined symbols without explicit name.
+		name: Option<String>,
+	},
+	/// Section symbol.
+	Section {
+		/// Symbol flags.
+		flags: u32,
+		/// Index of the section.
+		index: u32,
+	},
+	/// Tag (exception) symbol.
+	Tag {
+		/// Symbol flags.
+		flags: u32,
+		/// Index of the tag.
+		index: u32,
+		/// Symbol name, absent for undefined symbols without explicit name.
+		name: Option<String>,
+	},
+	/// Table symbol.
+	Table {
+		/// Symbol flags.
+		flags: u32,
+		/// Index of the table.
+		index: u32,
+		/// Symbol name, absent for undefined symbols without explicit name.
+		name: Option<String>,
+	},
+}
+
+impl SymbolInfo {
+	/// Symbol flags.
+	pub fn flags(&self) -> u32 {
+		match *self {
+			SymbolInfo::Function { flags, .. } |
+			SymbolInfo::Data { flags, .. } |
+			SymbolInfo::Global { flags, .. } |
+			SymbolInfo::Section { flags, .. } |
+			SymbolInfo::Tag { flags, .. } |
+			SymbolInfo::Table { flags, .. } => flags,
+		}
+	}
+
+	/// Symbol name, if any.
+	pub fn name(&self) -> Option<&str> {
+		match *self {
+			SymbolInfo::Function { ref name, .. } |
+			SymbolInfo::Global { ref name, .. } |
+			SymbolInfo::Tag { ref name, .. } |
+			SymbolInfo::Table { ref name, .. } => name.as_deref(),
+			SymbolInfo::Data { ref name, .. } => Some(name),
+			SymbolInfo::Section { .. } => None,
+		}
+	}
+
+	/// Whether the symbol refers to an import rather than a definition.
+	pub fn is_undefined(&self) -> bool {
+		self.flags() & WASM_SYM_UNDEFINED != 0
+	}
+
+	/// Deserialize symbol of the given kind, `None` if the kind is unknown.
+	fn deserialize_kind<R: io::Read>(kind: u8, rdr: &mut R) -> Result<Option<Self>, Error> {
+		if kind > SYMTAB_TABLE {
+			return Ok(None)
+		}
+
+		let flags: u32 = VarUint32::deserialize(rdr)?.into();
+		let has_name = flags & WASM_SYM_UNDEFINED == 0 || flags & WASM_SYM_EXPLICIT_NAME != 0;
+
+		Ok(Some(match kind {
+			SYMTAB_FUNCTION | SYMTAB_GLOBAL | SYMTAB_TAG | SYMTAB_TABLE => {
+				let index = VarUint32::deserialize(rdr)?.into();
+				let name = if has_name { Some(String::deserialize(rdr)?) } else { None };
+				match kind {
+					SYMTAB_FUNCTION => SymbolInfo::Function { flags, index, name },
+					SYMTAB_GLOBAL => SymbolInfo::Global { flags, index, name },
+					SYMTAB_TAG => SymbolInfo::Tag { flags, index, name },
+					_ => SymbolInfo::Table { flags, index, name },
+				}
+			},
+			SYMTAB_DATA => {
+				let name = String::deserialize(rdr)?;
+				let definition = if flags & WASM_SYM_UNDEFINED == 0 {
+					Some(DataSymbolDefinition {
+						segment: VarUint32::deserialize(rdr)?.into(),
+						offset: VarUint32::deserialize(rdr)?.into(),
+						size: VarUint32::deserialize(rdr)?.into(),
+					})
+				} else {
+					None
+				};
+				SymbolInfo::Data { flags, name, definition }
+			},
+			_ => SymbolInfo::Section { flags, index: VarUint32::deserialize(rdr)?.into() },
+		}))
+	}
+}
+
+impl Serialize for SymbolInfo {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		let (kind, flags, index, name) = match self {
+			SymbolInfo::Function { flags, index, name } => (SYMTAB_FUNCTION, flags, index, name),
+			SymbolInfo::Global { flags, index, name } => (SYMTAB_GLOBAL, flags, index, name),
+			SymbolInfo::Tag { flags, index, name } => (SYMTAB_TAG, flags, index, name),
+			SymbolInfo::Table { flags, index, name } => (SYMTAB_TABLE, flags, index, name),
+			SymbolInfo::Section { flags, index } => (SYMTAB_SECTION, flags, index, None),
+			SymbolInfo::Data { flags, name, definition } => {
+				Uint8::from(SYMTAB_DATA).serialize(wtr)?;
+				VarUint32::from(flags).serialize(wtr)?;
+				name.serialize(wtr)?;
+				if let Some(definition) = definition {
+					VarUint32::from(definition.segment).serialize(wtr)?;
+					VarUint32::from(definition.offset).serialize(wtr)?;
+					VarUint32::from(definition.size).serialize(wtr)?;
+				}
+				return Ok(())
+			},
+		};
+
+		Uint8::from(kind).serialize(wtr)?;
+		VarUint32::from(flags).serialize(wtr)?;
+		VarUint32::from(index).serialize(wtr)?;
+		if let Some(name) = name {
+			name.serialize(wtr)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{deserialize_buffer, deserialize_file, serialize},
+		DataSymbolDefinition, InitFunc, LinkingSection, LinkingSubsection, SymbolInfo,
+	};
+
+	#[test]
+	fn linking_section() {
+		let module = deserialize_file("./res/cases/v1/linking.wasm")
+			.expect("Module should be deserialized")
+			.parse_linking()
+			.expect("Linking section should be deserialized")
+			.parse_reloc()
+			.expect("Reloc sections should be deserialized");
+		let linking = module.linking_section().expect("linking.wasm has a linking section");
+
+		let symbols = linking.symbol_table().expect("linking.wasm has a symbol table");
+		assert_eq!(symbols.len(), 7);
+		assert_eq!(symbols[0].name(), Some("init"));
+		assert!(symbols[5].is_undefined());
+		assert_eq!(
+			symbols[5],
+			SymbolInfo::Function { flags: 0x10, index: 0, name: None },
+			"`ext` is imported and thus has no name of its own"
+		);
+		assert_eq!(
+			symbols[1],
+			SymbolInfo::Data {
+				flags: 0,
+				name: "counter".into(),
+				definition: Some(DataSymbolDefinition { segment: 0, offset: 0, size: 4 }),
+			}
+		);
+
+		assert_eq!(
+			linking.init_funcs().expect("linking.wasm has init funcs"),
+			&[InitFunc { priority: 65535, symbol_index: 0 }]
+		);
+
+		let segments = linking.segment_info().expect("linking.wasm has segment info");
+		assert_eq!(segments[0].name, ".bss.counter");
+
+		let comdats = linking.comdats().expect("linking.wasm has comdats");
+		assert_eq!(comdats[0].name, "shared");
+		assert_eq!(comdats[0].symbols.len(), 1);
+
+		assert_eq!(module.reloc_sections().count(), 2);
+	}
+
+	#[test]
+	fn linking_section_roundtrip() {
+		let module = deserialize_file("./res/cases/v1/linking.wasm")
+			.expect("Module should be deserialized")
+			.parse_linking()
+			.expect("Linking section should be deserialized")
+			.parse_reloc()
+			.expect("Reloc sections should be deserialized");
+
+		let reparsed = deserialize_buffer::<super::super::Module>(
+			&serialize(module.clone()).expect("Module should be serialized"),
+		)
+		.expect("Serialized module should be deserialized")
+		.parse_linking()
+		.expect("Linking section should be deserialized")
+		.parse_reloc()
+		.expect("Reloc sections should be deserialized");
+
+		assert_eq!(module.linking_section(), reparsed.linking_section());
+		assert!(module.reloc_sections().eq(reparsed.reloc_sections()));
+	}
+
+	#[test]
+	fn unknown_symbol_kind_is_preserved() {
+		let payload = vec![
+			0x02, // version
+			0x08, 0x05, // symbol table subsection of 5 bytes
+			0x02, // two symbols
+			0x03, 0x00, 0x01, // section symbol
+			0x7f, // unknown symbol kind
+		];
+		let linking: LinkingSection =
+			deserialize_buffer(&payload).expect("Linking section should be deserialized");
+		assert_eq!(
+			linking.subsections(),
+			&[LinkingSubsection::Raw { kind: 0x08, payload: payload[3..].to_vec() }]
+		);
+		assert_eq!(serialize(linking).expect("Linking section should be serialized"), payload);
+	}
+}
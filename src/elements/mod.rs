@@ -1,7 +1,7 @@
 //! Elements of the WebAssembly binary format.
 
 use crate::io;
-use alloc::{string::String, vec::Vec};
+use alloc::{boxed::Box, string::String, vec::Vec};
 
 use core::fmt;
 
@@ -24,35 +24,60 @@ macro_rules! buffered_read {
 	}};
 }
 
+mod const_expr;
+mod cost;
+mod diff;
+mod dylink_section;
+mod edit;
 mod export_entry;
+mod features;
 mod func;
 mod global_entry;
 mod import_entry;
 mod index_map;
+mod indices;
+mod interface;
+pub mod limits;
+mod linking_section;
 mod module;
 mod name_section;
+#[cfg(feature = "code_offsets")]
+mod offsets;
 mod ops;
-mod primitives;
+pub mod primitives;
+mod producers_section;
+mod proposals;
 mod reloc_section;
 mod section;
 mod segment;
+mod shift;
 mod types;
 
 pub use self::{
+	const_expr::{ConstValue, GlobalResolver},
+	cost::{CostModel, CostSummary, UniformCostModel},
+	diff::{diff, ChangedEntry, ModuleDiff, SectionDiff},
+	edit::Cursor,
 	export_entry::{ExportEntry, Internal},
+	features::Features,
 	global_entry::GlobalEntry,
 	import_entry::{External, GlobalType, ImportEntry, MemoryType, ResizableLimits, TableType},
-	module::{peek_size, ImportCountType, Module},
-	ops::{opcodes, BrTableData, InitExpr, Instruction, Instructions},
+	indices::{FuncIdx, GlobalIdx, LabelIdx, LocalIdx, MemIdx, TableIdx, TagIdx, TypeIdx},
+	interface::{ExportRef, ImportKind, ImportRef},
+	module::{peek_size, DeserializationIssue, ImportCountType, Module},
+	ops::{opcodes, BrTableData, InitExpr, Instruction, Instructions, OpcodeId},
 	primitives::{
 		CountedList, CountedListWriter, CountedWriter, Uint32, Uint64, Uint8, VarInt32, VarInt64,
 		VarInt7, VarUint1, VarUint32, VarUint64, VarUint7,
 	},
+	proposals::{Proposal, UnsupportedOpcode},
 	section::{
 		CodeSection, CustomSection, DataSection, ElementSection, ExportSection, FunctionSection,
-		GlobalSection, ImportSection, MemorySection, Section, TableSection, TypeSection,
+		GlobalSection, ImportSection, MemorySection, Section, SectionKey, TableSection,
+		TypeSection,
 	},
-	types::{BlockType, FunctionType, TableElementType, Type, ValueType},
+	shift::shift_function_indices,
+	types::{BlockType, FunctionType, FunctionTypeBuilder, TableElementType, Type, ValueType},
 };
 
 #[cfg(feature = "atomics")]
@@ -65,17 +90,32 @@ pub use self::ops::SimdInstruction;
 pub use self::ops::SignExtInstruction;
 
 #[cfg(feature = "bulk")]
-pub use self::ops::BulkInstruction;
+pub use self::{
+	ops::BulkInstruction,
+	segment::{PassiveMut, SegmentMode},
+};
+
+#[cfg(feature = "exceptions")]
+pub use self::{import_entry::TagType, ops::ExceptionsInstruction, section::TagSection};
 
 #[cfg(any(feature = "simd", feature = "atomics"))]
 pub use self::ops::MemArg;
 
 pub use self::{
+	dylink_section::{DylinkFormat, DylinkMemInfo, DylinkSection},
 	func::{Func, FuncBody, Local},
 	index_map::IndexMap,
+	linking_section::{
+		Comdat, ComdatSymbol, DataSymbolDefinition, InitFunc, LinkingSection, LinkingSubsection,
+		SegmentInfo, SymbolInfo,
+	},
 	name_section::{
 		FunctionNameSubsection, LocalNameSubsection, ModuleNameSubsection, NameMap, NameSection,
 	},
+	producers_section::{
+		ProducerValue, ProducersField, ProducersSection, PRODUCERS_FIELD_LANGUAGE,
+		PRODUCERS_FIELD_PROCESSED_BY, PRODUCERS_FIELD_SDK,
+	},
 	reloc_section::{RelocSection, RelocationEntry},
 	segment::{DataSegment, ElementSegment},
 };
@@ -131,6 +171,8 @@ pub enum Error {
 	UnknownInternalKind(u8),
 	/// Unknown opcode encountered.
 	UnknownOpcode(u8),
+	/// Unknown opcode encountered in a function body.
+	UnsupportedOpcode(Box<UnsupportedOpcode>),
 	#[cfg(feature = "simd")]
 	/// Unknown SIMD opcode encountered.
 	UnknownSimdOpcode(u32),
@@ -166,12 +208,70 @@ pub enum Error {
 	InconsistentCode,
 	/// Only flags 0, 1, and 2 are accepted on segments.
 	InvalidSegmentFlags(u32),
-	/// Sum of counts of locals is greater than 2^32.
+	#[cfg(feature = "exceptions")]
+	/// Invalid tag attribute (should be 0).
+	InvalidTagAttribute(u8),
+	/// Sum of counts of locals is greater than the limit.
 	TooManyLocals,
+	/// Number of function parameters is greater than the limit.
+	TooManyParams,
+	/// Number of functions is greater than the limit.
+	TooManyFunctions,
+	/// Memory size in pages is greater than the limit.
+	TooManyMemoryPages(u32),
+	/// Instruction not allowed in constant expressions.
+	NonConstantInstruction(Instruction),
+	/// Global can't be used in constant expressions.
+	UnresolvedGlobal(u32),
+	/// Type index out of the type section.
+	UnresolvedType(u32),
+	/// Constant expression doesn't produce exactly one value.
+	InvalidConstantExpression,
+	/// Blocks are not balanced by `end`s, at the given instruction position.
+	UnbalancedBlocks(usize),
+	/// Import added to the builder after it returned indices the import would shift.
+	ImportAfterIndices,
+	/// No custom section with the signature.
+	MissingSignature,
+	/// Several custom sections with the signature.
+	DuplicatedSignature,
+	/// Custom section with the signature is not the last section.
+	SignatureNotLast,
+	/// Reference to a function removed from the function index space.
+	RemovedFunctionReference(u32),
+	/// Segment mode not allowed for the segment, or active segment without an offset.
+	InvalidSegmentMode,
+	/// Several exports with the same name.
+	DuplicatedExport(String),
+	/// Cap of the deserialization config exceeded.
+	LimitExceeded {
+		/// Name of the cap, a field of `DeserializeConfig`.
+		limit: &'static str,
+		/// Index of the function body exceeding it.
+		function: u32,
+	},
+	/// Construct of a proposal which is not among the target features.
+	UnsupportedFeature {
+		/// Description of the construct.
+		construct: String,
+		/// Name of the proposal.
+		proposal: &'static str,
+	},
+	/// Instruction path not addressing an instruction of the code.
+	InvalidInstructionPath,
+	/// Integer not in the minimal LEB128 encoding, in the given function body.
+	NonMinimalEncoding(u32),
 	/// Duplicated name subsections.
 	DuplicatedNameSubsections(u8),
 	/// Unknown name subsection type.
 	UnknownNameSubsectionType(u8),
+	/// Limits of a memory or table not matching those of the import it is provided for.
+	IncompatibleLimits {
+		/// Limits of the import.
+		required: ResizableLimits,
+		/// Limits of the memory or table provided for it.
+		provided: ResizableLimits,
+	},
 }
 
 impl fmt::Display for Error {
@@ -192,6 +292,7 @@ impl fmt::Display for Error {
 			Error::UnknownExternalKind(kind) => write!(f, "Unknown external kind {}", kind),
 			Error::UnknownInternalKind(kind) => write!(f, "Unknown internal kind {}", kind),
 			Error::UnknownOpcode(opcode) => write!(f, "Unknown opcode {}", opcode),
+			Error::UnsupportedOpcode(ref opcode) => opcode.fmt(f),
 			#[cfg(feature = "simd")]
 			Error::UnknownSimdOpcode(opcode) => write!(f, "Unknown SIMD opcode {}", opcode),
 			Error::InvalidVarUint1(val) => write!(f, "Not an unsigned 1-bit integer: {}", val),
@@ -213,9 +314,41 @@ impl fmt::Display for Error {
 			Error::InconsistentCode =>
 				write!(f, "Number of function body entries and signatures does not match"),
 			Error::InvalidSegmentFlags(n) => write!(f, "Invalid segment flags: {}", n),
+			#[cfg(feature = "exceptions")]
+			Error::InvalidTagAttribute(attribute) => write!(f, "Invalid tag attribute: {}", attribute),
 			Error::TooManyLocals => write!(f, "Too many locals"),
+			Error::TooManyParams => write!(f, "Too many function parameters"),
+			Error::TooManyFunctions => write!(f, "Too many functions"),
+			Error::TooManyMemoryPages(pages) => write!(f, "Too many memory pages: {}", pages),
+			Error::NonConstantInstruction(ref instruction) =>
+				write!(f, "Instruction not allowed in constant expression: {}", instruction),
+			Error::UnresolvedGlobal(index) =>
+				write!(f, "Global {} can't be used in constant expression", index),
+			Error::UnresolvedType(index) => write!(f, "Type {} is not in the type section", index),
+			Error::InvalidConstantExpression =>
+				write!(f, "Constant expression must produce exactly one value"),
+			Error::UnbalancedBlocks(position) =>
+				write!(f, "Unbalanced blocks at instruction {}", position),
+			Error::ImportAfterIndices =>
+				write!(f, "Import declared after indices of its index space were returned"),
+			Error::MissingSignature => write!(f, "No signature section"),
+			Error::DuplicatedSignature => write!(f, "Several signature sections"),
+			Error::SignatureNotLast => write!(f, "Signature section is not the last section"),
+			Error::RemovedFunctionReference(index) =>
+				write!(f, "Reference to removed function {}", index),
+			Error::InvalidSegmentMode => write!(f, "Invalid segment mode"),
+			Error::DuplicatedExport(ref name) => write!(f, "Duplicated export {}", name),
+			Error::LimitExceeded { limit, function } =>
+				write!(f, "Limit {} exceeded by function body {}", limit, function),
+			Error::UnsupportedFeature { ref construct, proposal } =>
+				write!(f, "{} requires the {} proposal", construct, proposal),
+			Error::InvalidInstructionPath => write!(f, "Invalid instruction path"),
+			Error::NonMinimalEncoding(function) =>
+				write!(f, "Non-minimal integer encoding in function body {}", function),
 			Error::DuplicatedNameSubsections(n) => write!(f, "Duplicated name subsections: {}", n),
 			Error::UnknownNameSubsectionType(n) => write!(f, "Unknown subsection type: {}", n),
+			Error::IncompatibleLimits { required, provided } =>
+				write!(f, "Limits ({}) don't match the required limits ({})", provided, required),
 		}
 	}
 }
@@ -237,6 +370,7 @@ impl ::std::error::Error for Error {
 			Error::UnknownExternalKind(_) => "Unknown external kind",
 			Error::UnknownInternalKind(_) => "Unknown internal kind",
 			Error::UnknownOpcode(_) => "Unknown opcode",
+			Error::UnsupportedOpcode(_) => "Unsupported opcode",
 			#[cfg(feature = "simd")]
 			Error::UnknownSimdOpcode(_) => "Unknown SIMD opcode",
 			Error::InvalidVarUint1(_) => "Not an unsigned 1-bit integer",
@@ -256,9 +390,32 @@ impl ::std::error::Error for Error {
 			Error::InconsistentCode =>
 				"Number of function body entries and signatures does not match",
 			Error::InvalidSegmentFlags(_) => "Invalid segment flags",
+			#[cfg(feature = "exceptions")]
+			Error::InvalidTagAttribute(_) => "Invalid tag attribute",
 			Error::TooManyLocals => "Too many locals",
+			Error::TooManyParams => "Too many function parameters",
+			Error::TooManyFunctions => "Too many functions",
+			Error::TooManyMemoryPages(_) => "Too many memory pages",
+			Error::NonConstantInstruction(_) => "Instruction not allowed in constant expression",
+			Error::UnresolvedGlobal(_) => "Global can't be used in constant expression",
+			Error::UnresolvedType(_) => "Type is not in the type section",
+			Error::InvalidConstantExpression =>
+				"Constant expression must produce exactly one value",
+			Error::UnbalancedBlocks(_) => "Unbalanced blocks",
+			Error::ImportAfterIndices => "Import declared after indices were returned",
+			Error::MissingSignature => "No signature section",
+			Error::DuplicatedSignature => "Several signature sections",
+			Error::SignatureNotLast => "Signature section is not the last section",
+			Error::RemovedFunctionReference(_) => "Reference to removed function",
+			Error::InvalidSegmentMode => "Invalid segment mode",
+			Error::DuplicatedExport(_) => "Duplicated export",
+			Error::LimitExceeded { .. } => "Limit exceeded",
+			Error::UnsupportedFeature { .. } => "Unsupported feature",
+			Error::InvalidInstructionPath => "Invalid instruction path",
+			Error::NonMinimalEncoding(_) => "Non-minimal integer encoding",
 			Error::DuplicatedNameSubsections(_) => "Duplicated name subsections",
 			Error::UnknownNameSubsectionType(_) => "Unknown name subsections type",
+			Error::IncompatibleLimits { .. } => "Incompatible limits",
 		}
 	}
 }
@@ -301,6 +458,10 @@ impl From<Unparsed> for Vec<u8> {
 }
 
 /// Deserialize deserializable type from buffer.
+///
+/// No implementation limits are enforced, so a value may exceed the constants of [`limits`].
+/// Modules are checked with [`Module::deserialize_with_config`] or
+/// [`Limits::check`](limits::Limits::check).
 pub fn deserialize_buffer<T: Deserialize>(contents: &[u8]) -> Result<T, T::Error> {
 	let mut reader = io::Cursor::new(contents);
 	let result = T::deserialize(&mut reader)?;
@@ -3,28 +3,110 @@ use alloc::{borrow::ToOwned, string::String, vec::Vec};
 
 use super::{
 	deserialize_buffer,
+	dylink_section::DylinkSection,
+	limits::{DeserializeConfig, LebMode},
+	linking_section::LinkingSection,
 	name_section::NameSection,
+	producers_section::ProducersSection,
 	reloc_section::RelocSection,
 	section::{
-		CodeSection, CustomSection, DataSection, ElementSection, ExportSection, FunctionSection,
-		GlobalSection, ImportSection, MemorySection, Section, TableSection, TypeSection,
+		CodeDecoding, CodeSection, CustomSection, DataSection, ElementSection, ExportSection,
+		FunctionSection, GlobalSection, ImportSection, MemorySection, Section, SectionKey,
+		TableSection, TypeSection,
 	},
-	serialize, Deserialize, Error, External, Serialize, Uint32,
+	serialize, Deserialize, Error, External, FuncBody, Serialize, Uint32, VarUint32,
 };
 
-use core::cmp;
+#[cfg(feature = "exceptions")]
+use super::section::TagSection;
+
+#[cfg(feature = "code_offsets")]
+use super::offsets::{CodeOffsets, SectionRecorder};
+
+use core::{
+	cmp, fmt,
+	hash::{Hash, Hasher},
+};
 
 const WASM_MAGIC_NUMBER: [u8; 4] = [0x00, 0x61, 0x73, 0x6d];
 
 /// WebAssembly module
-#[derive(Debug, Clone, PartialEq)]
+///
+/// # Round trips
+///
+/// Sections, their entries and custom section payloads are kept in order, so
+/// [`Module::into_bytes`] writes a module read with [`Module::from_bytes`] back byte for byte,
+/// provided its integers are in the minimal LEB128 encoding. Toolchains sometimes pad LEB128
+/// numbers, section sizes in particular, and those are written back minimally. Re-encoded
+/// bytes then round trip exactly. [`Module::deserialize_with_config`] can instead reject, or
+/// keep, padded integers of function bodies, see [`LebMode`].
+#[derive(Debug, Clone)]
 pub struct Module {
 	magic: u32,
 	version: u32,
 	sections: Vec<Section>,
+	#[cfg(feature = "code_offsets")]
+	code_offsets: CodeOffsets,
+	normalized_encoding: bool,
+}
+
+impl PartialEq for Module {
+	/// Code offsets and whether the encoding was normalized describe the binary the module was
+	/// read from rather than the module, and are left out.
+	fn eq(&self, other: &Self) -> bool {
+		self.magic == other.magic &&
+			self.version == other.version &&
+			self.sections == other.sections
+	}
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+impl Eq for Module {}
+
+impl Hash for Module {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.magic.hash(state);
+		self.version.hash(state);
+		self.sections.hash(state);
+	}
+}
+
+/// Problem found while deserializing a damaged module with [`Module::deserialize_resilient`].
+#[derive(Debug)]
+pub struct DeserializationIssue {
+	offset: usize,
+	section_id: Option<u8>,
+	error: Error,
+}
+
+impl DeserializationIssue {
+	/// Offset in the input where the problem was found.
+	///
+	/// This is the start of the affected section, or of the first unreadable function body.
+	pub fn offset(&self) -> usize {
+		self.offset
+	}
+
+	/// Id of the affected section, `None` if the module header is invalid.
+	pub fn section_id(&self) -> Option<u8> {
+		self.section_id
+	}
+
+	/// Error the affected part failed with.
+	pub fn error(&self) -> &Error {
+		&self.error
+	}
+}
+
+impl fmt::Display for DeserializationIssue {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.section_id {
+			Some(id) => write!(f, "section {} at offset {}: {}", id, self.offset, self.error),
+			None => write!(f, "module header: {}", self.error),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 /// Type of the import entry to count
 pub enum ImportCountType {
 	/// Count functions
@@ -43,6 +125,9 @@ impl Default for Module {
 			magic: u32::from_le_bytes(WASM_MAGIC_NUMBER),
 			version: 1,
 			sections: Vec::with_capacity(16),
+			#[cfg(feature = "code_offsets")]
+			code_offsets: CodeOffsets::default(),
+			normalized_encoding: false,
 		}
 	}
 }
@@ -54,10 +139,164 @@ impl Module {
 	}
 
 	/// Construct a module from a slice.
+	///
+	/// Like [`deserialize_buffer`](super::deserialize_buffer), this doesn't enforce any
+	/// implementation limits, see [`Module::deserialize_with_config`].
 	pub fn from_bytes<T: AsRef<[u8]>>(input: T) -> Result<Self, Error> {
 		deserialize_buffer::<Module>(input.as_ref())
 	}
 
+	/// Construct a module from a slice, without decoding the instructions of function bodies.
+	///
+	/// Instructions of each body are kept as bytes until [`FuncBody::decode`] is called on
+	/// it, and undecoded bodies are serialized back verbatim. This makes loading much
+	/// cheaper when only module metadata, or a few functions, are of interest.
+	///
+	/// [`FuncBody::decode`]: super::FuncBody::decode
+	pub fn deserialize_lazy<T: AsRef<[u8]>>(input: T) -> Result<Self, Error> {
+		let input = input.as_ref();
+		let mut reader = io::Cursor::new(input);
+		let module = Module::deserialize_with(&mut reader, CodeDecoding::Lazy)?;
+		if reader.position() != input.len() {
+			return Err(io::Error::TrailingData.into())
+		}
+		Ok(module)
+	}
+
+	/// Construct a module from a slice, enforcing the caps of the config on the code section.
+	///
+	/// Bodies are decoded one after the other, and decoding stops at the first body exceeding
+	/// a cap, before its bytes are read. Integers of the bodies which are not in the minimal
+	/// LEB128 encoding are handled according to [`DeserializeConfig::leb_mode`]. Other
+	/// sections are deserialized as usual, then the module is checked against
+	/// [`DeserializeConfig::limits`].
+	pub fn deserialize_with_config<T: AsRef<[u8]>>(
+		input: T,
+		config: &DeserializeConfig,
+	) -> Result<Self, Error> {
+		let input = input.as_ref();
+		let mut reader = io::Cursor::new(input);
+		let mut module = Module::deserialize_with(&mut reader, CodeDecoding::Limited(config))?;
+		if reader.position() != input.len() {
+			return Err(io::Error::TrailingData.into())
+		}
+		if let Some(ref limits) = config.limits {
+			limits.check(&module)?;
+		}
+		if config.leb_mode == LebMode::Permissive {
+			let bodies = module.code_section_mut().map_or(&mut [][..], |code| code.bodies_mut());
+			let mut normalized = false;
+			for body in bodies.iter_mut().filter(|body| body.is_padded()) {
+				body.normalize();
+				normalized = true;
+			}
+			module.normalized_encoding = normalized;
+		}
+		Ok(module)
+	}
+
+	/// Whether padded integers of function bodies were re-encoded minimally by
+	/// [`Module::deserialize_with_config`], in which case the module doesn't serialize back to
+	/// its input bytes.
+	pub fn normalized_encoding(&self) -> bool {
+		self.normalized_encoding
+	}
+
+	/// Construct a module from a possibly damaged slice, on a best-effort basis.
+	///
+	/// Sections which fail to deserialize, or violate the section ordering, are skipped.
+	/// If the code section is damaged, the function bodies before the first unreadable one
+	/// are kept. Deserialization stops at the first section whose size can not be
+	/// determined, e.g. because the input is truncated. Every problem is reported as a
+	/// [`DeserializationIssue`], in input order.
+	///
+	/// The returned module may be inconsistent, e.g. have fewer bodies than functions.
+	pub fn deserialize_resilient<T: AsRef<[u8]>>(input: T) -> (Self, Vec<DeserializationIssue>) {
+		let input = input.as_ref();
+		let mut issues = Vec::new();
+
+		let mut reader = io::Cursor::new(input);
+		let (magic, version) = match Module::deserialize_header(&mut reader) {
+			Ok(header) => header,
+			Err(error) => {
+				issues.push(DeserializationIssue { offset: 0, section_id: None, error });
+				return (Module::default(), issues)
+			},
+		};
+		let mut module = Module { magic, version, ..Default::default() };
+
+		let mut offset = reader.position();
+		let mut last_section_rank = 0;
+		while offset < input.len() {
+			let section_id = input[offset];
+			let mut reader = io::Cursor::new(&input[offset..]);
+			let (section, next_offset) = match Section::deserialize(&mut reader) {
+				Ok(section) => (section, offset + reader.position()),
+				Err(error) => {
+					// Payload of the section, possibly truncated, if its size is readable.
+					let mut size_reader = io::Cursor::new(&input[offset + 1..]);
+					let payload = VarUint32::deserialize(&mut size_reader).ok().map(|size| {
+						let start = offset + 1 + size_reader.position();
+						let end = start.saturating_add(u32::from(size) as usize);
+						(start, cmp::min(end, input.len()))
+					});
+					let (start, end) = match payload {
+						Some(payload) => payload,
+						None => {
+							issues.push(DeserializationIssue {
+								offset,
+								section_id: Some(section_id),
+								error,
+							});
+							break
+						},
+					};
+
+					if section_id != 10 {
+						issues.push(DeserializationIssue {
+							offset,
+							section_id: Some(section_id),
+							error,
+						});
+						offset = end;
+						continue
+					}
+
+					let (bodies, issue) = salvage_bodies(input, start, end);
+					issues.push(issue.unwrap_or(DeserializationIssue {
+						offset,
+						section_id: Some(section_id),
+						error,
+					}));
+					(Section::Code(CodeSection::with_bodies(bodies)), end)
+				},
+			};
+
+			let rank = section.rank();
+			if rank != 0 {
+				if rank <= last_section_rank {
+					let error = if rank == last_section_rank {
+						Error::DuplicatedSections(section.order())
+					} else {
+						Error::SectionsOutOfOrder
+					};
+					issues.push(DeserializationIssue {
+						offset,
+						section_id: Some(section_id),
+						error,
+					});
+					offset = next_offset;
+					continue
+				}
+				last_section_rank = rank;
+			}
+			module.sections.push(section);
+			offset = next_offset;
+		}
+
+		(module, issues)
+	}
+
 	/// Serialize a module to a vector.
 	pub fn into_bytes(self) -> Result<Vec<u8>, Error> {
 		serialize::<Module>(self)
@@ -104,7 +343,7 @@ impl Module {
 		}
 
 		// Assume that the module is already well-ordered.
-		if let Some(pos) = sections.iter().position(|s| section.order() < s.order()) {
+		if let Some(pos) = sections.iter().position(|s| section.rank() < s.rank()) {
 			sections.insert(pos, section);
 		} else {
 			sections.push(section);
@@ -293,6 +532,28 @@ impl Module {
 		None
 	}
 
+	/// Exception tags section reference, if any.
+	#[cfg(feature = "exceptions")]
+	pub fn tag_section(&self) -> Option<&TagSection> {
+		for section in self.sections() {
+			if let Section::Tag(ref section) = *section {
+				return Some(section)
+			}
+		}
+		None
+	}
+
+	/// Exception tags section mutable reference, if any.
+	#[cfg(feature = "exceptions")]
+	pub fn tag_section_mut(&mut self) -> Option<&mut TagSection> {
+		for section in self.sections_mut() {
+			if let Section::Tag(ref mut section) = *section {
+				return Some(section)
+			}
+		}
+		None
+	}
+
 	/// Functions signatures section reference, if any.
 	pub fn function_section(&self) -> Option<&FunctionSection> {
 		for section in self.sections() {
@@ -399,6 +660,57 @@ impl Module {
 		None
 	}
 
+	/// Embed a signature of the module in the custom section `name`, placed last.
+	///
+	/// Other custom sections with that name are removed. The signature is expected to cover
+	/// [`Module::signed_payload`], computed by the caller with the cryptography of its choice.
+	pub fn embed_signature(&mut self, name: &str, signature: &[u8]) {
+		self.sections.retain(|section| section.key() != SectionKey::Custom(name));
+		self.sections
+			.push(Section::Custom(CustomSection::new(name.into(), signature.to_vec())));
+	}
+
+	/// Serialization of the module without the custom sections `name`, to be signed.
+	///
+	/// Integers are encoded in the minimal LEB128 encoding, so padding them in the binary
+	/// the module is read from doesn't change the payload.
+	pub fn signed_payload(&self, name: &str) -> Result<Vec<u8>, Error> {
+		let mut unsigned = self.clone();
+		unsigned.sections.retain(|section| section.key() != SectionKey::Custom(name));
+		unsigned.into_bytes()
+	}
+
+	/// Signed payload and the signature embedded in the custom section `name`, to be verified.
+	///
+	/// The signature section must be the only one with its name, and the last section of the
+	/// module, as written by [`Module::embed_signature`].
+	pub fn extract_signature(&self, name: &str) -> Result<(Vec<u8>, Vec<u8>), Error> {
+		let mut positions = self
+			.sections
+			.iter()
+			.enumerate()
+			.filter(|(_, section)| section.key() == SectionKey::Custom(name))
+			.map(|(position, _)| position);
+		let position = positions.next().ok_or(Error::MissingSignature)?;
+		if positions.next().is_some() {
+			return Err(Error::DuplicatedSignature)
+		}
+		if position + 1 != self.sections.len() {
+			return Err(Error::SignatureNotLast)
+		}
+		let signature = match self.sections[position] {
+			Section::Custom(ref custom) => custom.payload().to_vec(),
+			// Parsed custom section, re-encoded past its id.
+			ref section => {
+				let bytes = serialize(section.clone())?;
+				CustomSection::deserialize(&mut io::Cursor::new(&bytes[1..]))?
+					.payload()
+					.to_vec()
+			},
+		};
+		Ok((self.signed_payload(name)?, signature))
+	}
+
 	/// True if a name section is present.
 	///
 	/// NOTE: this can return true even if the section was not parsed, hence `names_section()` may return `None`
@@ -528,6 +840,196 @@ impl Module {
 		}
 	}
 
+	/// Linking section reference, if any.
+	///
+	/// NOTE: linking section is not parsed by default so `linking_section` could return None even if it exists.
+	/// Call `parse_linking` to parse linking section
+	pub fn linking_section(&self) -> Option<&LinkingSection> {
+		for section in self.sections() {
+			if let Section::Linking(ref sect) = *section {
+				return Some(sect)
+			}
+		}
+		None
+	}
+
+	/// Linking section mutable reference, if any.
+	///
+	/// NOTE: linking section is not parsed by default so `linking_section_mut` could return None even if it exists.
+	/// Call `parse_linking` to parse linking section
+	pub fn linking_section_mut(&mut self) -> Option<&mut LinkingSection> {
+		for section in self.sections_mut() {
+			if let Section::Linking(ref mut sect) = *section {
+				return Some(sect)
+			}
+		}
+		None
+	}
+
+	/// Relocation sections, in the order they appear in the module.
+	///
+	/// NOTE: reloc sections are not parsed by default. Call `parse_reloc` to parse them.
+	pub fn reloc_sections(&self) -> impl Iterator<Item = &RelocSection> {
+		self.sections()
+			.iter()
+			.filter_map(|s| if let Section::Reloc(s) = s { Some(s) } else { None })
+	}
+
+	/// Try to parse linking section in place.
+	///
+	/// Corresponding custom section with proper header will convert to linking section.
+	/// If it fails to be decoded, Err variant is returned with the list of
+	/// (index, Error) tuples of failed sections.
+	pub fn parse_linking(mut self) -> Result<Self, (Vec<(usize, Error)>, Self)> {
+		let mut parse_errors = Vec::new();
+
+		for (i, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Custom(ref custom) = *section {
+				if custom.name() == "linking" {
+					match deserialize_buffer::<LinkingSection>(custom.payload()) {
+						Ok(linking_section) => *section = Section::Linking(linking_section),
+						Err(e) => parse_errors.push((i, e)),
+					}
+				}
+			}
+		}
+
+		if !parse_errors.is_empty() {
+			Err((parse_errors, self))
+		} else {
+			Ok(self)
+		}
+	}
+
+	/// Producers section reference, if any.
+	///
+	/// NOTE: producers section is not parsed by default so `producers_section` could return None even if it exists.
+	/// Call `parse_producers` to parse producers section
+	pub fn producers_section(&self) -> Option<&ProducersSection> {
+		for section in self.sections() {
+			if let Section::Producers(ref sect) = *section {
+				return Some(sect)
+			}
+		}
+		None
+	}
+
+	/// Producers section mutable reference, if any.
+	///
+	/// NOTE: producers section is not parsed by default so `producers_section_mut` could return None even if it exists.
+	/// Call `parse_producers` to parse producers section
+	pub fn producers_section_mut(&mut self) -> Option<&mut ProducersSection> {
+		for section in self.sections_mut() {
+			if let Section::Producers(ref mut sect) = *section {
+				return Some(sect)
+			}
+		}
+		None
+	}
+
+	/// Try to parse producers section in place.
+	///
+	/// Corresponding custom section with proper header will convert to producers section.
+	/// If it fails to be decoded, Err variant is returned with the list of
+	/// (index, Error) tuples of failed sections.
+	pub fn parse_producers(mut self) -> Result<Self, (Vec<(usize, Error)>, Self)> {
+		let mut parse_errors = Vec::new();
+
+		for (i, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Custom(ref custom) = *section {
+				if custom.name() == "producers" {
+					match deserialize_buffer::<ProducersSection>(custom.payload()) {
+						Ok(producers_section) => *section = Section::Producers(producers_section),
+						Err(e) => parse_errors.push((i, e)),
+					}
+				}
+			}
+		}
+
+		if !parse_errors.is_empty() {
+			Err((parse_errors, self))
+		} else {
+			Ok(self)
+		}
+	}
+
+	/// Dynamic linking section reference, if any.
+	///
+	/// NOTE: dynamic linking section is not parsed by default so `dylink_section` could return None even if it exists.
+	/// Call `parse_dylink` to parse dynamic linking section
+	pub fn dylink_section(&self) -> Option<&DylinkSection> {
+		for section in self.sections() {
+			if let Section::Dylink(ref sect) = *section {
+				return Some(sect)
+			}
+		}
+		None
+	}
+
+	/// Dynamic linking section mutable reference, if any.
+	///
+	/// NOTE: dynamic linking section is not parsed by default so `dylink_section_mut` could return None even if it exists.
+	/// Call `parse_dylink` to parse dynamic linking section
+	pub fn dylink_section_mut(&mut self) -> Option<&mut DylinkSection> {
+		for section in self.sections_mut() {
+			if let Section::Dylink(ref mut sect) = *section {
+				return Some(sect)
+			}
+		}
+		None
+	}
+
+	/// Try to parse dynamic linking section in place.
+	///
+	/// Corresponding `dylink.0` or legacy `dylink` custom section will convert to dynamic
+	/// linking section. If it fails to be decoded, Err variant is returned with the list of
+	/// (index, Error) tuples of failed sections.
+	pub fn parse_dylink(mut self) -> Result<Self, (Vec<(usize, Error)>, Self)> {
+		let mut parse_errors = Vec::new();
+
+		for (i, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Custom(ref custom) = *section {
+				match DylinkSection::parse(custom.name(), custom.payload()) {
+					Ok(Some(dylink_section)) => *section = Section::Dylink(dylink_section),
+					Ok(None) => {},
+					Err(e) => parse_errors.push((i, e)),
+				}
+			}
+		}
+
+		if !parse_errors.is_empty() {
+			Err((parse_errors, self))
+		} else {
+			Ok(self)
+		}
+	}
+
+	/// URL of the source map of the module, from the `sourceMappingURL` custom section.
+	pub fn source_mapping_url(&self) -> Result<Option<String>, Error> {
+		self.custom_sections()
+			.find(|custom| custom.name() == "sourceMappingURL")
+			.map(|custom| deserialize_buffer::<String>(custom.payload()))
+			.transpose()
+	}
+
+	/// Set the URL of the source map of the module, in the `sourceMappingURL` custom section.
+	pub fn set_source_mapping_url(&mut self, url: &str) -> Result<(), Error> {
+		let payload = serialize(String::from(url))?;
+		self.set_custom_section("sourceMappingURL", payload);
+		Ok(())
+	}
+
+	/// Offsets of the instructions of every function body, from the start of the binary.
+	///
+	/// Offsets are recorded by [`Module::deserialize`](Deserialize::deserialize) and
+	/// [`Module::deserialize_lazy`], this is empty for any other module. They are not updated
+	/// when the module is changed and don't take part in comparisons of modules. Recording them
+	/// decodes the bodies, so [`Module::deserialize_lazy`] fails on malformed ones.
+	#[cfg(feature = "code_offsets")]
+	pub fn code_offsets(&self) -> &[Vec<u32>] {
+		&self.code_offsets.0
+	}
+
 	/// Count imports by provided type.
 	pub fn import_count(&self, count_type: ImportCountType) -> usize {
 		self.import_section()
@@ -571,14 +1073,65 @@ impl Module {
 		self.import_count(ImportCountType::Memory) +
 			self.memory_section().map(|ms| ms.entries().len()).unwrap_or(0)
 	}
+
+	/// Apply [`peephole`](crate::optimize::peephole) optimization to every function body,
+	/// decoding lazily deserialized bodies first.
+	///
+	/// Returns the total number of removed instructions.
+	pub fn optimize_all(&mut self) -> Result<usize, Error> {
+		let mut removed = 0;
+		if let Some(code_section) = self.code_section_mut() {
+			for body in code_section.bodies_mut() {
+				body.decode()?;
+				removed += crate::optimize::peephole(body);
+			}
+		}
+		Ok(removed)
+	}
+
+	/// Feed the serialized module into `hasher`.
+	///
+	/// Sections are serialized one by one, so the serialization of the whole module is
+	/// never kept in memory. With `exclude_custom`, custom sections (parsed ones included)
+	/// are skipped, so that stripping or adding debug information does not change the digest.
+	pub fn digest<H: Hasher>(&self, hasher: &mut H, exclude_custom: bool) -> Result<(), Error> {
+		let mut writer = HashWriter(hasher);
+		Uint32::from(self.magic).serialize(&mut writer)?;
+		Uint32::from(self.version).serialize(&mut writer)?;
+		for section in self.sections.iter().filter(|s| !(exclude_custom && s.is_custom())) {
+			section.clone().serialize(&mut writer)?;
+		}
+		Ok(())
+	}
+
+	/// Digest of every section of the module, in order.
+	///
+	/// Each digest is computed with a fresh `H` over the serialized section.
+	pub fn section_digests<H: Hasher + Default>(
+		&self,
+	) -> Result<Vec<(SectionKey<'_>, u64)>, Error> {
+		self.sections
+			.iter()
+			.map(|section| {
+				let mut hasher = H::default();
+				section.clone().serialize(&mut HashWriter(&mut hasher))?;
+				Ok((section.key(), hasher.finish()))
+			})
+			.collect()
+	}
 }
 
 impl Deserialize for Module {
 	type Error = super::Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
-		let mut sections = Vec::new();
+		Module::deserialize_with(reader, CodeDecoding::Eager)
+	}
+}
 
+impl Module {
+	/// Deserialize the magic number and version.
+	fn deserialize_header<R: io::Read>(reader: &mut R) -> Result<(u32, u32), Error> {
 		let mut magic = [0u8; 4];
 		reader.read(&mut magic)?;
 		if magic != WASM_MAGIC_NUMBER {
@@ -591,29 +1144,54 @@ impl Deserialize for Module {
 			return Err(Error::UnsupportedVersion(version))
 		}
 
-		let mut last_section_order = 0;
+		Ok((u32::from_le_bytes(magic), version))
+	}
+
+	fn deserialize_with<R: io::Read>(reader: &mut R, code: CodeDecoding) -> Result<Self, Error> {
+		#[cfg(feature = "code_offsets")]
+		let reader = &mut SectionRecorder::new(reader);
+		#[cfg(feature = "code_offsets")]
+		let mut code_offsets = CodeOffsets::default();
+		let mut sections = Vec::new();
+
+		let (magic, version) = Module::deserialize_header(reader)?;
+
+		let mut last_section_rank = 0;
 
 		loop {
-			match Section::deserialize(reader) {
+			#[cfg(feature = "code_offsets")]
+			reader.start_section();
+			match Section::deserialize_with(reader, code) {
 				Err(Error::UnexpectedEof) => break,
 				Err(e) => return Err(e),
 				Ok(section) => {
-					if section.order() != 0 {
-						match last_section_order {
-							x if x > section.order() => return Err(Error::SectionsOutOfOrder),
-							x if x == section.order() =>
-								return Err(Error::DuplicatedSections(last_section_order)),
+					#[cfg(feature = "code_offsets")]
+					if let Section::Code(_) = section {
+						code_offsets = reader.code_offsets()?;
+					}
+					if section.rank() != 0 {
+						match last_section_rank {
+							x if x > section.rank() => return Err(Error::SectionsOutOfOrder),
+							x if x == section.rank() =>
+								return Err(Error::DuplicatedSections(section.order())),
 							_ => {},
 						};
 
-						last_section_order = section.order();
+						last_section_rank = section.rank();
 					}
 					sections.push(section);
 				},
 			}
 		}
 
-		let module = Module { magic: u32::from_le_bytes(magic), version, sections };
+		let module = Module {
+			magic,
+			version,
+			sections,
+			#[cfg(feature = "code_offsets")]
+			code_offsets,
+			normalized_encoding: false,
+		};
 
 		if module.code_section().map(|cs| cs.bodies().len()).unwrap_or(0) !=
 			module.function_section().map(|fs| fs.entries().len()).unwrap_or(0)
@@ -639,6 +1217,45 @@ impl Serialize for Module {
 	}
 }
 
+/// Function bodies of the code section payload `input[start..end]` up to the first
+/// unreadable one, and the issue it failed with.
+fn salvage_bodies(
+	input: &[u8],
+	start: usize,
+	end: usize,
+) -> (Vec<FuncBody>, Option<DeserializationIssue>) {
+	let mut reader = io::Cursor::new(&input[start..end]);
+	let mut bodies = Vec::new();
+	let count: u32 = match VarUint32::deserialize(&mut reader) {
+		Ok(count) => count.into(),
+		Err(error) => {
+			let issue = DeserializationIssue { offset: start, section_id: Some(10), error };
+			return (bodies, Some(issue))
+		},
+	};
+	for _ in 0..count {
+		let offset = start + reader.position();
+		match FuncBody::deserialize(&mut reader) {
+			Ok(body) => bodies.push(body),
+			Err(error) => {
+				let issue = DeserializationIssue { offset, section_id: Some(10), error };
+				return (bodies, Some(issue))
+			},
+		}
+	}
+	(bodies, None)
+}
+
+/// Writer feeding everything written into a hasher.
+struct HashWriter<'a, H>(&'a mut H);
+
+impl<'a, H: Hasher> io::Write for HashWriter<'a, H> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+		self.0.write(buf);
+		Ok(())
+	}
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 struct PeekSection<'a> {
 	cursor: usize,
@@ -706,8 +1323,8 @@ pub fn peek_size(source: &[u8]) -> usize {
 mod integration_tests {
 	use super::{
 		super::{
-			deserialize_buffer, deserialize_file, serialize, CodeSection, ExportSection,
-			FunctionSection, Section, TypeSection,
+			deserialize_buffer, deserialize_file, serialize, CodeSection, Deserialize, Error,
+			ExportSection, FunctionSection, Section, TypeSection, VarUint32,
 		},
 		Module,
 	};
@@ -1078,4 +1695,511 @@ mod integration_tests {
 		let module_copy = Module::from_bytes(&module_copy).expect("failed to deserialize");
 		assert_eq!(module, module_copy);
 	}
+
+	#[test]
+	fn lazy_code() {
+		let bytes = std::fs::read("./res/cases/v1/with_names.wasm").expect("failed to read");
+		let eager = Module::from_bytes(&bytes).expect("failed to deserialize");
+		let mut lazy = Module::deserialize_lazy(&bytes).expect("failed to deserialize lazily");
+
+		let bodies = lazy.code_section_mut().expect("with_names.wasm has code").bodies_mut();
+		assert!(bodies.iter().all(|body| !body.is_decoded() && body.raw_code().is_some()));
+
+		// Undecoded bodies compare equal to their decoded counterparts.
+		assert_eq!(lazy, eager);
+
+		// Decoding a single function on demand matches the eager result.
+		let eager_bodies = eager.code_section().expect("with_names.wasm has code").bodies();
+		let bodies = lazy.code_section_mut().expect("with_names.wasm has code").bodies_mut();
+		let decoded = bodies[1].decode().expect("body to decode").clone();
+		assert_eq!(&decoded, eager_bodies[1].code());
+		assert!(bodies[1].is_decoded());
+		assert!(!bodies[0].is_decoded());
+
+		// Partially decoded module serializes to the same bytes as the eager one.
+		assert_eq!(
+			lazy.into_bytes().expect("failed to serialize"),
+			eager.into_bytes().expect("failed to serialize")
+		);
+	}
+
+	#[test]
+	fn lazy_code_access() {
+		use super::super::UniformCostModel;
+
+		let bytes = std::fs::read("./res/cases/v1/with_names.wasm").expect("failed to read");
+		let eager = Module::from_bytes(&bytes).expect("failed to deserialize");
+		let mut lazy = Module::deserialize_lazy(&bytes).expect("failed to deserialize lazily");
+		let eager_body = &eager.code_section().expect("with_names.wasm has code").bodies()[0];
+		let body = &mut lazy.code_section_mut().expect("with_names.wasm has code").bodies_mut()[0];
+
+		// Reading doesn't decode the body for good, writing does.
+		assert_eq!(body.try_code().expect("body to decode"), eager_body.code());
+		assert_eq!(body.code(), eager_body.code());
+		let copy = body.clone();
+		assert_eq!(copy.raw_code(), body.raw_code());
+		assert_eq!(copy.code(), eager_body.code());
+		let model = UniformCostModel::default();
+		assert_eq!(body.static_cost(&model), eager_body.static_cost(&model));
+		assert!(!body.is_decoded());
+		let mut expected = eager_body.clone();
+		let removed = crate::optimize::peephole(&mut expected);
+		assert_eq!(crate::optimize::peephole(body), removed);
+		assert!(body.is_decoded());
+		assert_eq!(body.code(), expected.code());
+	}
+
+	#[test]
+	fn lazy_code_malformed_body() {
+		let module = crate::builder::module()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.code()
+			.i32_const(1)
+			.drop()
+			.end()
+			.build()
+			.build()
+			.build()
+			.build();
+		let mut bytes = module.into_bytes().expect("failed to serialize");
+		// Turn `drop` into an unknown opcode.
+		let drop_position = bytes.len() - 2;
+		assert_eq!(bytes[drop_position], 0x1a);
+		bytes[drop_position] = 0xff;
+
+		assert!(Module::from_bytes(&bytes).is_err());
+		// Recording code offsets decodes the bodies.
+		if cfg!(feature = "code_offsets") {
+			assert!(Module::deserialize_lazy(&bytes).is_err());
+			return
+		}
+		let mut lazy = Module::deserialize_lazy(&bytes).expect("locals are still well-formed");
+		let body = &mut lazy.code_section_mut().expect("module has code").bodies_mut()[0];
+		assert!(body.try_code().is_err());
+		assert!(body.decode().is_err());
+		assert!(!body.is_decoded());
+	}
+
+	fn digest(module: &Module, exclude_custom: bool) -> u64 {
+		use core::hash::Hasher;
+
+		let mut hasher = std::collections::hash_map::DefaultHasher::new();
+		module.digest(&mut hasher, exclude_custom).expect("failed to digest");
+		hasher.finish()
+	}
+
+	#[test]
+	fn digest_is_stable() {
+		let module =
+			deserialize_file("./res/cases/v1/with_names.wasm").expect("Should be deserialized");
+		let reparsed =
+			Module::from_bytes(module.clone().into_bytes().expect("failed to serialize"))
+				.expect("failed to deserialize");
+		assert_eq!(digest(&module, false), digest(&reparsed, false));
+
+		let bytes = std::fs::read("./res/cases/v1/with_names.wasm").expect("failed to read");
+		let lazy = Module::deserialize_lazy(bytes).expect("failed to deserialize lazily");
+		assert_eq!(digest(&module, false), digest(&lazy, false));
+
+		type DefaultHasher = std::collections::hash_map::DefaultHasher;
+		assert_eq!(
+			module.section_digests::<DefaultHasher>().expect("failed to digest"),
+			reparsed.section_digests::<DefaultHasher>().expect("failed to digest")
+		);
+	}
+
+	#[test]
+	fn digest_changes_with_code() {
+		use super::super::{Instruction, SectionKey};
+
+		let module =
+			deserialize_file("./res/cases/v1/with_names.wasm").expect("Should be deserialized");
+		let mut changed = module.clone();
+		let instruction =
+			changed.code_section_mut().expect("with_names.wasm has code").bodies_mut()[0]
+				.code_mut()
+				.elements_mut()
+				.first_mut()
+				.expect("body has instructions");
+		assert_ne!(*instruction, Instruction::Nop);
+		*instruction = Instruction::Nop;
+
+		assert_ne!(digest(&module, false), digest(&changed, false));
+		assert_ne!(digest(&module, true), digest(&changed, true));
+
+		type DefaultHasher = std::collections::hash_map::DefaultHasher;
+		let before = module.section_digests::<DefaultHasher>().expect("failed to digest");
+		let after = changed.section_digests::<DefaultHasher>().expect("failed to digest");
+		for ((key, before), (_, after)) in before.into_iter().zip(after) {
+			if key == SectionKey::Id(0x0a) {
+				assert_ne!(before, after);
+			} else {
+				assert_eq!(before, after);
+			}
+		}
+	}
+
+	#[test]
+	fn digest_excludes_custom_sections() {
+		let module =
+			deserialize_file("./res/cases/v1/with_names.wasm").expect("Should be deserialized");
+		let mut stripped = module.clone();
+		stripped.sections_mut().retain(|section| !section.is_custom());
+		let mut extended = module.clone();
+		extended.set_custom_section("debug", vec![1, 2, 3]);
+
+		assert_eq!(digest(&module, true), digest(&stripped, true));
+		assert_eq!(digest(&module, true), digest(&extended, true));
+		assert_ne!(digest(&module, false), digest(&stripped, false));
+		assert_ne!(digest(&module, false), digest(&extended, false));
+	}
+
+	/// Offset, id and end of every section of a well-formed module.
+	fn section_spans(bytes: &[u8]) -> Vec<(usize, u8, usize)> {
+		let mut spans = Vec::new();
+		let mut offset = 8;
+		while offset < bytes.len() {
+			let mut reader = crate::io::Cursor::new(&bytes[offset + 1..]);
+			let size: u32 = VarUint32::deserialize(&mut reader).expect("valid size").into();
+			let end = offset + 1 + reader.position() + size as usize;
+			spans.push((offset, bytes[offset], end));
+			offset = end;
+		}
+		spans
+	}
+
+	#[test]
+	fn resilient_intact() {
+		let bytes = std::fs::read("./res/cases/v1/hello.wasm").expect("failed to read");
+		let (module, issues) = Module::deserialize_resilient(&bytes);
+		assert!(issues.is_empty());
+		assert_eq!(module, Module::from_bytes(&bytes).expect("Should be deserialized"));
+	}
+
+	#[test]
+	fn resilient_truncated() {
+		let bytes = std::fs::read("./res/cases/v1/hello.wasm").expect("failed to read");
+		let strict = Module::from_bytes(&bytes).expect("Should be deserialized");
+		let spans = section_spans(&bytes);
+		let (code_offset, _, code_end) =
+			*spans.iter().find(|(_, id, _)| *id == 10).expect("hello.wasm has code");
+		let code_index = spans.iter().position(|(_, id, _)| *id == 10).unwrap();
+
+		// In the header.
+		let (module, issues) = Module::deserialize_resilient(&bytes[..6]);
+		assert!(module.sections().is_empty());
+		assert_eq!(issues.len(), 1);
+		assert_eq!((issues[0].offset(), issues[0].section_id()), (0, None));
+		assert_eq!(issues[0].error().to_string(), "I/O Error: UnexpectedEof");
+
+		// At a section boundary.
+		let (module, issues) = Module::deserialize_resilient(&bytes[..code_offset]);
+		assert!(issues.is_empty());
+		assert_eq!(module.sections(), &strict.sections()[..code_index]);
+
+		// In the middle of the code section.
+		let cut = code_offset + (code_end - code_offset) / 2;
+		let (module, issues) = Module::deserialize_resilient(&bytes[..cut]);
+		assert_eq!(module.sections().len(), code_index + 1);
+		assert_eq!(&module.sections()[..code_index], &strict.sections()[..code_index]);
+		let bodies = module.code_section().expect("code is salvaged").bodies();
+		let strict_bodies = strict.code_section().expect("hello.wasm has code").bodies();
+		assert!(!bodies.is_empty() && bodies.len() < strict_bodies.len());
+		assert_eq!(bodies, &strict_bodies[..bodies.len()]);
+		assert_eq!(issues.len(), 1);
+		assert_eq!(issues[0].section_id(), Some(10));
+		assert!(issues[0].offset() > code_offset && issues[0].offset() < cut);
+		assert_eq!(issues[0].error().to_string(), "I/O Error: UnexpectedEof");
+
+		// In the middle of the data section.
+		let (data_offset, data_id, _) = spans[code_index + 1];
+		let (module, issues) = Module::deserialize_resilient(&bytes[..data_offset + 10]);
+		assert_eq!(module.sections(), &strict.sections()[..code_index + 1]);
+		assert_eq!(issues.len(), 1);
+		assert_eq!((issues[0].offset(), issues[0].section_id()), (data_offset, Some(data_id)));
+
+		// Anywhere.
+		for len in (0..bytes.len()).step_by(997) {
+			let (module, issues) = Module::deserialize_resilient(&bytes[..len]);
+			assert!(issues.len() <= 1);
+			assert!(module.sections().len() <= strict.sections().len());
+		}
+	}
+
+	#[test]
+	fn resilient_skips_damaged_section() {
+		let mut bytes = std::fs::read("./res/cases/v1/hello.wasm").expect("failed to read");
+		let strict = Module::from_bytes(&bytes).expect("Should be deserialized");
+		let spans = section_spans(&bytes);
+		let global_index = spans.iter().position(|(_, id, _)| *id == 6).expect("has globals");
+		let (global_offset, _, _) = spans[global_index];
+		bytes[global_offset] = 0x7f;
+
+		assert!(matches!(Module::from_bytes(&bytes), Err(Error::InvalidSectionId(0x7f))));
+		let (module, issues) = Module::deserialize_resilient(&bytes);
+		let mut expected = strict.sections().to_vec();
+		expected.remove(global_index);
+		assert_eq!(module.sections(), &expected[..]);
+		assert_eq!(issues.len(), 1);
+		assert_eq!((issues[0].offset(), issues[0].section_id()), (global_offset, Some(0x7f)));
+		assert!(matches!(issues[0].error(), Error::InvalidSectionId(0x7f)));
+		assert_eq!(
+			issues[0].to_string(),
+			format!("section 127 at offset {}: Invalid section id: 127", global_offset)
+		);
+	}
+
+	#[cfg(feature = "exceptions")]
+	#[test]
+	fn exceptions_round_trip() {
+		use super::super::{BlockType, ExceptionsInstruction::*, Instruction::*, TagType};
+
+		let module =
+			deserialize_file("./res/cases/v1/exceptions.wasm").expect("Should be deserialized");
+
+		// `__cpp_exception`, taking a pointer to the thrown object.
+		let tags = module.tag_section().expect("module has tags").entries();
+		assert_eq!(tags, &[TagType::new(2.into())]);
+		let bodies = module.code_section().expect("module has code").bodies();
+		assert_eq!(
+			bodies[0].code().elements()[4..7],
+			[Exceptions(Try(BlockType::NoResult)), Call(0.into()), Exceptions(Catch(0.into()))]
+		);
+		assert_eq!(bodies[1].code().elements()[4], Exceptions(CatchAll));
+		assert_eq!(bodies[1].code().elements()[8], Exceptions(Rethrow(0.into())));
+		assert_eq!(bodies[2].code().elements()[1], Exceptions(Throw(0.into())));
+
+		let bytes = module.clone().into_bytes().expect("Should be serialized");
+		assert_eq!(Module::from_bytes(bytes).expect("Should be deserialized"), module);
+	}
+
+	#[cfg(feature = "exceptions")]
+	#[test]
+	fn tag_section_order() {
+		use super::super::{GlobalSection, MemorySection, TagSection};
+
+		let mut module = Module::new(vec![
+			Section::Memory(MemorySection::default()),
+			Section::Global(GlobalSection::default()),
+		]);
+		module
+			.insert_section(Section::Tag(TagSection::default()))
+			.expect("tag to be inserted");
+		assert!(matches!(module.sections()[1], Section::Tag(_)));
+		let bytes = module.clone().into_bytes().expect("Should be serialized");
+		assert_eq!(Module::from_bytes(bytes).expect("Should be deserialized"), module);
+
+		module.sections_mut().swap(1, 2);
+		let bytes = module.into_bytes().expect("Should be serialized");
+		assert!(matches!(Module::from_bytes(bytes), Err(Error::SectionsOutOfOrder)));
+	}
+
+	#[cfg(not(feature = "exceptions"))]
+	#[test]
+	fn exceptions_not_supported() {
+		assert!(matches!(
+			deserialize_file("./res/cases/v1/exceptions.wasm"),
+			Err(Error::InvalidSectionId(13))
+		));
+	}
+
+	#[cfg(feature = "reference_types")]
+	#[test]
+	fn call_indirect_second_table() {
+		use super::super::{Instruction::*, Instructions};
+
+		let module = crate::builder::module()
+			.table()
+			.with_min(1)
+			.build()
+			.table()
+			.with_min(1)
+			.with_element(0, vec![0])
+			.build()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.with_instructions(Instructions::new(vec![I32Const(0), CallIndirect(0.into(), 1), End]))
+			.build()
+			.build()
+			.build();
+
+		let module = Module::from_bytes(module.into_bytes().expect("Should be serialized"))
+			.expect("Should be deserialized");
+		assert_eq!(module.table_section().expect("module has tables").entries().len(), 2);
+		let segment = &module.elements_section().expect("module has elements").entries()[0];
+		assert_eq!((segment.index(), segment.members()), (1, &[0][..]));
+		let body = &module.code_section().expect("module has code").bodies()[0];
+		assert_eq!(body.code().elements()[1], CallIndirect(0.into(), 1));
+	}
+
+	#[test]
+	fn fixtures_round_trip() {
+		let mut round_trips = 0;
+		for entry in std::fs::read_dir("./res/cases/v1").expect("fixtures to be listed") {
+			let path = entry.expect("fixture to be listed").path();
+			// The decoder benchmark input is too large for debug builds.
+			if path.extension().map_or(true, |extension| extension != "wasm") ||
+				path.ends_with("clang.wasm")
+			{
+				continue
+			}
+			let bytes = std::fs::read(&path).expect("fixture to be read");
+			// Some fixtures are malformed on purpose.
+			let module = match Module::from_bytes(&bytes) {
+				Ok(module) => module,
+				Err(_) => continue,
+			};
+
+			let encoded = module.into_bytes().expect("Should be serialized");
+			if encoded == bytes {
+				round_trips += 1;
+				continue
+			}
+			// Padded LEB128 numbers are re-encoded minimally, once.
+			assert!(encoded.len() < bytes.len(), "{} grew when re-encoded", path.display());
+			let reencoded = Module::from_bytes(&encoded)
+				.and_then(Module::into_bytes)
+				.expect("re-encoded module to round trip");
+			assert!(reencoded == encoded, "{} is not re-encoded stably", path.display());
+		}
+		assert!(round_trips >= 17);
+	}
+
+	#[test]
+	fn every_section_round_trips() {
+		use super::super::{BlockType, BrTableData, Instruction::*, Instructions, ValueType};
+		use crate::builder;
+
+		let code = vec![
+			Block(BlockType::Value(ValueType::I32)),
+			Loop(BlockType::NoResult),
+			GetLocal(0.into()),
+			BrTable(Box::new(BrTableData {
+				table: Box::new([0.into(), 1.into()]),
+				default: 0.into(),
+			})),
+			End,
+			I32Const(-1),
+			If(BlockType::Value(ValueType::I32)),
+			I32Load(2, 16),
+			Else,
+			CurrentMemory(0),
+			End,
+			End,
+			F64Const(f64::NAN.to_bits()),
+			I64TruncSF64,
+			GetGlobal(0.into()),
+			I64Add,
+			I64Store(3, 0),
+			I32Const(0),
+			CallIndirect(0.into(), 0),
+			Call(0.into()),
+			Select,
+			Drop,
+			End,
+		];
+		let module = builder::module()
+			.import()
+			.path("env", "f")
+			.external()
+			.func(0)
+			.build()
+			.function()
+			.main()
+			.signature()
+			.build()
+			.body()
+			.with_local(1, ValueType::I32)
+			.with_instructions(Instructions::new(code))
+			.build()
+			.build()
+			.table()
+			.with_min(1)
+			.with_element(0, vec![1])
+			.build()
+			.memory()
+			.with_min(1)
+			.with_data(8, b"data".to_vec())
+			.build()
+			.global()
+			.value_type()
+			.i64()
+			.mutable()
+			.init_expr(I64Const(1 << 40))
+			.build()
+			.export()
+			.field("main")
+			.internal()
+			.func(1)
+			.build()
+			.with_raw_custom_section("custom", vec![0, 1, 2])
+			.build();
+
+		let bytes = module.into_bytes().expect("Should be serialized");
+		let module = Module::from_bytes(&bytes).expect("Should be deserialized");
+		assert_eq!(module.sections().len(), 12);
+		assert_eq!(module.into_bytes().expect("Should be serialized"), bytes);
+	}
+
+	#[test]
+	fn source_mapping_url() {
+		let mut module =
+			deserialize_file("./res/cases/v1/hello.wasm").expect("Should be deserialized");
+		assert_eq!(module.source_mapping_url().expect("Should be decoded"), None);
+
+		module.set_source_mapping_url("http://localhost/hello.wasm.map").unwrap();
+		let bytes = module.into_bytes().expect("Should be serialized");
+		let module = Module::from_bytes(&bytes).expect("Should be deserialized");
+		assert_eq!(
+			module.source_mapping_url().expect("Should be decoded").as_deref(),
+			Some("http://localhost/hello.wasm.map")
+		);
+
+		let mut module = Module::default();
+		module.set_custom_section("sourceMappingURL", vec![5, b'a']);
+		assert!(module.source_mapping_url().is_err());
+	}
+
+	#[test]
+	fn signature() {
+		let mut module =
+			deserialize_file("./res/cases/v1/inc_i32.wasm").expect("module to deserialize");
+		let payload = module.signed_payload("signature").expect("module to serialize");
+		module.embed_signature("signature", &[1, 2, 3]);
+		module.set_custom_section("trailing", vec![]);
+		assert!(matches!(module.extract_signature("signature"), Err(Error::SignatureNotLast)));
+
+		module.embed_signature("signature", &[4, 5, 6]);
+		let bytes = module.into_bytes().expect("module to serialize");
+		let module = Module::from_bytes(bytes).expect("module to deserialize");
+		let (signed, signature) = module.extract_signature("signature").expect("signature");
+		assert_eq!(signature, [4, 5, 6]);
+		// The payload covers the other custom sections.
+		assert_eq!(signed[..payload.len()], payload[..]);
+		assert_eq!(signed[payload.len()..], b"\0\x09\x08trailing"[..]);
+
+		// The fixture pads section sizes, which doesn't change the signed payload.
+		let raw = std::fs::read("./res/cases/v1/inc_i32.wasm").expect("fixture to exist");
+		assert_ne!(payload, raw);
+		let reencoded = Module::from_bytes(&payload).expect("payload to deserialize");
+		assert_eq!(reencoded.signed_payload("signature").unwrap(), payload);
+	}
+
+	#[test]
+	fn invalid_signature() {
+		let mut module = Module::default();
+		assert!(matches!(module.extract_signature("signature"), Err(Error::MissingSignature)));
+		module.set_custom_section("signature", vec![1]);
+		let duplicate = module.sections()[0].clone();
+		module.sections_mut().push(duplicate);
+		assert!(matches!(module.extract_signature("signature"), Err(Error::DuplicatedSignature)));
+		module.embed_signature("signature", &[2]);
+		assert_eq!(module.sections().len(), 1);
+		assert_eq!(module.extract_signature("signature").unwrap().1, [2]);
+	}
 }
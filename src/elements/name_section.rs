@@ -1,5 +1,5 @@
 use crate::io;
-use alloc::string::String;
+use alloc::{boxed::Box, string::String, vec::Vec};
 
 use super::{
 	index_map::IndexMap, Deserialize, Error, Module, Serialize, Type, VarUint32, VarUint7,
@@ -8,9 +8,22 @@ use super::{
 const NAME_TYPE_MODULE: u8 = 0;
 const NAME_TYPE_FUNCTION: u8 = 1;
 const NAME_TYPE_LOCAL: u8 = 2;
+const NAME_TYPE_LABEL: u8 = 3;
+const NAME_TYPE_TYPE: u8 = 4;
+const NAME_TYPE_TABLE: u8 = 5;
+const NAME_TYPE_MEMORY: u8 = 6;
+const NAME_TYPE_GLOBAL: u8 = 7;
+const NAME_TYPE_ELEMENT_SEGMENT: u8 = 8;
+const NAME_TYPE_DATA_SEGMENT: u8 = 9;
+const NAME_TYPE_FIELD: u8 = 10;
+const NAME_TYPE_TAG: u8 = 11;
 
 /// Debug name information.
-#[derive(Clone, Debug, PartialEq)]
+///
+/// Subsections of the extended name section proposal are supported along with the module,
+/// function and local names. Subsections of unknown type are kept as is, and written back after
+/// the known ones.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct NameSection {
 	/// Module name subsection.
 	module: Option<ModuleNameSubsection>,
@@ -20,6 +33,43 @@ pub struct NameSection {
 
 	/// Local name subsection.
 	locals: Option<LocalNameSubsection>,
+
+	/// Subsections of the extended name section proposal, and unknown ones.
+	extended: Box<ExtendedNames>,
+}
+
+/// Subsections of a name section besides the module, function and local names.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+struct ExtendedNames {
+	/// Label names, by function then by label.
+	labels: Option<IndexMap<NameMap>>,
+
+	/// Type names.
+	types: Option<NameMap>,
+
+	/// Table names.
+	tables: Option<NameMap>,
+
+	/// Memory names.
+	memories: Option<NameMap>,
+
+	/// Global names.
+	globals: Option<NameMap>,
+
+	/// Element segment names.
+	element_segments: Option<NameMap>,
+
+	/// Data segment names.
+	data_segments: Option<NameMap>,
+
+	/// Field names, by type then by field.
+	fields: Option<IndexMap<NameMap>>,
+
+	/// Tag names.
+	tags: Option<NameMap>,
+
+	/// Type and payload of the subsections of unknown type.
+	unknown: Vec<(u8, Vec<u8>)>,
 }
 
 impl NameSection {
@@ -29,7 +79,7 @@ impl NameSection {
 		functions: Option<FunctionNameSubsection>,
 		locals: Option<LocalNameSubsection>,
 	) -> Self {
-		Self { module, functions, locals }
+		Self { module, functions, locals, extended: Box::default() }
 	}
 
 	/// Module name subsection of this section.
@@ -61,52 +111,149 @@ impl NameSection {
 	pub fn locals_mut(&mut self) -> &mut Option<LocalNameSubsection> {
 		&mut self.locals
 	}
+
+	/// Type and payload of the subsections of unknown type, in input order.
+	pub fn unknown_subsections(&self) -> &[(u8, Vec<u8>)] {
+		&self.extended.unknown
+	}
+
+	/// Type and payload of the subsections of unknown type (mutable).
+	pub fn unknown_subsections_mut(&mut self) -> &mut Vec<(u8, Vec<u8>)> {
+		&mut self.extended.unknown
+	}
+}
+
+macro_rules! name_maps {
+	($($field:ident, $field_mut:ident: $map:ty, $what:literal;)*) => {
+		impl NameSection {
+			$(
+				#[doc = concat!($what, " of this section.")]
+				pub fn $field(&self) -> Option<&$map> {
+					self.extended.$field.as_ref()
+				}
+
+				#[doc = concat!($what, " of this section (mutable).")]
+				pub fn $field_mut(&mut self) -> &mut Option<$map> {
+					&mut self.extended.$field
+				}
+			)*
+		}
+	};
+}
+
+name_maps! {
+	labels, labels_mut: IndexMap<NameMap>, "Label names by function, then by label index,";
+	types, types_mut: NameMap, "Type names";
+	tables, tables_mut: NameMap, "Table names";
+	memories, memories_mut: NameMap, "Memory names";
+	globals, globals_mut: NameMap, "Global names";
+	element_segments, element_segments_mut: NameMap, "Element segment names";
+	data_segments, data_segments_mut: NameMap, "Data segment names";
+	fields, fields_mut: IndexMap<NameMap>, "Field names by type, then by field index,";
+	tags, tags_mut: NameMap, "Tag names";
 }
 
 impl NameSection {
 	/// Deserialize a name section.
+	///
+	/// Names must refer to the entities of the module. Labels are only checked against the
+	/// size of the largest function body, and there are no fields to name since types are
+	/// function types.
 	pub fn deserialize<R: io::Read>(module: &Module, rdr: &mut R) -> Result<Self, Error> {
-		let mut module_name: Option<ModuleNameSubsection> = None;
-		let mut function_names: Option<FunctionNameSubsection> = None;
-		let mut local_names: Option<LocalNameSubsection> = None;
+		fn read_once<T>(
+			subsection: &mut Option<T>,
+			name_type: u8,
+			read: impl FnOnce() -> Result<T, Error>,
+		) -> Result<(), Error> {
+			if subsection.is_some() {
+				return Err(Error::DuplicatedNameSubsections(name_type))
+			}
+			*subsection = Some(read()?);
+			Ok(())
+		}
 
+		fn read_indirect(
+			outer_space: usize,
+			inner_space: usize,
+			rdr: &mut io::Cursor<&[u8]>,
+		) -> Result<IndexMap<NameMap>, Error> {
+			let read_inner =
+				|_: u32, rdr: &mut io::Cursor<&[u8]>| IndexMap::deserialize(inner_space, rdr);
+			IndexMap::deserialize_with(outer_space, &read_inner, rdr)
+		}
+
+		let functions = module.functions_space();
+		let labels = module.code_section().map_or(0, |section| {
+			let body_size = |body: &super::FuncBody| match body.raw_code() {
+				Some(raw) => raw.len(),
+				None => body.code().elements().len(),
+			};
+			section.bodies().iter().map(body_size).max().unwrap_or(0)
+		});
+		let types = module.type_section().map_or(0, |section| section.types().len());
+		let element_segments =
+			module.elements_section().map_or(0, |section| section.entries().len());
+		let data_segments = module.data_section().map_or(0, |section| section.entries().len());
+
+		let mut names = NameSection::new(None, None, None);
 		while let Ok(raw_subsection_type) = VarUint7::deserialize(rdr) {
 			let subsection_type = raw_subsection_type.into();
-			// deserialize the section size
 			let size: usize = VarUint32::deserialize(rdr)?.into();
+			let payload = buffered_read!(1024, size, rdr);
+			let mut subsection = io::Cursor::new(&payload[..]);
+			let reader = &mut subsection;
+			let extended = &mut *names.extended;
 
 			match subsection_type {
-				NAME_TYPE_MODULE => {
-					if module_name.is_some() {
-						return Err(Error::DuplicatedNameSubsections(NAME_TYPE_FUNCTION))
-					}
-					module_name = Some(ModuleNameSubsection::deserialize(rdr)?);
-				},
-
-				NAME_TYPE_FUNCTION => {
-					if function_names.is_some() {
-						return Err(Error::DuplicatedNameSubsections(NAME_TYPE_FUNCTION))
-					}
-					function_names = Some(FunctionNameSubsection::deserialize(module, rdr)?);
-				},
-
-				NAME_TYPE_LOCAL => {
-					if local_names.is_some() {
-						return Err(Error::DuplicatedNameSubsections(NAME_TYPE_LOCAL))
-					}
-					local_names = Some(LocalNameSubsection::deserialize(module, rdr)?);
-				},
-
+				NAME_TYPE_MODULE => read_once(&mut names.module, subsection_type, || {
+					ModuleNameSubsection::deserialize(reader)
+				})?,
+				NAME_TYPE_FUNCTION => read_once(&mut names.functions, subsection_type, || {
+					FunctionNameSubsection::deserialize(module, reader)
+				})?,
+				NAME_TYPE_LOCAL => read_once(&mut names.locals, subsection_type, || {
+					LocalNameSubsection::deserialize(module, reader)
+				})?,
+				NAME_TYPE_LABEL => read_once(&mut extended.labels, subsection_type, || {
+					read_indirect(functions, labels, reader)
+				})?,
+				NAME_TYPE_TYPE => read_once(&mut extended.types, subsection_type, || {
+					IndexMap::deserialize(types, reader)
+				})?,
+				NAME_TYPE_TABLE => read_once(&mut extended.tables, subsection_type, || {
+					IndexMap::deserialize(module.table_space(), reader)
+				})?,
+				NAME_TYPE_MEMORY => read_once(&mut extended.memories, subsection_type, || {
+					IndexMap::deserialize(module.memory_space(), reader)
+				})?,
+				NAME_TYPE_GLOBAL => read_once(&mut extended.globals, subsection_type, || {
+					IndexMap::deserialize(module.globals_space(), reader)
+				})?,
+				NAME_TYPE_ELEMENT_SEGMENT =>
+					read_once(&mut extended.element_segments, subsection_type, || {
+						IndexMap::deserialize(element_segments, reader)
+					})?,
+				NAME_TYPE_DATA_SEGMENT =>
+					read_once(&mut extended.data_segments, subsection_type, || {
+						IndexMap::deserialize(data_segments, reader)
+					})?,
+				NAME_TYPE_FIELD => read_once(&mut extended.fields, subsection_type, || {
+					read_indirect(types, 0, reader)
+				})?,
+				NAME_TYPE_TAG => read_once(&mut extended.tags, subsection_type, || {
+					IndexMap::deserialize(tag_space(module), reader)
+				})?,
 				_ => {
-					// Consume the entire subsection size and drop it. This allows other sections to still be
-					// consumed if there are any.
-					let mut buf = vec![0; size];
-					rdr.read(&mut buf)?;
+					extended.unknown.push((subsection_type, payload.clone()));
+					continue
 				},
-			};
+			}
+			if subsection.position() != payload.len() {
+				return Err(io::Error::InvalidData.into())
+			}
 		}
 
-		Ok(Self { module: module_name, functions: function_names, locals: local_names })
+		Ok(names)
 	}
 }
 
@@ -124,30 +271,60 @@ impl Serialize for NameSection {
 			wtr.write(name_payload).map_err(Into::into)
 		}
 
-		if let Some(module_name_subsection) = self.module {
-			let mut buffer = vec![];
-			module_name_subsection.serialize(&mut buffer)?;
-			serialize_subsection(wtr, NAME_TYPE_MODULE, &buffer)?;
-		}
-
-		if let Some(function_name_subsection) = self.functions {
-			let mut buffer = vec![];
-			function_name_subsection.serialize(&mut buffer)?;
-			serialize_subsection(wtr, NAME_TYPE_FUNCTION, &buffer)?;
+		fn serialize_known<W: io::Write, T: Serialize<Error = Error>>(
+			wtr: &mut W,
+			name_type: u8,
+			subsection: Option<T>,
+		) -> Result<(), Error> {
+			if let Some(subsection) = subsection {
+				let mut buffer = vec![];
+				subsection.serialize(&mut buffer)?;
+				serialize_subsection(wtr, name_type, &buffer)?;
+			}
+			Ok(())
 		}
 
-		if let Some(local_name_subsection) = self.locals {
-			let mut buffer = vec![];
-			local_name_subsection.serialize(&mut buffer)?;
-			serialize_subsection(wtr, NAME_TYPE_LOCAL, &buffer)?;
+		serialize_known(wtr, NAME_TYPE_MODULE, self.module)?;
+		serialize_known(wtr, NAME_TYPE_FUNCTION, self.functions)?;
+		serialize_known(wtr, NAME_TYPE_LOCAL, self.locals)?;
+		let extended = *self.extended;
+		serialize_known(wtr, NAME_TYPE_LABEL, extended.labels)?;
+		serialize_known(wtr, NAME_TYPE_TYPE, extended.types)?;
+		serialize_known(wtr, NAME_TYPE_TABLE, extended.tables)?;
+		serialize_known(wtr, NAME_TYPE_MEMORY, extended.memories)?;
+		serialize_known(wtr, NAME_TYPE_GLOBAL, extended.globals)?;
+		serialize_known(wtr, NAME_TYPE_ELEMENT_SEGMENT, extended.element_segments)?;
+		serialize_known(wtr, NAME_TYPE_DATA_SEGMENT, extended.data_segments)?;
+		serialize_known(wtr, NAME_TYPE_FIELD, extended.fields)?;
+		serialize_known(wtr, NAME_TYPE_TAG, extended.tags)?;
+		for (name_type, payload) in extended.unknown {
+			serialize_subsection(wtr, name_type, &payload)?;
 		}
 
 		Ok(())
 	}
 }
 
+/// Number of tags, imported or not.
+fn tag_space(module: &Module) -> usize {
+	#[cfg(feature = "exceptions")]
+	{
+		let imports = module.import_section().map_or(&[][..], |section| section.entries());
+		let imported = imports
+			.iter()
+			.filter(|entry| matches!(*entry.external(), super::External::Tag(_)))
+			.count();
+		imported + module.tag_section().map_or(0, |section| section.entries().len())
+	}
+	#[cfg(not(feature = "exceptions"))]
+	{
+		let _ = module;
+		0
+	}
+}
+
 /// The name of this module.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ModuleNameSubsection {
 	name: String,
 }
@@ -187,7 +364,7 @@ impl Deserialize for ModuleNameSubsection {
 }
 
 /// The names of the functions in this module.
-#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct FunctionNameSubsection {
 	names: NameMap,
 }
@@ -222,7 +399,7 @@ impl Serialize for FunctionNameSubsection {
 }
 
 /// The names of the local variables in this module's functions.
-#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct LocalNameSubsection {
 	local_names: IndexMap<NameMap>,
 }
@@ -294,7 +471,11 @@ pub type NameMap = IndexMap<String>;
 
 #[cfg(test)]
 mod tests {
-	use super::*;
+	use super::{
+		super::{serialize, Section},
+		*,
+	};
+	use crate::io::Read;
 
 	// A helper function for the tests. Serialize a section, deserialize it,
 	// and make sure it matches the original.
@@ -369,4 +550,106 @@ mod tests {
 		let locals = local_names.local_names().get(1).expect("entry #1 should be present");
 		assert_eq!(locals.get(0).expect("entry #0 should be present"), "def");
 	}
+
+	/// Module with an entity of each kind, named by a section with every known subsection and
+	/// an unknown one.
+	fn named_module() -> (Module, NameSection) {
+		let mut module = crate::builder::module()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.build()
+			.build()
+			.global()
+			.value_type()
+			.i32()
+			.build()
+			.table()
+			.with_min(1)
+			.with_element(0, vec![0])
+			.build()
+			.memory()
+			.with_min(1)
+			.with_data(0, vec![1])
+			.build()
+			.build();
+
+		let map = |name: &str| {
+			let mut map = NameMap::default();
+			map.insert(0, name.to_string());
+			map
+		};
+		let by_index = |inner: NameMap| {
+			let mut map = IndexMap::default();
+			map.insert(0, inner);
+			map
+		};
+		let mut functions = FunctionNameSubsection::default();
+		*functions.names_mut() = map("main");
+		let mut locals = LocalNameSubsection::default();
+		*locals.local_names_mut() = by_index(NameMap::default());
+		let mut names =
+			NameSection::new(Some(ModuleNameSubsection::new("m")), Some(functions), Some(locals));
+		*names.labels_mut() = Some(by_index(map("exit")));
+		*names.types_mut() = Some(map("callback"));
+		*names.tables_mut() = Some(map("functions"));
+		*names.memories_mut() = Some(map("heap"));
+		*names.globals_mut() = Some(map("stack_pointer"));
+		*names.element_segments_mut() = Some(map("vtable"));
+		*names.data_segments_mut() = Some(map(".rodata"));
+		*names.fields_mut() = Some(by_index(NameMap::default()));
+		*names.tags_mut() = Some(NameMap::default());
+		names.unknown_subsections_mut().push((42, vec![1, 2, 3]));
+		module.insert_section(Section::Name(names.clone())).unwrap();
+		(module, names)
+	}
+
+	#[test]
+	fn every_subsection() {
+		let (module, names) = named_module();
+		let bytes = serialize(module).unwrap();
+		let module = Module::from_bytes(&bytes).unwrap();
+		let payload = module.custom_sections().next().unwrap().payload();
+		let mut reader = io::Cursor::new(payload);
+		let mut subsections = vec![];
+		while let Ok(name_type) = VarUint7::deserialize(&mut reader) {
+			subsections.push(u8::from(name_type));
+			let size: usize = VarUint32::deserialize(&mut reader).unwrap().into();
+			reader.read(&mut vec![0; size]).unwrap();
+		}
+		assert_eq!(subsections, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 42]);
+
+		let module = module.parse_names().unwrap();
+		assert_eq!(module.names_section(), Some(&names));
+		assert_eq!(names.globals().unwrap().get(0).map(String::as_str), Some("stack_pointer"));
+		assert_eq!(serialize(module).unwrap(), bytes);
+	}
+
+	#[test]
+	fn global_names() {
+		let bytes = std::fs::read("./res/cases/v1/global_section.wasm").expect("fixture to exist");
+		let module = Module::from_bytes(&bytes).unwrap().parse_names().unwrap();
+		let globals = module.names_section().unwrap().globals().expect("global names");
+		assert_eq!(globals.len(), 55);
+		assert_eq!(globals.get(0).map(String::as_str), Some("~lib/rt/stub/offset"));
+		assert_eq!(serialize(module).unwrap(), bytes);
+	}
+
+	#[test]
+	fn invalid_subsections() {
+		let (module, _) = named_module();
+		let deserialize =
+			|payload: &[u8]| NameSection::deserialize(&module, &mut io::Cursor::new(payload));
+		let global = [7, 4, 1, 0, 1, b'g'];
+		assert!(deserialize(&global).is_ok());
+		assert!(matches!(
+			deserialize(&[&global[..], &global[..]].concat()),
+			Err(Error::DuplicatedNameSubsections(7))
+		));
+		// No second global, no field to name, and a size not matching the names.
+		assert!(deserialize(&[7, 4, 1, 1, 1, b'g']).is_err());
+		assert!(deserialize(&[10, 5, 1, 0, 1, 0, 0]).is_err());
+		assert!(deserialize(&[7, 5, 1, 0, 1, b'g', 0]).is_err());
+	}
 }
@@ -0,0 +1,105 @@
+use super::{CountedList, Deserialize, Error, Instruction, Local, VarUint32, VarUint7};
+use crate::io;
+use alloc::vec::Vec;
+
+/// Id of the code section, the only one whose bytes are recorded.
+const CODE_SECTION_ID: u8 = 0x0a;
+
+/// Offsets of the instructions of every function body.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct CodeOffsets(pub(crate) Vec<Vec<u32>>);
+
+/// Reader keeping the position in the module and the bytes of the current section, if it is
+/// the code section.
+pub(crate) struct SectionRecorder<'a, R> {
+	inner: &'a mut R,
+	position: usize,
+	section_start: usize,
+	section: Vec<u8>,
+}
+
+impl<'a, R: io::Read> SectionRecorder<'a, R> {
+	pub(crate) fn new(inner: &'a mut R) -> Self {
+		SectionRecorder { inner, position: 0, section_start: 0, section: Vec::new() }
+	}
+
+	/// Forget the bytes read so far, the next section starts here.
+	pub(crate) fn start_section(&mut self) {
+		self.section_start = self.position;
+		self.section.clear();
+	}
+
+	/// Offsets of the instructions in the code section which was just read.
+	///
+	/// Instructions of lazily deserialized bodies are decoded here, and fail if malformed.
+	pub(crate) fn code_offsets(&self) -> Result<CodeOffsets, Error> {
+		let mut reader = io::Cursor::new(&self.section[..]);
+		read_code_offsets(&mut reader, self.section_start).map(CodeOffsets)
+	}
+}
+
+impl<'a, R: io::Read> io::Read for SectionRecorder<'a, R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<()> {
+		self.inner.read(buf)?;
+		// Sections start with their id, so the first byte tells whether to record the section.
+		let first = self.position == self.section_start;
+		if first && buf.first() == Some(&CODE_SECTION_ID) || !self.section.is_empty() {
+			self.section.extend_from_slice(buf);
+		}
+		self.position += buf.len();
+		Ok(())
+	}
+}
+
+fn read_code_offsets(
+	reader: &mut io::Cursor<&[u8]>,
+	section_start: usize,
+) -> Result<Vec<Vec<u32>>, Error> {
+	// Section id and payload size.
+	VarUint7::deserialize(reader)?;
+	VarUint32::deserialize(reader)?;
+
+	let count: usize = VarUint32::deserialize(reader)?.into();
+	let mut functions = Vec::with_capacity(count);
+	for _ in 0..count {
+		let size: usize = VarUint32::deserialize(reader)?.into();
+		let end = reader.position() + size;
+		CountedList::<Local>::deserialize(reader)?;
+
+		let mut offsets = Vec::new();
+		while reader.position() < end {
+			offsets.push((section_start + reader.position()) as u32);
+			Instruction::deserialize(reader)?;
+		}
+		functions.push(offsets);
+	}
+	Ok(functions)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::{deserialize_file, Module};
+
+	#[test]
+	fn instruction_offsets() {
+		let module =
+			deserialize_file("./res/cases/v1/inc_i32.wasm").expect("module to deserialize");
+		let offsets = module.code_offsets();
+		assert_eq!(offsets.len(), 3);
+		// `local.get 0`, `i32.const 1`, `i32.add` and `end`.
+		assert_eq!(offsets[0], [0xc2, 0xc4, 0xc6, 0xc7]);
+		assert_eq!(offsets[1], [0xce, 0xcf]);
+		// `block`, `global.get 0`, `global.set 2`, then `i32.const 5242880` spans 5 bytes.
+		assert_eq!(offsets[2][..5], [0xd6, 0xd8, 0xda, 0xdc, 0xde]);
+		assert_eq!(offsets[2][5..], [0xe3, 0xe4, 0xe6, 0xe8, 0xe9]);
+
+		let bytes = std::fs::read("./res/cases/v1/inc_i32.wasm").expect("fixture to exist");
+		let lazy = Module::deserialize_lazy(&bytes).expect("module to deserialize");
+		assert_eq!(lazy.code_offsets(), offsets);
+		// The module is re-encoded with other offsets, which don't take part in comparisons.
+		let reencoded = Module::from_bytes(module.clone().into_bytes().unwrap()).unwrap();
+		assert_ne!(reencoded.code_offsets(), offsets);
+		assert_eq!(reencoded, module);
+		assert!(Module::default().code_offsets().is_empty());
+	}
+}
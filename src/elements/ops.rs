@@ -1,13 +1,16 @@
+#[cfg(feature = "exceptions")]
+use super::TagIdx;
 use super::{
-	BlockType, CountedList, CountedListWriter, Deserialize, Error, Serialize, Uint32, Uint64,
-	Uint8, VarInt32, VarInt64, VarUint32,
+	BlockType, ConstValue, CountedList, CountedListWriter, Deserialize, Error, FuncIdx, GlobalIdx,
+	LabelIdx, LocalIdx, Serialize, TypeIdx, Uint32, Uint64, Uint8, UnsupportedOpcode, VarInt32,
+	VarInt64, VarUint32,
 };
 use crate::io;
 use alloc::{boxed::Box, vec::Vec};
 use core::fmt;
 
 /// List of instructions (usually inside a block section).
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Instructions(Vec<Instruction>);
 
 impl Instructions {
@@ -30,18 +33,45 @@ impl Instructions {
 	pub fn elements_mut(&mut self) -> &mut Vec<Instruction> {
 		&mut self.0
 	}
+
+	/// Exact size of the instruction list in the binary encoding, see
+	/// [`Instruction::serialized_size`].
+	pub fn serialized_size(&self) -> Result<usize, Error> {
+		self.0.iter().map(Instruction::serialized_size).sum()
+	}
 }
 
 impl Deserialize for Instructions {
 	type Error = Error;
 
+	/// Deserialize instructions up to the `end` closing the sequence.
+	///
+	/// Unknown opcodes are reported as [`Error::UnsupportedOpcode`], with the offset of the
+	/// instruction from the start of the sequence.
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		Ok(Instructions::deserialize_at_most(reader, usize::MAX)?
+			.expect("no more than usize::MAX instructions fit in memory"))
+	}
+}
+
+impl Instructions {
+	/// Deserialize at most `max` instructions, `None` if the sequence is longer.
+	pub(crate) fn deserialize_at_most<R: io::Read>(
+		reader: &mut R,
+		max: usize,
+	) -> Result<Option<Self>, Error> {
 		let mut instructions = Vec::new();
 		let mut block_count = 1usize;
+		let mut reader = OpcodeTracker::new(reader);
 
 		loop {
-			let instruction = Instruction::deserialize(reader)?;
-			if instruction.is_terminal() {
+			if instructions.len() == max {
+				return Ok(None)
+			}
+			reader.start_instruction();
+			let instruction =
+				Instruction::deserialize(&mut reader).map_err(|err| reader.unsupported(err))?;
+			if instruction.closes_block() {
 				block_count -= 1;
 			} else if instruction.is_block() {
 				block_count =
@@ -54,12 +84,83 @@ impl Deserialize for Instructions {
 			}
 		}
 
-		Ok(Instructions(instructions))
+		Ok(Some(Instructions(instructions)))
+	}
+}
+
+/// Reader keeping track of the position and the first bytes of the current instruction,
+/// to report unknown opcodes precisely.
+struct OpcodeTracker<'a, R> {
+	inner: &'a mut R,
+	position: usize,
+	start: usize,
+	head: [u8; 6],
+}
+
+impl<'a, R: io::Read> OpcodeTracker<'a, R> {
+	fn new(inner: &'a mut R) -> Self {
+		OpcodeTracker { inner, position: 0, start: 0, head: [0; 6] }
+	}
+
+	fn start_instruction(&mut self) {
+		self.start = self.position;
+	}
+
+	/// Turn an unknown opcode error of the current instruction into `UnsupportedOpcode`.
+	fn unsupported(&mut self, err: Error) -> Error {
+		let unknown = match err {
+			Error::UnknownOpcode(_) => true,
+			#[cfg(feature = "simd")]
+			Error::UnknownSimdOpcode(_) => true,
+			_ => false,
+		};
+		if !unknown {
+			return err
+		}
+
+		let read = self.position - self.start;
+		let first = self.head[0];
+		let (prefix, opcode) = match first {
+			// Bulk memory, SIMD and atomics prefixes respectively.
+			0xfc..=0xfe => {
+				let sub_opcode = if read > 1 {
+					let len = read.min(self.head.len());
+					VarUint32::deserialize(&mut io::Cursor::new(&self.head[1..len]))
+				} else {
+					// Prefix of a disabled proposal, the sub-opcode was not read yet.
+					VarUint32::deserialize(self)
+				};
+				match sub_opcode {
+					Ok(sub_opcode) => (Some(first), sub_opcode.into()),
+					Err(_) => return err,
+				}
+			},
+			_ => (None, first.into()),
+		};
+		Error::UnsupportedOpcode(Box::new(UnsupportedOpcode {
+			prefix,
+			opcode,
+			offset: self.start,
+			function: None,
+		}))
+	}
+}
+
+impl<'a, R: io::Read> io::Read for OpcodeTracker<'a, R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<()> {
+		self.inner.read(buf)?;
+		for &byte in buf.iter() {
+			if let Some(slot) = self.head.get_mut(self.position - self.start) {
+				*slot = byte;
+			}
+			self.position += 1;
+		}
+		Ok(())
 	}
 }
 
 /// Initialization expression.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct InitExpr(Vec<Instruction>);
 
 impl InitExpr {
@@ -116,22 +217,22 @@ pub enum Instruction {
 	If(BlockType),
 	Else,
 	End,
-	Br(u32),
-	BrIf(u32),
+	Br(LabelIdx),
+	BrIf(LabelIdx),
 	BrTable(Box<BrTableData>),
 	Return,
 
-	Call(u32),
-	CallIndirect(u32, u8),
+	Call(FuncIdx),
+	CallIndirect(TypeIdx, u8),
 
 	Drop,
 	Select,
 
-	GetLocal(u32),
-	SetLocal(u32),
-	TeeLocal(u32),
-	GetGlobal(u32),
-	SetGlobal(u32),
+	GetLocal(LocalIdx),
+	SetLocal(LocalIdx),
+	TeeLocal(LocalIdx),
+	GetGlobal(GlobalIdx),
+	SetGlobal(GlobalIdx),
 
 	// All store/load instructions operate with 'memory immediates'
 	// which represented here as (flag, offset) tuple
@@ -159,12 +260,15 @@ pub enum Instruction {
 	I64Store16(u32, u32),
 	I64Store32(u32, u32),
 
+	// Memory index, which is a reserved zero byte unless the multi-memory proposal is enabled
 	CurrentMemory(u8),
 	GrowMemory(u8),
 
 	I32Const(i32),
 	I64Const(i64),
+	/// Bit pattern of the constant, kept verbatim (NaN payloads included).
 	F32Const(u32),
+	/// Bit pattern of the constant, kept verbatim (NaN payloads included).
 	F64Const(u64),
 
 	I32Eqz,
@@ -309,6 +413,9 @@ pub enum Instruction {
 
 	#[cfg(feature = "bulk")]
 	Bulk(BulkInstruction),
+
+	#[cfg(feature = "exceptions")]
+	Exceptions(ExceptionsInstruction),
 }
 
 #[allow(missing_docs)]
@@ -572,6 +679,23 @@ pub enum BulkInstruction {
 	TableCopy,
 }
 
+/// Instructions of the exception handling proposal.
+///
+/// Like other blocks, `Try` is followed by its body in the flat instruction list. The body
+/// is split by `Catch` and `CatchAll` and closed by `End`, or by `Delegate` in place of
+/// the `End`.
+#[allow(missing_docs)]
+#[cfg(feature = "exceptions")]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ExceptionsInstruction {
+	Try(BlockType),
+	Catch(TagIdx),
+	CatchAll,
+	Delegate(LabelIdx),
+	Throw(TagIdx),
+	Rethrow(LabelIdx),
+}
+
 #[cfg(any(feature = "simd", feature = "atomics"))]
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 #[allow(missing_docs)]
@@ -583,14 +707,19 @@ pub struct MemArg {
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 #[allow(missing_docs)]
 pub struct BrTableData {
-	pub table: Box<[u32]>,
-	pub default: u32,
+	pub table: Box<[LabelIdx]>,
+	pub default: LabelIdx,
 }
 
 impl Instruction {
 	/// Is this instruction starts the new block (which should end with terminal instruction).
 	pub fn is_block(&self) -> bool {
-		matches!(self, &Instruction::Block(_) | &Instruction::Loop(_) | &Instruction::If(_))
+		match self {
+			Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) => true,
+			#[cfg(feature = "exceptions")]
+			Instruction::Exceptions(ExceptionsInstruction::Try(_)) => true,
+			_ => false,
+		}
 	}
 
 	/// Is this instruction determines the termination of instruction sequence?
@@ -599,6 +728,114 @@ impl Instruction {
 	pub fn is_terminal(&self) -> bool {
 		matches!(self, &Instruction::End)
 	}
+
+	/// Whether this instruction closes the innermost block, i.e. `End` or `Delegate`.
+	pub(crate) fn closes_block(&self) -> bool {
+		match self {
+			Instruction::End => true,
+			#[cfg(feature = "exceptions")]
+			Instruction::Exceptions(ExceptionsInstruction::Delegate(_)) => true,
+			_ => false,
+		}
+	}
+
+	/// Opcode of this instruction as it appears in the binary encoding.
+	pub fn code(&self) -> OpcodeId {
+		// The opcode is written before any immediate, so it is known even if an immediate
+		// can't be encoded.
+		let (probe, _) = EncodingProbe::encode(self);
+		let head = &probe.head[..probe.len.min(probe.head.len())];
+		match head[0] {
+			// Bulk memory, SIMD and atomics prefixes respectively.
+			prefix @ 0xfc..=0xfe => {
+				let sub = VarUint32::deserialize(&mut io::Cursor::new(&head[1..]))
+					.expect("prefixed instructions are always followed by a sub-opcode");
+				OpcodeId::Prefixed(prefix, sub.into())
+			},
+			byte => OpcodeId::Single(byte),
+		}
+	}
+
+	/// Exact size of this instruction in the binary encoding, immediates included.
+	///
+	/// Fails if the instruction can't be encoded, like serializing it would.
+	pub fn serialized_size(&self) -> Result<usize, Error> {
+		let (probe, result) = EncodingProbe::encode(self);
+		result.map(|()| probe.len)
+	}
+
+	/// `i32.const` of the value.
+	pub fn i32_const(value: i32) -> Self {
+		Instruction::I32Const(value)
+	}
+
+	/// `i64.const` of the value.
+	pub fn i64_const(value: i64) -> Self {
+		Instruction::I64Const(value)
+	}
+
+	/// `f32.const` of the value, stored as its bits.
+	pub fn f32_const(value: f32) -> Self {
+		Instruction::F32Const(value.to_bits())
+	}
+
+	/// `f64.const` of the value, stored as its bits.
+	pub fn f64_const(value: f64) -> Self {
+		Instruction::F64Const(value.to_bits())
+	}
+
+	/// Value pushed by a constant instruction, `None` for any other instruction.
+	pub fn const_value(&self) -> Option<ConstValue> {
+		match *self {
+			Instruction::I32Const(value) => Some(ConstValue::I32(value)),
+			Instruction::I64Const(value) => Some(ConstValue::I64(value)),
+			Instruction::F32Const(bits) => Some(ConstValue::F32(bits)),
+			Instruction::F64Const(bits) => Some(ConstValue::F64(bits)),
+			_ => None,
+		}
+	}
+}
+
+/// Opcode of an instruction.
+///
+/// Post-MVP proposals put their instructions behind a prefix byte, followed by
+/// the actual sub-opcode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OpcodeId {
+	/// Single byte opcode.
+	Single(u8),
+	/// Prefix byte and the sub-opcode following it.
+	Prefixed(u8, u32),
+}
+
+/// Writer which only keeps the leading bytes and the total length of what is written.
+///
+/// Used to derive opcode and size information from the serializer itself, so that
+/// those can never diverge from the actual encoding.
+struct EncodingProbe {
+	head: [u8; 6],
+	len: usize,
+}
+
+impl EncodingProbe {
+	/// Probe of what was written before the encoding ended, and whether it succeeded.
+	fn encode(instruction: &Instruction) -> (Self, Result<(), Error>) {
+		let mut probe = EncodingProbe { head: [0; 6], len: 0 };
+		let result = instruction.encode(&mut probe);
+		(probe, result)
+	}
+}
+
+impl io::Write for EncodingProbe {
+	fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+		for &byte in buf {
+			if let Some(slot) = self.head.get_mut(self.len) {
+				*slot = byte;
+			}
+			self.len += 1;
+		}
+		Ok(())
+	}
 }
 
 #[allow(missing_docs)]
@@ -791,6 +1028,16 @@ pub mod opcodes {
 		pub const I64_EXTEND32_S: u8 = 0xc4;
 	}
 
+	#[cfg(feature = "exceptions")]
+	pub mod exceptions {
+		pub const TRY: u8 = 0x06;
+		pub const CATCH: u8 = 0x07;
+		pub const THROW: u8 = 0x08;
+		pub const RETHROW: u8 = 0x09;
+		pub const DELEGATE: u8 = 0x18;
+		pub const CATCH_ALL: u8 = 0x19;
+	}
+
 	#[cfg(feature = "atomics")]
 	pub mod atomics {
 		pub const ATOMIC_PREFIX: u8 = 0xfe;
@@ -1049,6 +1296,191 @@ pub mod opcodes {
 	}
 }
 
+/// Table of the MVP instructions made of a single opcode byte, without immediates, with their
+/// opcode and mnemonic.
+///
+/// Invokes `$callback!` with the given tokens followed by `;` and the rows of the table, each
+/// `Variant = OPCODE, "mnemonic";`. The decoder, the encoder and `Display` of [`Instruction`]
+/// are generated from it for these instructions, see `match_instruction!`. Instructions with
+/// immediates and those of the proposals are still handled by hand.
+macro_rules! plain_instructions {
+	($callback:ident! { $($args:tt)* }) => {
+		$callback! {
+			$($args)*;
+			Unreachable = UNREACHABLE, "unreachable";
+			Nop = NOP, "nop";
+			Else = ELSE, "else";
+			End = END, "end";
+			Return = RETURN, "return";
+			Drop = DROP, "drop";
+			Select = SELECT, "select";
+			I32Eqz = I32EQZ, "i32.eqz";
+			I32Eq = I32EQ, "i32.eq";
+			I32Ne = I32NE, "i32.ne";
+			I32LtS = I32LTS, "i32.lt_s";
+			I32LtU = I32LTU, "i32.lt_u";
+			I32GtS = I32GTS, "i32.gt_s";
+			I32GtU = I32GTU, "i32.gt_u";
+			I32LeS = I32LES, "i32.le_s";
+			I32LeU = I32LEU, "i32.le_u";
+			I32GeS = I32GES, "i32.ge_s";
+			I32GeU = I32GEU, "i32.ge_u";
+			I64Eqz = I64EQZ, "i64.eqz";
+			I64Eq = I64EQ, "i64.eq";
+			I64Ne = I64NE, "i64.ne";
+			I64LtS = I64LTS, "i64.lt_s";
+			I64LtU = I64LTU, "i64.lt_u";
+			I64GtS = I64GTS, "i64.gt_s";
+			I64GtU = I64GTU, "i64.gt_u";
+			I64LeS = I64LES, "i64.le_s";
+			I64LeU = I64LEU, "i64.le_u";
+			I64GeS = I64GES, "i64.ge_s";
+			I64GeU = I64GEU, "i64.ge_u";
+			F32Eq = F32EQ, "f32.eq";
+			F32Ne = F32NE, "f32.ne";
+			F32Lt = F32LT, "f32.lt";
+			F32Gt = F32GT, "f32.gt";
+			F32Le = F32LE, "f32.le";
+			F32Ge = F32GE, "f32.ge";
+			F64Eq = F64EQ, "f64.eq";
+			F64Ne = F64NE, "f64.ne";
+			F64Lt = F64LT, "f64.lt";
+			F64Gt = F64GT, "f64.gt";
+			F64Le = F64LE, "f64.le";
+			F64Ge = F64GE, "f64.ge";
+			I32Clz = I32CLZ, "i32.clz";
+			I32Ctz = I32CTZ, "i32.ctz";
+			I32Popcnt = I32POPCNT, "i32.popcnt";
+			I32Add = I32ADD, "i32.add";
+			I32Sub = I32SUB, "i32.sub";
+			I32Mul = I32MUL, "i32.mul";
+			I32DivS = I32DIVS, "i32.div_s";
+			I32DivU = I32DIVU, "i32.div_u";
+			I32RemS = I32REMS, "i32.rem_s";
+			I32RemU = I32REMU, "i32.rem_u";
+			I32And = I32AND, "i32.and";
+			I32Or = I32OR, "i32.or";
+			I32Xor = I32XOR, "i32.xor";
+			I32Shl = I32SHL, "i32.shl";
+			I32ShrS = I32SHRS, "i32.shr_s";
+			I32ShrU = I32SHRU, "i32.shr_u";
+			I32Rotl = I32ROTL, "i32.rotl";
+			I32Rotr = I32ROTR, "i32.rotr";
+			I64Clz = I64CLZ, "i64.clz";
+			I64Ctz = I64CTZ, "i64.ctz";
+			I64Popcnt = I64POPCNT, "i64.popcnt";
+			I64Add = I64ADD, "i64.add";
+			I64Sub = I64SUB, "i64.sub";
+			I64Mul = I64MUL, "i64.mul";
+			I64DivS = I64DIVS, "i64.div_s";
+			I64DivU = I64DIVU, "i64.div_u";
+			I64RemS = I64REMS, "i64.rem_s";
+			I64RemU = I64REMU, "i64.rem_u";
+			I64And = I64AND, "i64.and";
+			I64Or = I64OR, "i64.or";
+			I64Xor = I64XOR, "i64.xor";
+			I64Shl = I64SHL, "i64.shl";
+			I64ShrS = I64SHRS, "i64.shr_s";
+			I64ShrU = I64SHRU, "i64.shr_u";
+			I64Rotl = I64ROTL, "i64.rotl";
+			I64Rotr = I64ROTR, "i64.rotr";
+			F32Abs = F32ABS, "f32.abs";
+			F32Neg = F32NEG, "f32.neg";
+			F32Ceil = F32CEIL, "f32.ceil";
+			F32Floor = F32FLOOR, "f32.floor";
+			F32Trunc = F32TRUNC, "f32.trunc";
+			F32Nearest = F32NEAREST, "f32.nearest";
+			F32Sqrt = F32SQRT, "f32.sqrt";
+			F32Add = F32ADD, "f32.add";
+			F32Sub = F32SUB, "f32.sub";
+			F32Mul = F32MUL, "f32.mul";
+			F32Div = F32DIV, "f32.div";
+			F32Min = F32MIN, "f32.min";
+			F32Max = F32MAX, "f32.max";
+			F32Copysign = F32COPYSIGN, "f32.copysign";
+			F64Abs = F64ABS, "f64.abs";
+			F64Neg = F64NEG, "f64.neg";
+			F64Ceil = F64CEIL, "f64.ceil";
+			F64Floor = F64FLOOR, "f64.floor";
+			F64Trunc = F64TRUNC, "f64.trunc";
+			F64Nearest = F64NEAREST, "f64.nearest";
+			F64Sqrt = F64SQRT, "f64.sqrt";
+			F64Add = F64ADD, "f64.add";
+			F64Sub = F64SUB, "f64.sub";
+			F64Mul = F64MUL, "f64.mul";
+			F64Div = F64DIV, "f64.div";
+			F64Min = F64MIN, "f64.min";
+			F64Max = F64MAX, "f64.max";
+			F64Copysign = F64COPYSIGN, "f64.copysign";
+			I32WrapI64 = I32WRAPI64, "i32.wrap/i64";
+			I32TruncSF32 = I32TRUNCSF32, "i32.trunc_s/f32";
+			I32TruncUF32 = I32TRUNCUF32, "i32.trunc_u/f32";
+			I32TruncSF64 = I32TRUNCSF64, "i32.trunc_s/f64";
+			I32TruncUF64 = I32TRUNCUF64, "i32.trunc_u/f64";
+			I64ExtendSI32 = I64EXTENDSI32, "i64.extend_s/i32";
+			I64ExtendUI32 = I64EXTENDUI32, "i64.extend_u/i32";
+			I64TruncSF32 = I64TRUNCSF32, "i64.trunc_s/f32";
+			I64TruncUF32 = I64TRUNCUF32, "i64.trunc_u/f32";
+			I64TruncSF64 = I64TRUNCSF64, "i64.trunc_s/f64";
+			I64TruncUF64 = I64TRUNCUF64, "i64.trunc_u/f64";
+			F32ConvertSI32 = F32CONVERTSI32, "f32.convert_s/i32";
+			F32ConvertUI32 = F32CONVERTUI32, "f32.convert_u/i32";
+			F32ConvertSI64 = F32CONVERTSI64, "f32.convert_s/i64";
+			F32ConvertUI64 = F32CONVERTUI64, "f32.convert_u/i64";
+			F32DemoteF64 = F32DEMOTEF64, "f32.demote/f64";
+			F64ConvertSI32 = F64CONVERTSI32, "f64.convert_s/i32";
+			F64ConvertUI32 = F64CONVERTUI32, "f64.convert_u/i32";
+			F64ConvertSI64 = F64CONVERTSI64, "f64.convert_s/i64";
+			F64ConvertUI64 = F64CONVERTUI64, "f64.convert_u/i64";
+			F64PromoteF32 = F64PROMOTEF32, "f64.promote/f32";
+			I32ReinterpretF32 = I32REINTERPRETF32, "i32.reinterpret/f32";
+			I64ReinterpretF64 = I64REINTERPRETF64, "i64.reinterpret/f64";
+			F32ReinterpretI32 = F32REINTERPRETI32, "f32.reinterpret/i32";
+			F64ReinterpretI64 = F64REINTERPRETI64, "f64.reinterpret/i64";
+		}
+	};
+}
+
+/// Match an instruction against the given arms, and the instructions of `plain_instructions!`
+/// against `|opcode, mnemonic| expression`, so that the match stays exhaustive.
+macro_rules! match_instruction {
+	(
+		$instruction:expr;
+		|$opcode:tt, $mnemonic:tt| $plain:expr;
+		$($(#[$attr:meta])* $arm:pat => $body:expr,)*;
+		$($variant:ident = $code:ident, $name:literal;)*
+	) => {
+		match $instruction {
+			$($(#[$attr])* $arm => $body,)*
+			$(Instruction::$variant => {
+				let ($opcode, $mnemonic): (u8, &'static str) = (opcodes::$code, $name);
+				$plain
+			},)*
+		}
+	};
+}
+
+/// Lookup of the instructions of `plain_instructions!` by opcode.
+macro_rules! plain_opcodes {
+	(; $($variant:ident = $opcode:ident, $mnemonic:literal;)*) => {
+		impl Instruction {
+			/// Instruction without immediates with the given opcode.
+			fn from_plain_opcode(opcode: u8) -> Option<Instruction> {
+				match opcode {
+					$(opcodes::$opcode => Some(Instruction::$variant),)*
+					_ => None,
+				}
+			}
+		}
+
+		#[cfg(test)]
+		const PLAIN_INSTRUCTIONS: &[(Instruction, u8, &str)] =
+			&[$((Instruction::$variant, opcodes::$opcode, $mnemonic)),*];
+	};
+}
+
+plain_instructions!(plain_opcodes! {});
+
 impl Deserialize for Instruction {
 	type Error = Error;
 
@@ -1059,20 +1491,19 @@ impl Deserialize for Instruction {
 		use self::opcodes::sign_ext::*;
 
 		let val: u8 = Uint8::deserialize(reader)?.into();
+		if let Some(instruction) = Instruction::from_plain_opcode(val) {
+			return Ok(instruction)
+		}
 
 		Ok(match val {
-			UNREACHABLE => Unreachable,
-			NOP => Nop,
 			BLOCK => Block(BlockType::deserialize(reader)?),
 			LOOP => Loop(BlockType::deserialize(reader)?),
 			IF => If(BlockType::deserialize(reader)?),
-			ELSE => Else,
-			END => End,
 
 			BR => Br(VarUint32::deserialize(reader)?.into()),
 			BRIF => BrIf(VarUint32::deserialize(reader)?.into()),
 			BRTABLE => {
-				let t1: Vec<u32> = CountedList::<VarUint32>::deserialize(reader)?
+				let t1: Vec<LabelIdx> = CountedList::<VarUint32>::deserialize(reader)?
 					.into_inner()
 					.into_iter()
 					.map(Into::into)
@@ -1083,19 +1514,17 @@ impl Deserialize for Instruction {
 					default: VarUint32::deserialize(reader)?.into(),
 				}))
 			},
-			RETURN => Return,
 			CALL => Call(VarUint32::deserialize(reader)?.into()),
 			CALLINDIRECT => {
-				let signature: u32 = VarUint32::deserialize(reader)?.into();
-				let table_ref: u8 = Uint8::deserialize(reader)?.into();
-				if table_ref != 0 {
-					return Err(Error::InvalidTableReference(table_ref))
-				}
+				let signature: TypeIdx = VarUint32::deserialize(reader)?.into();
+				let table_ref = deserialize_index_byte(
+					reader,
+					cfg!(feature = "reference_types"),
+					Error::InvalidTableReference,
+				)?;
 
 				CallIndirect(signature, table_ref)
 			},
-			DROP => Drop,
-			SELECT => Select,
 
 			GETLOCAL => GetLocal(VarUint32::deserialize(reader)?.into()),
 			SETLOCAL => SetLocal(VarUint32::deserialize(reader)?.into()),
@@ -1218,155 +1647,21 @@ impl Deserialize for Instruction {
 				VarUint32::deserialize(reader)?.into(),
 			),
 
-			CURRENTMEMORY => {
-				let mem_ref: u8 = Uint8::deserialize(reader)?.into();
-				if mem_ref != 0 {
-					return Err(Error::InvalidMemoryReference(mem_ref))
-				}
-				CurrentMemory(mem_ref)
-			},
-			GROWMEMORY => {
-				let mem_ref: u8 = Uint8::deserialize(reader)?.into();
-				if mem_ref != 0 {
-					return Err(Error::InvalidMemoryReference(mem_ref))
-				}
-				GrowMemory(mem_ref)
-			},
+			CURRENTMEMORY => CurrentMemory(deserialize_index_byte(
+				reader,
+				cfg!(feature = "multi_memory"),
+				Error::InvalidMemoryReference,
+			)?),
+			GROWMEMORY => GrowMemory(deserialize_index_byte(
+				reader,
+				cfg!(feature = "multi_memory"),
+				Error::InvalidMemoryReference,
+			)?),
 
 			I32CONST => I32Const(VarInt32::deserialize(reader)?.into()),
 			I64CONST => I64Const(VarInt64::deserialize(reader)?.into()),
 			F32CONST => F32Const(Uint32::deserialize(reader)?.into()),
 			F64CONST => F64Const(Uint64::deserialize(reader)?.into()),
-			I32EQZ => I32Eqz,
-			I32EQ => I32Eq,
-			I32NE => I32Ne,
-			I32LTS => I32LtS,
-			I32LTU => I32LtU,
-			I32GTS => I32GtS,
-			I32GTU => I32GtU,
-			I32LES => I32LeS,
-			I32LEU => I32LeU,
-			I32GES => I32GeS,
-			I32GEU => I32GeU,
-
-			I64EQZ => I64Eqz,
-			I64EQ => I64Eq,
-			I64NE => I64Ne,
-			I64LTS => I64LtS,
-			I64LTU => I64LtU,
-			I64GTS => I64GtS,
-			I64GTU => I64GtU,
-			I64LES => I64LeS,
-			I64LEU => I64LeU,
-			I64GES => I64GeS,
-			I64GEU => I64GeU,
-
-			F32EQ => F32Eq,
-			F32NE => F32Ne,
-			F32LT => F32Lt,
-			F32GT => F32Gt,
-			F32LE => F32Le,
-			F32GE => F32Ge,
-
-			F64EQ => F64Eq,
-			F64NE => F64Ne,
-			F64LT => F64Lt,
-			F64GT => F64Gt,
-			F64LE => F64Le,
-			F64GE => F64Ge,
-
-			I32CLZ => I32Clz,
-			I32CTZ => I32Ctz,
-			I32POPCNT => I32Popcnt,
-			I32ADD => I32Add,
-			I32SUB => I32Sub,
-			I32MUL => I32Mul,
-			I32DIVS => I32DivS,
-			I32DIVU => I32DivU,
-			I32REMS => I32RemS,
-			I32REMU => I32RemU,
-			I32AND => I32And,
-			I32OR => I32Or,
-			I32XOR => I32Xor,
-			I32SHL => I32Shl,
-			I32SHRS => I32ShrS,
-			I32SHRU => I32ShrU,
-			I32ROTL => I32Rotl,
-			I32ROTR => I32Rotr,
-
-			I64CLZ => I64Clz,
-			I64CTZ => I64Ctz,
-			I64POPCNT => I64Popcnt,
-			I64ADD => I64Add,
-			I64SUB => I64Sub,
-			I64MUL => I64Mul,
-			I64DIVS => I64DivS,
-			I64DIVU => I64DivU,
-			I64REMS => I64RemS,
-			I64REMU => I64RemU,
-			I64AND => I64And,
-			I64OR => I64Or,
-			I64XOR => I64Xor,
-			I64SHL => I64Shl,
-			I64SHRS => I64ShrS,
-			I64SHRU => I64ShrU,
-			I64ROTL => I64Rotl,
-			I64ROTR => I64Rotr,
-			F32ABS => F32Abs,
-			F32NEG => F32Neg,
-			F32CEIL => F32Ceil,
-			F32FLOOR => F32Floor,
-			F32TRUNC => F32Trunc,
-			F32NEAREST => F32Nearest,
-			F32SQRT => F32Sqrt,
-			F32ADD => F32Add,
-			F32SUB => F32Sub,
-			F32MUL => F32Mul,
-			F32DIV => F32Div,
-			F32MIN => F32Min,
-			F32MAX => F32Max,
-			F32COPYSIGN => F32Copysign,
-			F64ABS => F64Abs,
-			F64NEG => F64Neg,
-			F64CEIL => F64Ceil,
-			F64FLOOR => F64Floor,
-			F64TRUNC => F64Trunc,
-			F64NEAREST => F64Nearest,
-			F64SQRT => F64Sqrt,
-			F64ADD => F64Add,
-			F64SUB => F64Sub,
-			F64MUL => F64Mul,
-			F64DIV => F64Div,
-			F64MIN => F64Min,
-			F64MAX => F64Max,
-			F64COPYSIGN => F64Copysign,
-
-			I32WRAPI64 => I32WrapI64,
-			I32TRUNCSF32 => I32TruncSF32,
-			I32TRUNCUF32 => I32TruncUF32,
-			I32TRUNCSF64 => I32TruncSF64,
-			I32TRUNCUF64 => I32TruncUF64,
-			I64EXTENDSI32 => I64ExtendSI32,
-			I64EXTENDUI32 => I64ExtendUI32,
-			I64TRUNCSF32 => I64TruncSF32,
-			I64TRUNCUF32 => I64TruncUF32,
-			I64TRUNCSF64 => I64TruncSF64,
-			I64TRUNCUF64 => I64TruncUF64,
-			F32CONVERTSI32 => F32ConvertSI32,
-			F32CONVERTUI32 => F32ConvertUI32,
-			F32CONVERTSI64 => F32ConvertSI64,
-			F32CONVERTUI64 => F32ConvertUI64,
-			F32DEMOTEF64 => F32DemoteF64,
-			F64CONVERTSI32 => F64ConvertSI32,
-			F64CONVERTUI32 => F64ConvertUI32,
-			F64CONVERTSI64 => F64ConvertSI64,
-			F64CONVERTUI64 => F64ConvertUI64,
-			F64PROMOTEF32 => F64PromoteF32,
-
-			I32REINTERPRETF32 => I32ReinterpretF32,
-			I64REINTERPRETF64 => I64ReinterpretF64,
-			F32REINTERPRETI32 => F32ReinterpretI32,
-			F64REINTERPRETI64 => F64ReinterpretI64,
 
 			#[cfg(feature = "sign_ext")]
 			I32_EXTEND8_S | I32_EXTEND16_S | I64_EXTEND8_S | I64_EXTEND16_S | I64_EXTEND32_S => match val {
@@ -1387,11 +1682,60 @@ impl Deserialize for Instruction {
 			#[cfg(feature = "bulk")]
 			bulk::BULK_PREFIX => return deserialize_bulk(reader),
 
+			#[cfg(feature = "exceptions")]
+			exceptions::TRY => Exceptions(ExceptionsInstruction::Try(BlockType::deserialize(reader)?)),
+			#[cfg(feature = "exceptions")]
+			exceptions::CATCH =>
+				Exceptions(ExceptionsInstruction::Catch(VarUint32::deserialize(reader)?.into())),
+			#[cfg(feature = "exceptions")]
+			exceptions::CATCH_ALL => Exceptions(ExceptionsInstruction::CatchAll),
+			#[cfg(feature = "exceptions")]
+			exceptions::DELEGATE =>
+				Exceptions(ExceptionsInstruction::Delegate(VarUint32::deserialize(reader)?.into())),
+			#[cfg(feature = "exceptions")]
+			exceptions::THROW =>
+				Exceptions(ExceptionsInstruction::Throw(VarUint32::deserialize(reader)?.into())),
+			#[cfg(feature = "exceptions")]
+			exceptions::RETHROW =>
+				Exceptions(ExceptionsInstruction::Rethrow(VarUint32::deserialize(reader)?.into())),
+
 			_ => return Err(Error::UnknownOpcode(val)),
 		})
 	}
 }
 
+/// Memory or table index of `memory.size`, `memory.grow` and `call_indirect`.
+///
+/// The MVP requires a zero byte. Once the proposal introducing the index is `enabled`, it is a
+/// LEB128 number, only indices fitting in its first byte are supported.
+fn deserialize_index_byte<R: io::Read>(
+	reader: &mut R,
+	enabled: bool,
+	invalid: fn(u8) -> Error,
+) -> Result<u8, Error> {
+	let index: u8 = Uint8::deserialize(reader)?.into();
+	let valid = if enabled { index < 0x80 } else { index == 0 };
+	if !valid {
+		return Err(invalid(index))
+	}
+	Ok(index)
+}
+
+fn serialize_index_byte<W: io::Write>(
+	index: u8,
+	enabled: bool,
+	invalid: fn(u8) -> Error,
+	writer: &mut W,
+) -> Result<(), Error> {
+	if !enabled {
+		return Uint8::from(0).serialize(writer)
+	}
+	if index >= 0x80 {
+		return Err(invalid(index))
+	}
+	Uint8::from(index).serialize(writer)
+}
+
 #[cfg(feature = "atomics")]
 fn deserialize_atomic<R: io::Read>(reader: &mut R) -> Result<Instruction, Error> {
 	use self::{opcodes::atomics::*, AtomicsInstruction::*};
@@ -1719,7 +2063,7 @@ macro_rules! op {
 macro_rules! atomic {
 	($writer: expr, $byte: expr, $mem:expr) => {{
 		$writer.write(&[ATOMIC_PREFIX, $byte])?;
-		MemArg::serialize($mem, $writer)?;
+		$mem.encode($writer)?;
 	}};
 }
 
@@ -1747,11 +2091,18 @@ impl Serialize for Instruction {
 	type Error = Error;
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		self.encode(writer)
+	}
+}
+
+impl Instruction {
+	/// Write the binary encoding of the instruction, without taking it by value.
+	fn encode<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
 		use self::{opcodes::*, Instruction::*};
 
-		match self {
-			Unreachable => op!(writer, UNREACHABLE),
-			Nop => op!(writer, NOP),
+		plain_instructions!(match_instruction! {
+			*self;
+			|opcode, _| op!(writer, opcode);
 			Block(block_type) => op!(writer, BLOCK, {
 				block_type.serialize(writer)?;
 			}),
@@ -1761,8 +2112,6 @@ impl Serialize for Instruction {
 			If(block_type) => op!(writer, IF, {
 				block_type.serialize(writer)?;
 			}),
-			Else => op!(writer, ELSE),
-			End => op!(writer, END),
 			Br(idx) => op!(writer, BR, {
 				VarUint32::from(idx).serialize(writer)?;
 			}),
@@ -1777,16 +2126,18 @@ impl Serialize for Instruction {
 				list_writer.serialize(writer)?;
 				VarUint32::from(table.default).serialize(writer)?;
 			}),
-			Return => op!(writer, RETURN),
 			Call(index) => op!(writer, CALL, {
 				VarUint32::from(index).serialize(writer)?;
 			}),
-			CallIndirect(index, reserved) => op!(writer, CALLINDIRECT, {
+			CallIndirect(index, table_ref) => op!(writer, CALLINDIRECT, {
 				VarUint32::from(index).serialize(writer)?;
-				Uint8::from(reserved).serialize(writer)?;
+				serialize_index_byte(
+					table_ref,
+					cfg!(feature = "reference_types"),
+					Error::InvalidTableReference,
+					writer,
+				)?;
 			}),
-			Drop => op!(writer, DROP),
-			Select => op!(writer, SELECT),
 			GetLocal(index) => op!(writer, GETLOCAL, {
 				VarUint32::from(index).serialize(writer)?;
 			}),
@@ -1894,11 +2245,21 @@ impl Serialize for Instruction {
 				VarUint32::from(flags).serialize(writer)?;
 				VarUint32::from(offset).serialize(writer)?;
 			}),
-			CurrentMemory(flag) => op!(writer, CURRENTMEMORY, {
-				Uint8::from(flag).serialize(writer)?;
+			CurrentMemory(mem_ref) => op!(writer, CURRENTMEMORY, {
+				serialize_index_byte(
+					mem_ref,
+					cfg!(feature = "multi_memory"),
+					Error::InvalidMemoryReference,
+					writer,
+				)?;
 			}),
-			GrowMemory(flag) => op!(writer, GROWMEMORY, {
-				Uint8::from(flag).serialize(writer)?;
+			GrowMemory(mem_ref) => op!(writer, GROWMEMORY, {
+				serialize_index_byte(
+					mem_ref,
+					cfg!(feature = "multi_memory"),
+					Error::InvalidMemoryReference,
+					writer,
+				)?;
 			}),
 			I32Const(def) => op!(writer, I32CONST, {
 				VarInt32::from(def).serialize(writer)?;
@@ -1912,136 +2273,6 @@ impl Serialize for Instruction {
 			F64Const(def) => op!(writer, F64CONST, {
 				Uint64::from(def).serialize(writer)?;
 			}),
-			I32Eqz => op!(writer, I32EQZ),
-			I32Eq => op!(writer, I32EQ),
-			I32Ne => op!(writer, I32NE),
-			I32LtS => op!(writer, I32LTS),
-			I32LtU => op!(writer, I32LTU),
-			I32GtS => op!(writer, I32GTS),
-			I32GtU => op!(writer, I32GTU),
-			I32LeS => op!(writer, I32LES),
-			I32LeU => op!(writer, I32LEU),
-			I32GeS => op!(writer, I32GES),
-			I32GeU => op!(writer, I32GEU),
-
-			I64Eqz => op!(writer, I64EQZ),
-			I64Eq => op!(writer, I64EQ),
-			I64Ne => op!(writer, I64NE),
-			I64LtS => op!(writer, I64LTS),
-			I64LtU => op!(writer, I64LTU),
-			I64GtS => op!(writer, I64GTS),
-			I64GtU => op!(writer, I64GTU),
-			I64LeS => op!(writer, I64LES),
-			I64LeU => op!(writer, I64LEU),
-			I64GeS => op!(writer, I64GES),
-			I64GeU => op!(writer, I64GEU),
-
-			F32Eq => op!(writer, F32EQ),
-			F32Ne => op!(writer, F32NE),
-			F32Lt => op!(writer, F32LT),
-			F32Gt => op!(writer, F32GT),
-			F32Le => op!(writer, F32LE),
-			F32Ge => op!(writer, F32GE),
-
-			F64Eq => op!(writer, F64EQ),
-			F64Ne => op!(writer, F64NE),
-			F64Lt => op!(writer, F64LT),
-			F64Gt => op!(writer, F64GT),
-			F64Le => op!(writer, F64LE),
-			F64Ge => op!(writer, F64GE),
-
-			I32Clz => op!(writer, I32CLZ),
-			I32Ctz => op!(writer, I32CTZ),
-			I32Popcnt => op!(writer, I32POPCNT),
-			I32Add => op!(writer, I32ADD),
-			I32Sub => op!(writer, I32SUB),
-			I32Mul => op!(writer, I32MUL),
-			I32DivS => op!(writer, I32DIVS),
-			I32DivU => op!(writer, I32DIVU),
-			I32RemS => op!(writer, I32REMS),
-			I32RemU => op!(writer, I32REMU),
-			I32And => op!(writer, I32AND),
-			I32Or => op!(writer, I32OR),
-			I32Xor => op!(writer, I32XOR),
-			I32Shl => op!(writer, I32SHL),
-			I32ShrS => op!(writer, I32SHRS),
-			I32ShrU => op!(writer, I32SHRU),
-			I32Rotl => op!(writer, I32ROTL),
-			I32Rotr => op!(writer, I32ROTR),
-
-			I64Clz => op!(writer, I64CLZ),
-			I64Ctz => op!(writer, I64CTZ),
-			I64Popcnt => op!(writer, I64POPCNT),
-			I64Add => op!(writer, I64ADD),
-			I64Sub => op!(writer, I64SUB),
-			I64Mul => op!(writer, I64MUL),
-			I64DivS => op!(writer, I64DIVS),
-			I64DivU => op!(writer, I64DIVU),
-			I64RemS => op!(writer, I64REMS),
-			I64RemU => op!(writer, I64REMU),
-			I64And => op!(writer, I64AND),
-			I64Or => op!(writer, I64OR),
-			I64Xor => op!(writer, I64XOR),
-			I64Shl => op!(writer, I64SHL),
-			I64ShrS => op!(writer, I64SHRS),
-			I64ShrU => op!(writer, I64SHRU),
-			I64Rotl => op!(writer, I64ROTL),
-			I64Rotr => op!(writer, I64ROTR),
-			F32Abs => op!(writer, F32ABS),
-			F32Neg => op!(writer, F32NEG),
-			F32Ceil => op!(writer, F32CEIL),
-			F32Floor => op!(writer, F32FLOOR),
-			F32Trunc => op!(writer, F32TRUNC),
-			F32Nearest => op!(writer, F32NEAREST),
-			F32Sqrt => op!(writer, F32SQRT),
-			F32Add => op!(writer, F32ADD),
-			F32Sub => op!(writer, F32SUB),
-			F32Mul => op!(writer, F32MUL),
-			F32Div => op!(writer, F32DIV),
-			F32Min => op!(writer, F32MIN),
-			F32Max => op!(writer, F32MAX),
-			F32Copysign => op!(writer, F32COPYSIGN),
-			F64Abs => op!(writer, F64ABS),
-			F64Neg => op!(writer, F64NEG),
-			F64Ceil => op!(writer, F64CEIL),
-			F64Floor => op!(writer, F64FLOOR),
-			F64Trunc => op!(writer, F64TRUNC),
-			F64Nearest => op!(writer, F64NEAREST),
-			F64Sqrt => op!(writer, F64SQRT),
-			F64Add => op!(writer, F64ADD),
-			F64Sub => op!(writer, F64SUB),
-			F64Mul => op!(writer, F64MUL),
-			F64Div => op!(writer, F64DIV),
-			F64Min => op!(writer, F64MIN),
-			F64Max => op!(writer, F64MAX),
-			F64Copysign => op!(writer, F64COPYSIGN),
-
-			I32WrapI64 => op!(writer, I32WRAPI64),
-			I32TruncSF32 => op!(writer, I32TRUNCSF32),
-			I32TruncUF32 => op!(writer, I32TRUNCUF32),
-			I32TruncSF64 => op!(writer, I32TRUNCSF64),
-			I32TruncUF64 => op!(writer, I32TRUNCUF64),
-			I64ExtendSI32 => op!(writer, I64EXTENDSI32),
-			I64ExtendUI32 => op!(writer, I64EXTENDUI32),
-			I64TruncSF32 => op!(writer, I64TRUNCSF32),
-			I64TruncUF32 => op!(writer, I64TRUNCUF32),
-			I64TruncSF64 => op!(writer, I64TRUNCSF64),
-			I64TruncUF64 => op!(writer, I64TRUNCUF64),
-			F32ConvertSI32 => op!(writer, F32CONVERTSI32),
-			F32ConvertUI32 => op!(writer, F32CONVERTUI32),
-			F32ConvertSI64 => op!(writer, F32CONVERTSI64),
-			F32ConvertUI64 => op!(writer, F32CONVERTUI64),
-			F32DemoteF64 => op!(writer, F32DEMOTEF64),
-			F64ConvertSI32 => op!(writer, F64CONVERTSI32),
-			F64ConvertUI32 => op!(writer, F64CONVERTUI32),
-			F64ConvertSI64 => op!(writer, F64CONVERTSI64),
-			F64ConvertUI64 => op!(writer, F64CONVERTUI64),
-			F64PromoteF32 => op!(writer, F64PROMOTEF32),
-
-			I32ReinterpretF32 => op!(writer, I32REINTERPRETF32),
-			I64ReinterpretF64 => op!(writer, I64REINTERPRETF64),
-			F32ReinterpretI32 => op!(writer, F32REINTERPRETI32),
-			F64ReinterpretI64 => op!(writer, F64REINTERPRETI64),
 
 			#[cfg(feature = "sign_ext")]
 			SignExt(ref a) => match *a {
@@ -2053,13 +2284,53 @@ impl Serialize for Instruction {
 			},
 
 			#[cfg(feature = "atomics")]
-			Atomics(a) => return a.serialize(writer),
+			Atomics(ref a) => return a.encode(writer),
 
 			#[cfg(feature = "simd")]
-			Simd(a) => return a.serialize(writer),
+			Simd(ref a) => return a.encode(writer),
 
 			#[cfg(feature = "bulk")]
-			Bulk(a) => return a.serialize(writer),
+			Bulk(ref a) => return a.encode(writer),
+
+			#[cfg(feature = "exceptions")]
+			Exceptions(ref a) => return a.encode(writer),
+		});
+
+		Ok(())
+	}
+}
+
+#[cfg(feature = "exceptions")]
+impl Serialize for ExceptionsInstruction {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		self.encode(writer)
+	}
+}
+
+#[cfg(feature = "exceptions")]
+impl ExceptionsInstruction {
+	fn encode<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		use self::{opcodes::exceptions::*, ExceptionsInstruction::*};
+
+		match *self {
+			Try(block_type) => op!(writer, TRY, {
+				block_type.serialize(writer)?;
+			}),
+			Catch(tag) => op!(writer, CATCH, {
+				VarUint32::from(tag).serialize(writer)?;
+			}),
+			CatchAll => op!(writer, CATCH_ALL),
+			Delegate(label) => op!(writer, DELEGATE, {
+				VarUint32::from(label).serialize(writer)?;
+			}),
+			Throw(tag) => op!(writer, THROW, {
+				VarUint32::from(tag).serialize(writer)?;
+			}),
+			Rethrow(label) => op!(writer, RETHROW, {
+				VarUint32::from(label).serialize(writer)?;
+			}),
 		}
 
 		Ok(())
@@ -2071,6 +2342,13 @@ impl Serialize for AtomicsInstruction {
 	type Error = Error;
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		self.encode(writer)
+	}
+}
+
+#[cfg(feature = "atomics")]
+impl AtomicsInstruction {
+	fn encode<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
 		use self::{opcodes::atomics::*, AtomicsInstruction::*};
 
 		match self {
@@ -2159,12 +2437,19 @@ impl Serialize for SimdInstruction {
 	type Error = Error;
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		self.encode(writer)
+	}
+}
+
+#[cfg(feature = "simd")]
+impl SimdInstruction {
+	fn encode<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
 		use self::{opcodes::simd::*, SimdInstruction::*};
 
-		match self {
+		match *self {
 			V128Const(ref c) => simd!(writer, V128_CONST, writer.write(&c[..])?),
-			V128Load(m) => simd!(writer, V128_LOAD, MemArg::serialize(m, writer)?),
-			V128Store(m) => simd!(writer, V128_STORE, MemArg::serialize(m, writer)?),
+			V128Load(ref m) => simd!(writer, V128_LOAD, m.encode(writer)?),
+			V128Store(ref m) => simd!(writer, V128_STORE, m.encode(writer)?),
 			I8x16Splat => simd!(writer, I8X16_SPLAT, {}),
 			I16x8Splat => simd!(writer, I16X8_SPLAT, {}),
 			I32x4Splat => simd!(writer, I32X4_SPLAT, {}),
@@ -2324,9 +2609,16 @@ impl Serialize for BulkInstruction {
 	type Error = Error;
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		self.encode(writer)
+	}
+}
+
+#[cfg(feature = "bulk")]
+impl BulkInstruction {
+	fn encode<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
 		use self::{opcodes::bulk::*, BulkInstruction::*};
 
-		match self {
+		match *self {
 			MemoryInit(seg) => bulk!(writer, MEMORY_INIT, {
 				Uint8::from(0).serialize(writer)?;
 				VarUint32::from(seg).serialize(writer)?;
@@ -2351,6 +2643,13 @@ impl Serialize for MemArg {
 	type Error = Error;
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		self.encode(writer)
+	}
+}
+
+#[cfg(any(feature = "simd", feature = "atomics"))]
+impl MemArg {
+	fn encode<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
 		Uint8::from(self.align).serialize(writer)?;
 		VarUint32::from(self.offset).serialize(writer)?;
 		Ok(())
@@ -2373,9 +2672,9 @@ impl fmt::Display for Instruction {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		use self::Instruction::*;
 
-		match *self {
-			Unreachable => fmt_op!(f, "unreachable"),
-			Nop => fmt_op!(f, "nop"),
+		plain_instructions!(match_instruction! {
+			*self;
+			|_, mnemonic| f.write_str(mnemonic);
 			Block(BlockType::NoResult) => fmt_op!(f, "block"),
 			Block(BlockType::Value(value_type)) => fmt_op!(f, "block", value_type),
 			#[cfg(feature = "multi_value")]
@@ -2388,16 +2687,11 @@ impl fmt::Display for Instruction {
 			If(BlockType::Value(value_type)) => fmt_op!(f, "if", value_type),
 			#[cfg(feature = "multi_value")]
 			If(BlockType::TypeIndex(idx)) => write!(f, "if type_idx={}", idx),
-			Else => fmt_op!(f, "else"),
-			End => fmt_op!(f, "end"),
 			Br(idx) => fmt_op!(f, "br", idx),
 			BrIf(idx) => fmt_op!(f, "br_if", idx),
 			BrTable(ref table) => fmt_op!(f, "br_table", table.default),
-			Return => fmt_op!(f, "return"),
 			Call(index) => fmt_op!(f, "call", index),
 			CallIndirect(index, _) => fmt_op!(f, "call_indirect", index),
-			Drop => fmt_op!(f, "drop"),
-			Select => fmt_op!(f, "select"),
 			GetLocal(index) => fmt_op!(f, "get_local", index),
 			SetLocal(index) => fmt_op!(f, "set_local", index),
 			TeeLocal(index) => fmt_op!(f, "tee_local", index),
@@ -2481,143 +2775,6 @@ impl fmt::Display for Instruction {
 			F32Const(def) => fmt_op!(f, "f32.const", def),
 			F64Const(def) => fmt_op!(f, "f64.const", def),
 
-			I32Eq => write!(f, "i32.eq"),
-			I32Eqz => write!(f, "i32.eqz"),
-			I32Ne => write!(f, "i32.ne"),
-			I32LtS => write!(f, "i32.lt_s"),
-			I32LtU => write!(f, "i32.lt_u"),
-			I32GtS => write!(f, "i32.gt_s"),
-			I32GtU => write!(f, "i32.gt_u"),
-			I32LeS => write!(f, "i32.le_s"),
-			I32LeU => write!(f, "i32.le_u"),
-			I32GeS => write!(f, "i32.ge_s"),
-			I32GeU => write!(f, "i32.ge_u"),
-
-			I64Eq => write!(f, "i64.eq"),
-			I64Eqz => write!(f, "i64.eqz"),
-			I64Ne => write!(f, "i64.ne"),
-			I64LtS => write!(f, "i64.lt_s"),
-			I64LtU => write!(f, "i64.lt_u"),
-			I64GtS => write!(f, "i64.gt_s"),
-			I64GtU => write!(f, "i64.gt_u"),
-			I64LeS => write!(f, "i64.le_s"),
-			I64LeU => write!(f, "i64.le_u"),
-			I64GeS => write!(f, "i64.ge_s"),
-			I64GeU => write!(f, "i64.ge_u"),
-
-			F32Eq => write!(f, "f32.eq"),
-			F32Ne => write!(f, "f32.ne"),
-			F32Lt => write!(f, "f32.lt"),
-			F32Gt => write!(f, "f32.gt"),
-			F32Le => write!(f, "f32.le"),
-			F32Ge => write!(f, "f32.ge"),
-
-			F64Eq => write!(f, "f64.eq"),
-			F64Ne => write!(f, "f64.ne"),
-			F64Lt => write!(f, "f64.lt"),
-			F64Gt => write!(f, "f64.gt"),
-			F64Le => write!(f, "f64.le"),
-			F64Ge => write!(f, "f64.ge"),
-
-			I32Clz => write!(f, "i32.clz"),
-			I32Ctz => write!(f, "i32.ctz"),
-			I32Popcnt => write!(f, "i32.popcnt"),
-			I32Add => write!(f, "i32.add"),
-			I32Sub => write!(f, "i32.sub"),
-			I32Mul => write!(f, "i32.mul"),
-			I32DivS => write!(f, "i32.div_s"),
-			I32DivU => write!(f, "i32.div_u"),
-			I32RemS => write!(f, "i32.rem_s"),
-			I32RemU => write!(f, "i32.rem_u"),
-			I32And => write!(f, "i32.and"),
-			I32Or => write!(f, "i32.or"),
-			I32Xor => write!(f, "i32.xor"),
-			I32Shl => write!(f, "i32.shl"),
-			I32ShrS => write!(f, "i32.shr_s"),
-			I32ShrU => write!(f, "i32.shr_u"),
-			I32Rotl => write!(f, "i32.rotl"),
-			I32Rotr => write!(f, "i32.rotr"),
-
-			I64Clz => write!(f, "i64.clz"),
-			I64Ctz => write!(f, "i64.ctz"),
-			I64Popcnt => write!(f, "i64.popcnt"),
-			I64Add => write!(f, "i64.add"),
-			I64Sub => write!(f, "i64.sub"),
-			I64Mul => write!(f, "i64.mul"),
-			I64DivS => write!(f, "i64.div_s"),
-			I64DivU => write!(f, "i64.div_u"),
-			I64RemS => write!(f, "i64.rem_s"),
-			I64RemU => write!(f, "i64.rem_u"),
-			I64And => write!(f, "i64.and"),
-			I64Or => write!(f, "i64.or"),
-			I64Xor => write!(f, "i64.xor"),
-			I64Shl => write!(f, "i64.shl"),
-			I64ShrS => write!(f, "i64.shr_s"),
-			I64ShrU => write!(f, "i64.shr_u"),
-			I64Rotl => write!(f, "i64.rotl"),
-			I64Rotr => write!(f, "i64.rotr"),
-
-			F32Abs => write!(f, "f32.abs"),
-			F32Neg => write!(f, "f32.neg"),
-			F32Ceil => write!(f, "f32.ceil"),
-			F32Floor => write!(f, "f32.floor"),
-			F32Trunc => write!(f, "f32.trunc"),
-			F32Nearest => write!(f, "f32.nearest"),
-			F32Sqrt => write!(f, "f32.sqrt"),
-			F32Add => write!(f, "f32.add"),
-			F32Sub => write!(f, "f32.sub"),
-			F32Mul => write!(f, "f32.mul"),
-			F32Div => write!(f, "f32.div"),
-			F32Min => write!(f, "f32.min"),
-			F32Max => write!(f, "f32.max"),
-			F32Copysign => write!(f, "f32.copysign"),
-
-			F64Abs => write!(f, "f64.abs"),
-			F64Neg => write!(f, "f64.neg"),
-			F64Ceil => write!(f, "f64.ceil"),
-			F64Floor => write!(f, "f64.floor"),
-			F64Trunc => write!(f, "f64.trunc"),
-			F64Nearest => write!(f, "f64.nearest"),
-			F64Sqrt => write!(f, "f64.sqrt"),
-			F64Add => write!(f, "f64.add"),
-			F64Sub => write!(f, "f64.sub"),
-			F64Mul => write!(f, "f64.mul"),
-			F64Div => write!(f, "f64.div"),
-			F64Min => write!(f, "f64.min"),
-			F64Max => write!(f, "f64.max"),
-			F64Copysign => write!(f, "f64.copysign"),
-
-			I32WrapI64 => write!(f, "i32.wrap/i64"),
-			I32TruncSF32 => write!(f, "i32.trunc_s/f32"),
-			I32TruncUF32 => write!(f, "i32.trunc_u/f32"),
-			I32TruncSF64 => write!(f, "i32.trunc_s/f64"),
-			I32TruncUF64 => write!(f, "i32.trunc_u/f64"),
-
-			I64ExtendSI32 => write!(f, "i64.extend_s/i32"),
-			I64ExtendUI32 => write!(f, "i64.extend_u/i32"),
-
-			I64TruncSF32 => write!(f, "i64.trunc_s/f32"),
-			I64TruncUF32 => write!(f, "i64.trunc_u/f32"),
-			I64TruncSF64 => write!(f, "i64.trunc_s/f64"),
-			I64TruncUF64 => write!(f, "i64.trunc_u/f64"),
-
-			F32ConvertSI32 => write!(f, "f32.convert_s/i32"),
-			F32ConvertUI32 => write!(f, "f32.convert_u/i32"),
-			F32ConvertSI64 => write!(f, "f32.convert_s/i64"),
-			F32ConvertUI64 => write!(f, "f32.convert_u/i64"),
-			F32DemoteF64 => write!(f, "f32.demote/f64"),
-
-			F64ConvertSI32 => write!(f, "f64.convert_s/i32"),
-			F64ConvertUI32 => write!(f, "f64.convert_u/i32"),
-			F64ConvertSI64 => write!(f, "f64.convert_s/i64"),
-			F64ConvertUI64 => write!(f, "f64.convert_u/i64"),
-			F64PromoteF32 => write!(f, "f64.promote/f32"),
-
-			I32ReinterpretF32 => write!(f, "i32.reinterpret/f32"),
-			I64ReinterpretF64 => write!(f, "i64.reinterpret/f64"),
-			F32ReinterpretI32 => write!(f, "f32.reinterpret/i32"),
-			F64ReinterpretI64 => write!(f, "f64.reinterpret/i64"),
-
 			#[cfg(feature = "sign_ext")]
 			SignExt(ref i) => match i {
 				SignExtInstruction::I32Extend8S => write!(f, "i32.extend8_s"),
@@ -2635,6 +2792,28 @@ impl fmt::Display for Instruction {
 
 			#[cfg(feature = "bulk")]
 			Bulk(ref i) => i.fmt(f),
+
+			#[cfg(feature = "exceptions")]
+			Exceptions(ref i) => i.fmt(f),
+		})
+	}
+}
+
+#[cfg(feature = "exceptions")]
+impl fmt::Display for ExceptionsInstruction {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		use self::ExceptionsInstruction::*;
+
+		match *self {
+			Try(BlockType::NoResult) => fmt_op!(f, "try"),
+			Try(BlockType::Value(value_type)) => fmt_op!(f, "try", value_type),
+			#[cfg(feature = "multi_value")]
+			Try(BlockType::TypeIndex(idx)) => write!(f, "try type_idx={}", idx),
+			Catch(tag) => fmt_op!(f, "catch", tag),
+			CatchAll => fmt_op!(f, "catch_all"),
+			Delegate(label) => fmt_op!(f, "delegate", label),
+			Throw(tag) => fmt_op!(f, "throw", tag),
+			Rethrow(label) => fmt_op!(f, "rethrow", label),
 		}
 	}
 }
@@ -2953,7 +3132,7 @@ fn ifelse() {
 
 #[test]
 fn display() {
-	let instruction = Instruction::GetLocal(0);
+	let instruction = Instruction::GetLocal(0.into());
 	assert_eq!("get_local 0", format!("{}", instruction));
 
 	let instruction = Instruction::F64Store(0, 24);
@@ -2963,9 +3142,54 @@ fn display() {
 	assert_eq!("i64.store", format!("{}", instruction));
 }
 
+#[cfg(feature = "exceptions")]
+#[test]
+fn try_catch() {
+	use self::{ExceptionsInstruction::*, Instruction::*};
+	use super::types::BlockType;
+
+	let bytes = [
+		0x06, 0x40, // try
+		0x41, 0x01, // i32.const 1
+		0x08, 0x00, // throw 0
+		0x07, 0x00, // catch 0
+		0x1a, // drop
+		0x19, // catch_all
+		0x06, 0x40, // try
+		0x09, 0x01, // rethrow 1
+		0x18, 0x00, // delegate 0
+		0x0b, // end
+		0x0b, // end
+	];
+	let instructions =
+		super::deserialize_buffer::<Instructions>(&bytes).expect("Should be deserialized");
+	assert_eq!(
+		instructions.elements(),
+		&[
+			Exceptions(Try(BlockType::NoResult)),
+			I32Const(1),
+			Exceptions(Throw(0.into())),
+			Exceptions(Catch(0.into())),
+			Drop,
+			Exceptions(CatchAll),
+			Exceptions(Try(BlockType::NoResult)),
+			Exceptions(Rethrow(1.into())),
+			Exceptions(Delegate(0.into())),
+			End,
+			End,
+		]
+	);
+	assert_eq!(instructions.elements()[3].to_string(), "catch 0");
+	assert_eq!(instructions.serialized_size().unwrap(), bytes.len());
+	assert_eq!(super::serialize(instructions).expect("Should be serialized"), bytes);
+}
+
 #[test]
 fn size_off() {
-	assert!(::std::mem::size_of::<Instruction>() <= 24);
+	// Payloads larger than a word, like `br_table` targets, are boxed. Only the nested
+	// instruction enum of the SIMD proposal needs a word of its own for its discriminant.
+	let max_size = if cfg!(feature = "simd") { 24 } else { 16 };
+	assert!(::std::mem::size_of::<Instruction>() <= max_size);
 }
 
 #[test]
@@ -2974,6 +3198,268 @@ fn instructions_hashset() {
 	use super::types::{BlockType::Value, ValueType};
 
 	let set: std::collections::HashSet<Instruction> =
-		vec![Call(1), Block(Value(ValueType::I32)), Drop].into_iter().collect();
+		vec![Call(1.into()), Block(Value(ValueType::I32)), Drop].into_iter().collect();
 	assert!(set.contains(&Drop));
 }
+
+#[test]
+fn opcode_ids() {
+	use super::types::BlockType;
+
+	assert_eq!(Instruction::Nop.code(), OpcodeId::Single(opcodes::NOP));
+	assert_eq!(Instruction::Block(BlockType::NoResult).code(), OpcodeId::Single(opcodes::BLOCK));
+	assert_eq!(Instruction::I64Const(-1).code(), OpcodeId::Single(opcodes::I64CONST));
+
+	#[cfg(feature = "bulk")]
+	assert_eq!(
+		Instruction::Bulk(BulkInstruction::MemoryCopy).code(),
+		OpcodeId::Prefixed(opcodes::bulk::BULK_PREFIX, opcodes::bulk::MEMORY_COPY as u32)
+	);
+
+	#[cfg(feature = "simd")]
+	assert_eq!(
+		Instruction::Simd(SimdInstruction::F64x2Sqrt).code(),
+		OpcodeId::Prefixed(opcodes::simd::SIMD_PREFIX, opcodes::simd::F64X2_SQRT)
+	);
+}
+
+#[test]
+fn serialized_sizes() {
+	use super::types::{BlockType, ValueType};
+
+	let br_table = |table: &[u32], default: u32| {
+		let table = table.iter().map(|&label| label.into()).collect();
+		Instruction::BrTable(Box::new(BrTableData { table, default: default.into() }))
+	};
+	let sizes = [
+		(Instruction::Nop, 1),
+		(Instruction::Block(BlockType::Value(ValueType::I64)), 2),
+		(Instruction::Br(127.into()), 2),
+		(Instruction::Br(128.into()), 3),
+		(Instruction::GetLocal(16_384.into()), 4),
+		(Instruction::Call(u32::MAX.into()), 6),
+		(Instruction::CallIndirect(300.into(), 0), 4),
+		(Instruction::I32Load(2, 0x0fff_ffff), 6),
+		(Instruction::I64Store(3, u32::MAX), 7),
+		(Instruction::I32Const(-64), 2),
+		(Instruction::I32Const(64), 3),
+		(Instruction::I32Const(i32::MIN), 6),
+		(Instruction::I64Const(i64::MIN), 11),
+		(Instruction::F32Const(0), 5),
+		(Instruction::F64Const(0), 9),
+		(Instruction::GrowMemory(0), 2),
+		// Opcode, label count, labels, default.
+		(br_table(&[], 0), 3),
+		(br_table(&[0, 200, 20_000], 128), 1 + 1 + (1 + 2 + 3) + 2),
+		(br_table(&[1; 128], 0), 1 + 2 + 128 + 1),
+	];
+	for (instruction, size) in sizes.iter() {
+		assert_eq!(instruction.serialized_size().unwrap(), *size, "{:?}", instruction);
+	}
+	let list: Vec<_> = sizes.iter().map(|(instruction, _)| instruction.clone()).collect();
+	let total: usize = sizes.iter().map(|(_, size)| size).sum();
+	assert_eq!(Instructions::new(list).serialized_size().unwrap(), total);
+
+	// Prefix byte, then the sub-opcode in LEB128 and the immediates.
+	#[cfg(feature = "bulk")]
+	{
+		assert_eq!(Instruction::Bulk(BulkInstruction::MemoryCopy).serialized_size().unwrap(), 3);
+		assert_eq!(
+			Instruction::Bulk(BulkInstruction::TableInit(200)).serialized_size().unwrap(),
+			5
+		);
+	}
+	#[cfg(feature = "simd")]
+	{
+		let constant = Instruction::Simd(SimdInstruction::V128Const(Box::new([0; 16])));
+		assert_eq!(constant.serialized_size().unwrap(), 18);
+		assert_eq!(Instruction::Simd(SimdInstruction::F64x2Sqrt).serialized_size().unwrap(), 3);
+	}
+	#[cfg(feature = "atomics")]
+	{
+		let load = AtomicsInstruction::I32AtomicLoad(MemArg { align: 2, offset: 300 });
+		assert_eq!(Instruction::Atomics(load).serialized_size().unwrap(), 5);
+	}
+	#[cfg(feature = "sign_ext")]
+	assert_eq!(Instruction::SignExt(SignExtInstruction::I32Extend8S).serialized_size().unwrap(), 1);
+	#[cfg(feature = "exceptions")]
+	assert_eq!(
+		Instruction::Exceptions(ExceptionsInstruction::Catch(200.into()))
+			.serialized_size()
+			.unwrap(),
+		3
+	);
+}
+
+#[test]
+fn unencodable_instructions() {
+	let call = Instruction::CallIndirect(0.into(), 0x80);
+	let grow = Instruction::GrowMemory(0x80);
+	assert_eq!(call.code(), OpcodeId::Single(opcodes::CALLINDIRECT));
+	assert_eq!(grow.code(), OpcodeId::Single(opcodes::GROWMEMORY));
+
+	// Table and memory indices are only written with the proposals that introduce them.
+	if cfg!(feature = "reference_types") {
+		assert!(matches!(call.serialized_size(), Err(Error::InvalidTableReference(0x80))));
+	} else {
+		assert_eq!(call.serialized_size().unwrap(), 3);
+	}
+	if cfg!(feature = "multi_memory") {
+		assert!(matches!(grow.serialized_size(), Err(Error::InvalidMemoryReference(0x80))));
+		let list = Instructions::new(vec![Instruction::Nop, grow, Instruction::End]);
+		assert!(list.serialized_size().is_err());
+	} else {
+		assert_eq!(grow.serialized_size().unwrap(), 2);
+	}
+}
+
+#[test]
+fn float_constants_keep_bit_patterns() {
+	use super::{deserialize_buffer, serialize};
+
+	let mut state = 0x9e37_79b9_7f4a_7c15u64;
+	let mut next = move || {
+		state ^= state << 13;
+		state ^= state >> 7;
+		state ^= state << 17;
+		state
+	};
+
+	let mut f32_bits = vec![0x7f80_0001, 0x7fc0_0000, 0xffbf_ffff, 0x8000_0000];
+	let mut f64_bits = vec![0x7ff0_0000_0000_0001, 0x7ff8_0000_0000_0000, 0xfff7_ffff_ffff_ffff];
+	for _ in 0..256 {
+		let bits = next();
+		f32_bits.push(bits as u32);
+		// Same bits with the exponent set, landing in the NaN (or infinity) space.
+		f32_bits.push(bits as u32 | 0x7f80_0000);
+		f64_bits.push(bits);
+		f64_bits.push(bits | 0x7ff0_0000_0000_0000);
+	}
+
+	for bits in f32_bits {
+		let mut bytes = vec![opcodes::F32CONST];
+		bytes.extend(bits.to_le_bytes());
+		bytes.push(opcodes::END);
+
+		let instructions: Instructions = deserialize_buffer(&bytes).expect("f32.const to decode");
+		assert_eq!(instructions.elements()[0], Instruction::F32Const(bits));
+		assert_eq!(serialize(instructions).expect("f32.const to encode"), bytes);
+	}
+	for bits in f64_bits {
+		let mut bytes = vec![opcodes::F64CONST];
+		bytes.extend(bits.to_le_bytes());
+		bytes.push(opcodes::END);
+
+		let instructions: Instructions = deserialize_buffer(&bytes).expect("f64.const to decode");
+		assert_eq!(instructions.elements()[0], Instruction::F64Const(bits));
+		assert_eq!(serialize(instructions).expect("f64.const to encode"), bytes);
+	}
+}
+
+#[test]
+fn multi_byte_immediates_are_little_endian() {
+	use super::{deserialize_buffer, serialize};
+
+	// Golden bytes, so that the encoding is checked on any host.
+	let cases = [
+		(Instruction::f32_const(1.5), vec![0x43, 0x00, 0x00, 0xc0, 0x3f]),
+		(Instruction::f32_const(-0.0), vec![0x43, 0x00, 0x00, 0x00, 0x80]),
+		(Instruction::F32Const(0x7fc0_0001), vec![0x43, 0x01, 0x00, 0xc0, 0x7f]),
+		(Instruction::f64_const(-2.5), vec![0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0xc0]),
+		(
+			Instruction::F64Const(0x7ff8_0000_0000_0001),
+			vec![0x44, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8, 0x7f],
+		),
+		(Instruction::I64Const(63), vec![0x42, 0x3f]),
+		(Instruction::I64Const(64), vec![0x42, 0xc0, 0x00]),
+		(Instruction::I64Const(-65), vec![0x42, 0xbf, 0x7f]),
+		(
+			Instruction::I64Const(i64::MAX),
+			vec![0x42, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00],
+		),
+		(
+			Instruction::I64Const(i64::MIN),
+			vec![0x42, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f],
+		),
+	];
+	for (instruction, bytes) in cases {
+		assert_eq!(serialize(instruction.clone()).expect("instruction to encode"), bytes);
+		let decoded: Instruction = deserialize_buffer(&bytes).expect("bytes to decode");
+		assert_eq!(decoded, instruction);
+	}
+}
+
+#[test]
+fn memory_index() {
+	use super::{deserialize_buffer, serialize};
+
+	let bytes = [opcodes::GROWMEMORY, 0x01, opcodes::CURRENTMEMORY, 0x00, opcodes::END];
+	let instructions = deserialize_buffer::<Instructions>(&bytes);
+	if cfg!(feature = "multi_memory") {
+		let instructions = instructions.expect("memory index to be accepted");
+		assert_eq!(instructions.elements()[0], Instruction::GrowMemory(1));
+		assert_eq!(serialize(instructions).expect("memory index to encode"), bytes);
+	} else {
+		assert!(matches!(instructions, Err(Error::InvalidMemoryReference(1))));
+	}
+}
+
+#[test]
+fn table_index() {
+	use super::{deserialize_buffer, serialize};
+
+	let instructions =
+		Instructions::new(vec![Instruction::CallIndirect(0.into(), 1), Instruction::End]);
+	let bytes = serialize(instructions).expect("call_indirect to encode");
+	if cfg!(feature = "reference_types") {
+		assert_eq!(bytes, [opcodes::CALLINDIRECT, 0x00, 0x01, opcodes::END]);
+		let instructions =
+			deserialize_buffer::<Instructions>(&bytes).expect("table index to be accepted");
+		assert_eq!(instructions.elements()[0], Instruction::CallIndirect(0.into(), 1));
+	} else {
+		// The reserved byte is always encoded as zero in the MVP.
+		assert_eq!(bytes, [opcodes::CALLINDIRECT, 0x00, 0x00, opcodes::END]);
+		assert!(matches!(
+			deserialize_buffer::<Instructions>(&[opcodes::CALLINDIRECT, 0x00, 0x01, opcodes::END]),
+			Err(Error::InvalidTableReference(1))
+		));
+	}
+}
+
+#[test]
+fn plain_instructions_table() {
+	use std::collections::BTreeSet;
+
+	let mut opcodes = BTreeSet::new();
+	let mut mnemonics = BTreeSet::new();
+	for (instruction, opcode, mnemonic) in PLAIN_INSTRUCTIONS {
+		assert!(opcodes.insert(opcode), "opcode {:#x} listed twice", opcode);
+		assert!(mnemonics.insert(mnemonic), "{} listed twice", mnemonic);
+
+		let bytes = super::serialize(instruction.clone()).expect("instruction to serialize");
+		assert_eq!(bytes, [*opcode]);
+		assert_eq!(super::deserialize_buffer::<Instruction>(&bytes).unwrap(), *instruction);
+		assert_eq!(instruction.to_string(), *mnemonic);
+		assert_eq!(instruction.code(), OpcodeId::Single(*opcode));
+	}
+
+	// Every instruction decoded from a single byte, so without immediates, is listed exactly
+	// once. That the encoder and `Display` handle every variant exactly once is checked by the
+	// compiler, as their matches are exhaustive and without wildcards.
+	for byte in 0..=u8::MAX {
+		let listed = |instruction: &Instruction| {
+			PLAIN_INSTRUCTIONS.iter().filter(|(listed, _, _)| listed == instruction).count()
+		};
+		match super::deserialize_buffer::<Instruction>(&[byte]) {
+			// Instructions of the proposals aren't part of the table.
+			#[cfg(feature = "sign_ext")]
+			Ok(Instruction::SignExt(_)) => assert!(!opcodes.contains(&byte)),
+			#[cfg(feature = "exceptions")]
+			Ok(Instruction::Exceptions(_)) => assert!(!opcodes.contains(&byte)),
+			Ok(instruction) =>
+				assert_eq!(listed(&instruction), 1, "{} not listed once", instruction),
+			Err(_) => assert!(!opcodes.contains(&byte), "opcode {:#x} doesn't decode", byte),
+		}
+		assert_eq!(Instruction::from_plain_opcode(byte).is_some(), opcodes.contains(&byte));
+	}
+}
@@ -1,3 +1,66 @@
+//! Primitive types of the binary format.
+//!
+//! These are the building blocks of all (de)serialization in this crate and can be
+//! reused for custom formats, for example the payload of a custom section. All of them
+//! fail with [`Error::UnexpectedEof`](super::Error) (wrapped from [`io::Error`]) when the
+//! input ends early; errors specific to a type are documented on the type.
+//!
+//! # Examples
+//!
+//! ```
+//! use parity_wasm::elements::{
+//!     primitives::{CountedList, CountedListWriter, CountedWriter, VarUint32},
+//!     CustomSection, Deserialize, Error, Serialize,
+//! };
+//! use parity_wasm::io;
+//!
+//! /// Named offset, stored as a name followed by a LEB128 offset.
+//! #[derive(Debug, PartialEq)]
+//! struct Symbol {
+//!     name: String,
+//!     offset: u32,
+//! }
+//!
+//! impl Serialize for Symbol {
+//!     type Error = Error;
+//!
+//!     fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Error> {
+//!         self.name.serialize(writer)?;
+//!         VarUint32::from(self.offset).serialize(writer)
+//!     }
+//! }
+//!
+//! impl Deserialize for Symbol {
+//!     type Error = Error;
+//!
+//!     fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Error> {
+//!         let name = String::deserialize(reader)?;
+//!         let offset = VarUint32::deserialize(reader)?.into();
+//!         Ok(Symbol { name, offset })
+//!     }
+//! }
+//!
+//! let symbols = vec![
+//!     Symbol { name: "main".into(), offset: 16 },
+//!     Symbol { name: "helper".into(), offset: 300 },
+//! ];
+//!
+//! // Payload: the symbols as a counted list, followed by a length-prefixed blob.
+//! let mut payload = Vec::new();
+//! CountedListWriter(symbols.len(), symbols).serialize(&mut payload)?;
+//! let mut blob = CountedWriter::new(&mut payload);
+//! io::Write::write(&mut blob, b"extra")?;
+//! blob.done()?;
+//! let section = CustomSection::new("symbols".into(), payload);
+//!
+//! let mut reader = io::Cursor::new(section.payload());
+//! let symbols = CountedList::<Symbol>::deserialize(&mut reader)?.into_inner();
+//! assert_eq!(symbols[1], Symbol { name: "helper".into(), offset: 300 });
+//! let blob: String = Deserialize::deserialize(&mut reader)?;
+//! assert_eq!(blob, "extra");
+//! # Ok::<(), Error>(())
+//! ```
+
 use super::{Deserialize, Error, Serialize};
 use crate::{elements, io};
 use alloc::{string::String, vec::Vec};
@@ -10,7 +73,11 @@ const PRIMITIVES_BUFFER_LENGTH: usize = 1024;
 
 /// Unsigned variable-length integer, limited to 32 bits,
 /// represented by at most 5 bytes that may contain padding 0x80 bytes.
-#[derive(Debug, Copy, Clone, PartialEq)]
+///
+/// Deserialization fails with [`Error::InvalidVarUint32`] if the encoding is longer
+/// than 5 bytes, and with [`Error::InvalidVarInt32`] if its last byte doesn't fit into
+/// 32 bits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct VarUint32(u32);
 
 impl From<VarUint32> for usize {
@@ -33,7 +100,7 @@ impl From<u32> for VarUint32 {
 
 impl From<usize> for VarUint32 {
 	fn from(i: usize) -> VarUint32 {
-		assert!(i <= u32::max_value() as usize);
+		assert!(i <= u32::MAX as usize);
 		VarUint32(i as u32)
 	}
 }
@@ -65,6 +132,32 @@ impl Deserialize for VarUint32 {
 	}
 }
 
+impl VarUint32 {
+	/// Serialize in at least `width` bytes, padding the minimal encoding with continuation
+	/// bytes.
+	pub(crate) fn serialize_padded<W: io::Write>(
+		self,
+		writer: &mut W,
+		width: usize,
+	) -> io::Result<()> {
+		let mut v = self.0;
+		let mut written = 0;
+		loop {
+			let mut byte = (v & 0b0111_1111) as u8;
+			v >>= 7;
+			written += 1;
+			let more = v > 0 || written < width;
+			if more {
+				byte |= 0b1000_0000;
+			}
+			writer.write(&[byte])?;
+			if !more {
+				return Ok(())
+			}
+		}
+	}
+}
+
 impl Serialize for VarUint32 {
 	type Error = Error;
 
@@ -89,7 +182,11 @@ impl Serialize for VarUint32 {
 
 /// Unsigned variable-length integer, limited to 64 bits,
 /// represented by at most 9 bytes that may contain padding 0x80 bytes.
-#[derive(Debug, Copy, Clone, PartialEq)]
+///
+/// Deserialization fails with [`Error::InvalidVarUint64`] if the encoding is longer
+/// than 10 bytes, and with [`Error::InvalidVarInt64`] if its last byte doesn't fit into
+/// 64 bits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct VarUint64(u64);
 
 impl From<VarUint64> for u64 {
@@ -154,7 +251,9 @@ impl From<u64> for VarUint64 {
 }
 
 /// 7-bit unsigned integer, encoded in LEB128 (always 1 byte length).
-#[derive(Debug, Copy, Clone, PartialEq)]
+///
+/// The byte is taken as is when deserializing, the continuation bit is not checked.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct VarUint7(u8);
 
 impl From<VarUint7> for u8 {
@@ -190,7 +289,9 @@ impl Serialize for VarUint7 {
 }
 
 /// 7-bit signed integer, encoded in LEB128 (always 1 byte length)
-#[derive(Debug, Copy, Clone, PartialEq)]
+///
+/// Deserialization fails with [`Error::InvalidVarInt7`] if the continuation bit is set.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct VarInt7(i8);
 
 impl From<VarInt7> for i8 {
@@ -243,7 +344,7 @@ impl Serialize for VarInt7 {
 
 /// 8-bit unsigned integer, NOT encoded in LEB128;
 /// it's just a single byte.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Uint8(u8);
 
 impl From<Uint8> for u8 {
@@ -278,7 +379,10 @@ impl Serialize for Uint8 {
 }
 
 /// 32-bit signed integer, encoded in LEB128 (can be 1-5 bytes length).
-#[derive(Debug, Copy, Clone, PartialEq)]
+///
+/// Deserialization fails with [`Error::InvalidVarInt32`] if the encoding is longer
+/// than 5 bytes or doesn't fit into 32 bits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct VarInt32(i32);
 
 impl From<VarInt32> for i32 {
@@ -353,7 +457,10 @@ impl Serialize for VarInt32 {
 }
 
 /// 64-bit signed integer, encoded in LEB128 (can be 1-9 bytes length).
-#[derive(Debug, Copy, Clone, PartialEq)]
+///
+/// Deserialization fails with [`Error::InvalidVarInt64`] if the encoding is longer
+/// than 10 bytes or doesn't fit into 64 bits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct VarInt64(i64);
 
 impl From<VarInt64> for i64 {
@@ -429,7 +536,7 @@ impl Serialize for VarInt64 {
 }
 
 /// 32-bit unsigned integer, encoded in little endian.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Uint32(u32);
 
 impl Deserialize for Uint32 {
@@ -465,7 +572,7 @@ impl From<u32> for Uint32 {
 }
 
 /// 64-bit unsigned integer, encoded in little endian.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Uint64(u64);
 
 impl Deserialize for Uint64 {
@@ -501,7 +608,9 @@ impl From<Uint64> for u64 {
 }
 
 /// VarUint1, 1-bit value (0/1).
-#[derive(Debug, Copy, Clone, PartialEq)]
+///
+/// Deserialization fails with [`Error::InvalidVarUint1`] for any other byte.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct VarUint1(bool);
 
 impl From<VarUint1> for bool {
@@ -565,6 +674,8 @@ impl Serialize for String {
 
 /// List for reading sequence of elements typed `T`, given
 /// they are preceded by length (serialized as VarUint32).
+///
+/// Deserialization stops at the first element failing to deserialize, returning its error.
 #[derive(Debug, Clone)]
 pub struct CountedList<T: Deserialize>(Vec<T>);
 
@@ -593,6 +704,9 @@ where
 
 /// Helper struct to write payload which is preceded by
 /// it's own length in bytes.
+///
+/// The payload is buffered until [`CountedWriter::done`], which writes the length
+/// (as VarUint32) followed by the payload. Nothing is written if `done` is not called.
 #[derive(Debug)]
 pub struct CountedWriter<'a, W: 'a + io::Write> {
 	writer: &'a mut W,
@@ -616,6 +730,15 @@ impl<'a, W: 'a + io::Write> CountedWriter<'a, W> {
 		writer.write(&data[..])?;
 		Ok(())
 	}
+
+	/// Like [`CountedWriter::done`], with the length padded to `width` bytes.
+	pub(crate) fn done_padded(self, width: usize) -> io::Result<()> {
+		let writer = self.writer;
+		let data = self.data;
+		VarUint32::from(data.len()).serialize_padded(writer, width)?;
+		writer.write(&data[..])?;
+		Ok(())
+	}
 }
 
 impl<'a, W: 'a + io::Write> io::Write for CountedWriter<'a, W> {
@@ -656,9 +779,9 @@ mod tests {
 
 	use super::{
 		super::{deserialize_buffer, Serialize},
-		CountedList, VarInt32, VarInt64, VarInt7, VarUint32, VarUint64,
+		CountedList, CountedWriter, VarInt32, VarInt64, VarInt7, VarUint32, VarUint64,
 	};
-	use crate::elements::Error;
+	use crate::{elements::Error, io::Write};
 
 	fn varuint32_ser_test(val: u32, expected: Vec<u8>) {
 		let mut buf = Vec::new();
@@ -914,4 +1037,52 @@ mod tests {
 		let v3: i8 = (*vars.get(1).unwrap()).into();
 		assert_eq!(-0x03i8, v3);
 	}
+
+	#[test]
+	fn varuint_overflow_in_last_byte() {
+		assert!(matches!(
+			deserialize_buffer::<VarUint32>(&[0xff, 0xff, 0xff, 0xff, 0x1f]),
+			Err(Error::InvalidVarInt32)
+		));
+		assert!(matches!(
+			deserialize_buffer::<VarUint64>(&[
+				0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x03
+			]),
+			Err(Error::InvalidVarInt64)
+		));
+	}
+
+	#[test]
+	fn counted_writer_empty() {
+		let mut buf = Vec::new();
+		CountedWriter::new(&mut buf).done().expect("to be written");
+		assert_eq!(buf, vec![0x00]);
+	}
+
+	#[test]
+	fn counted_writer_multibyte_length() {
+		let mut buf = vec![0xaa];
+		let mut counted = CountedWriter::new(&mut buf);
+		counted.write(&[0x01; 150]).expect("to be written");
+		counted.write(&[0x02; 50]).expect("to be written");
+		counted.done().expect("to be written");
+
+		assert_eq!(&buf[..3], &[0xaa, 0xc8, 0x01]);
+		assert_eq!(buf.len(), 203);
+		assert_eq!(buf[3 + 149], 0x01);
+		assert_eq!(buf[3 + 150], 0x02);
+	}
+
+	#[test]
+	fn counted_writer_nested() {
+		let mut buf = Vec::new();
+		let mut outer = CountedWriter::new(&mut buf);
+		outer.write(&[0x01]).expect("to be written");
+		let mut inner = CountedWriter::new(&mut outer);
+		inner.write(&[0x02, 0x03]).expect("to be written");
+		inner.done().expect("to be written");
+		outer.done().expect("to be written");
+
+		assert_eq!(buf, vec![0x04, 0x01, 0x02, 0x02, 0x03]);
+	}
 }
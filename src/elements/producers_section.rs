@@ -0,0 +1,233 @@
+use crate::io;
+use alloc::{string::String, vec::Vec};
+
+use super::{CountedList, CountedListWriter, Deserialize, Error, Serialize};
+
+/// Field listing the source languages.
+pub const PRODUCERS_FIELD_LANGUAGE: &str = "language";
+/// Field listing the tools which processed the module.
+pub const PRODUCERS_FIELD_PROCESSED_BY: &str = "processed-by";
+/// Field listing the SDKs used to build the module.
+pub const PRODUCERS_FIELD_SDK: &str = "sdk";
+
+/// Tool identification information (the `producers` custom section).
+///
+/// See the [tool conventions](https://github.com/WebAssembly/tool-conventions/blob/main/ProducersSection.md).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ProducersSection {
+	fields: Vec<ProducersField>,
+}
+
+impl ProducersSection {
+	/// Creates a new producers section.
+	///
+	/// Fields with the same name are merged.
+	pub fn new(fields: Vec<ProducersField>) -> Self {
+		let mut section = ProducersSection::default();
+		for field in fields {
+			for value in field.values {
+				section.add(field.name.clone(), value.name, value.version);
+			}
+		}
+		section
+	}
+
+	/// List of fields.
+	pub fn fields(&self) -> &[ProducersField] {
+		&self.fields
+	}
+
+	/// Values of the field with the given name, if present.
+	pub fn field(&self, name: &str) -> Option<&[ProducerValue]> {
+		self.fields
+			.iter()
+			.find(|field| field.name == name)
+			.map(|field| &field.values[..])
+	}
+
+	/// Source languages of the module.
+	pub fn language(&self) -> Option<&[ProducerValue]> {
+		self.field(PRODUCERS_FIELD_LANGUAGE)
+	}
+
+	/// Tools which processed the module.
+	pub fn processed_by(&self) -> Option<&[ProducerValue]> {
+		self.field(PRODUCERS_FIELD_PROCESSED_BY)
+	}
+
+	/// SDKs used to build the module.
+	pub fn sdk(&self) -> Option<&[ProducerValue]> {
+		self.field(PRODUCERS_FIELD_SDK)
+	}
+
+	/// Add a producer to the given field, creating the field if needed.
+	///
+	/// Value names are unique within a field, so adding an already listed
+	/// producer only updates its version.
+	pub fn add(
+		&mut self,
+		field: impl Into<String>,
+		name: impl Into<String>,
+		version: impl Into<String>,
+	) {
+		let field = field.into();
+		let value = ProducerValue { name: name.into(), version: version.into() };
+
+		let values = match self.fields.iter().position(|f| f.name == field) {
+			Some(idx) => &mut self.fields[idx].values,
+			None => {
+				self.fields.push(ProducersField { name: field, values: Vec::new() });
+				&mut self.fields.last_mut().expect("field was just pushed; qed").values
+			},
+		};
+
+		match values.iter_mut().find(|v| v.name == value.name) {
+			Some(existing) => existing.version = value.version,
+			None => values.push(value),
+		}
+	}
+}
+
+impl Deserialize for ProducersSection {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		let fields = CountedList::<ProducersField>::deserialize(rdr)?.into_inner();
+		Ok(ProducersSection::new(fields))
+	}
+}
+
+impl Serialize for ProducersSection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		CountedListWriter(self.fields.len(), self.fields.into_iter()).serialize(wtr)
+	}
+}
+
+/// Field of the producers section.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ProducersField {
+	/// Name of the field.
+	pub name: String,
+	/// Values listed in the field.
+	pub values: Vec<ProducerValue>,
+}
+
+impl Deserialize for ProducersField {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(ProducersField {
+			name: String::deserialize(rdr)?,
+			values: CountedList::<ProducerValue>::deserialize(rdr)?.into_inner(),
+		})
+	}
+}
+
+impl Serialize for ProducersField {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		self.name.serialize(wtr)?;
+		CountedListWriter(self.values.len(), self.values.into_iter()).serialize(wtr)
+	}
+}
+
+/// Producer listed in a field of the producers section.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ProducerValue {
+	/// Name of the language, tool or SDK.
+	pub name: String,
+	/// Version, possibly empty.
+	pub version: String,
+}
+
+impl Deserialize for ProducerValue {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		Ok(ProducerValue { name: String::deserialize(rdr)?, version: String::deserialize(rdr)? })
+	}
+}
+
+impl Serialize for ProducerValue {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		self.name.serialize(wtr)?;
+		self.version.serialize(wtr)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{deserialize_buffer, deserialize_file, serialize, Module},
+		ProducerValue, ProducersField, ProducersSection,
+	};
+
+	fn value(name: &str, version: &str) -> ProducerValue {
+		ProducerValue { name: name.into(), version: version.into() }
+	}
+
+	#[test]
+	fn producers_section() {
+		let module = deserialize_file("./res/cases/v1/producers.wasm")
+			.expect("Module should be deserialized")
+			.parse_producers()
+			.expect("Producers section should be deserialized");
+		let producers = module.producers_section().expect("producers.wasm has a producers section");
+
+		assert_eq!(producers.language(), Some(&[value("Rust", "")][..]));
+		assert_eq!(
+			producers.processed_by(),
+			Some(&[value("rustc", "1.70.0 (90c541806 2023-05-31)")][..])
+		);
+		assert_eq!(producers.sdk(), None);
+	}
+
+	#[test]
+	fn producers_section_roundtrip() {
+		let mut module = deserialize_file("./res/cases/v1/producers.wasm")
+			.expect("Module should be deserialized")
+			.parse_producers()
+			.expect("Producers section should be deserialized");
+		module
+			.producers_section_mut()
+			.expect("producers.wasm has a producers section")
+			.add("processed-by", "wasm-opt", "113");
+
+		let module: Module =
+			deserialize_buffer(&serialize(module).expect("Module should be serialized"))
+				.expect("Serialized module should be deserialized");
+		let module = module.parse_producers().expect("Producers section should be deserialized");
+		let producers = module.producers_section().expect("producers section is preserved");
+
+		assert_eq!(
+			producers.processed_by(),
+			Some(&[value("rustc", "1.70.0 (90c541806 2023-05-31)"), value("wasm-opt", "113")][..])
+		);
+	}
+
+	#[test]
+	fn duplicate_fields_are_merged() {
+		let producers = ProducersSection::new(vec![
+			ProducersField { name: "language".into(), values: vec![value("C", "")] },
+			ProducersField { name: "sdk".into(), values: vec![value("Emscripten", "3.1")] },
+			ProducersField {
+				name: "language".into(),
+				values: vec![value("Rust", ""), value("C", "11")],
+			},
+		]);
+
+		assert_eq!(producers.fields().len(), 2);
+		assert_eq!(producers.language(), Some(&[value("C", "11"), value("Rust", "")][..]));
+
+		let reparsed: ProducersSection = deserialize_buffer(
+			&serialize(producers.clone()).expect("Section should be serialized"),
+		)
+		.expect("Section should be deserialized");
+		assert_eq!(reparsed, producers);
+	}
+}
@@ -0,0 +1,157 @@
+use super::{Instruction, OpcodeId};
+use core::{fmt, ops::RangeInclusive};
+
+/// Post-MVP proposal introducing some of the instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Proposal {
+	/// Name of the proposal.
+	pub name: &'static str,
+	/// Cargo feature of this crate supporting the proposal, even partly, if any.
+	pub feature: Option<&'static str>,
+}
+
+impl Proposal {
+	/// Proposal which introduced the opcode of the instruction, `None` for MVP opcodes.
+	pub(crate) fn of(instruction: &Instruction) -> Option<Proposal> {
+		match instruction.code() {
+			OpcodeId::Single(opcode) => find_proposal(None, u32::from(opcode)),
+			OpcodeId::Prefixed(prefix, opcode) => find_proposal(Some(prefix), opcode),
+		}
+	}
+
+	/// Whether the feature supporting the proposal is enabled.
+	pub fn is_enabled(&self) -> bool {
+		ENABLED_FEATURES
+			.iter()
+			.any(|&(feature, enabled)| enabled && self.feature == Some(feature))
+	}
+}
+
+const ENABLED_FEATURES: &[(&str, bool)] = &[
+	("sign_ext", cfg!(feature = "sign_ext")),
+	("exceptions", cfg!(feature = "exceptions")),
+	("bulk", cfg!(feature = "bulk")),
+	("simd", cfg!(feature = "simd")),
+	("atomics", cfg!(feature = "atomics")),
+	("multi_value", cfg!(feature = "multi_value")),
+	("multi_memory", cfg!(feature = "multi_memory")),
+	("reference_types", cfg!(feature = "reference_types")),
+];
+
+pub(crate) const SIGN_EXT: Proposal =
+	Proposal { name: "sign-extension operators", feature: Some("sign_ext") };
+pub(crate) const EXCEPTIONS: Proposal =
+	Proposal { name: "exception handling", feature: Some("exceptions") };
+pub(crate) const TAIL_CALL: Proposal = Proposal { name: "tail call", feature: None };
+pub(crate) const REFERENCE_TYPES: Proposal =
+	Proposal { name: "reference types", feature: Some("reference_types") };
+pub(crate) const SATURATING_FLOAT_TO_INT: Proposal =
+	Proposal { name: "non-trapping float-to-int conversions", feature: None };
+pub(crate) const BULK: Proposal =
+	Proposal { name: "bulk memory operations", feature: Some("bulk") };
+pub(crate) const SIMD: Proposal = Proposal { name: "fixed-width SIMD", feature: Some("simd") };
+pub(crate) const THREADS: Proposal = Proposal { name: "threads", feature: Some("atomics") };
+pub(crate) const MULTI_VALUE: Proposal =
+	Proposal { name: "multi-value", feature: Some("multi_value") };
+
+/// Opcodes introduced by proposals, as `(prefix, opcodes, proposal)`.
+const PROPOSAL_OPCODES: &[(Option<u8>, RangeInclusive<u32>, Proposal)] = &[
+	(None, 0x06..=0x09, EXCEPTIONS),
+	(None, 0x12..=0x13, TAIL_CALL),
+	(None, 0x18..=0x19, EXCEPTIONS),
+	(None, 0x1c..=0x1c, REFERENCE_TYPES),
+	(None, 0x25..=0x26, REFERENCE_TYPES),
+	(None, 0xc0..=0xc4, SIGN_EXT),
+	(None, 0xd0..=0xd2, REFERENCE_TYPES),
+	(Some(0xfc), 0x00..=0x07, SATURATING_FLOAT_TO_INT),
+	(Some(0xfc), 0x08..=0x0e, BULK),
+	(Some(0xfc), 0x0f..=0x11, REFERENCE_TYPES),
+	(Some(0xfd), 0x00..=u32::MAX, SIMD),
+	(Some(0xfe), 0x00..=u32::MAX, THREADS),
+];
+
+/// Instruction which could not be decoded because its opcode is unknown.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UnsupportedOpcode {
+	/// Prefix byte of the opcode, for prefixed instructions.
+	pub prefix: Option<u8>,
+	/// Opcode, or the sub-opcode following the prefix.
+	pub opcode: u32,
+	/// Offset of the instruction from the start of the function code (after the locals).
+	pub offset: usize,
+	/// Index of the function body in the code section, if known.
+	pub function: Option<usize>,
+}
+
+impl UnsupportedOpcode {
+	/// Proposal which introduced the opcode, if it is a known one.
+	pub fn proposal(&self) -> Option<Proposal> {
+		find_proposal(self.prefix, self.opcode)
+	}
+
+	fn proposal_enabled(&self) -> bool {
+		self.proposal().map_or(false, |proposal| proposal.is_enabled())
+	}
+}
+
+fn find_proposal(prefix: Option<u8>, opcode: u32) -> Option<Proposal> {
+	PROPOSAL_OPCODES
+		.iter()
+		.find(|(known, opcodes, _)| *known == prefix && opcodes.contains(&opcode))
+		.map(|&(_, _, proposal)| proposal)
+}
+
+impl fmt::Display for UnsupportedOpcode {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.prefix {
+			Some(prefix) => write!(f, "Unsupported opcode {:#04x} {:#04x}", prefix, self.opcode)?,
+			None => write!(f, "Unsupported opcode {:#04x}", self.opcode)?,
+		}
+		write!(f, " at offset {}", self.offset)?;
+		if let Some(function) = self.function {
+			write!(f, " of function body {}", function)?;
+		}
+		match self.proposal() {
+			Some(Proposal { name, feature: Some(feature) }) if !self.proposal_enabled() =>
+				write!(f, " ({} proposal, enable the `{}` feature)", name, feature),
+			Some(Proposal { name, .. }) => write!(f, " ({} proposal)", name),
+			None => Ok(()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{UnsupportedOpcode, BULK, REFERENCE_TYPES, SIMD};
+
+	fn opcode(prefix: Option<u8>, opcode: u32) -> UnsupportedOpcode {
+		UnsupportedOpcode { prefix, opcode, offset: 3, function: None }
+	}
+
+	#[test]
+	fn proposals() {
+		assert_eq!(opcode(Some(0xfc), 0x0a).proposal(), Some(BULK));
+		assert_eq!(opcode(Some(0xfd), 300).proposal(), Some(SIMD));
+		assert_eq!(opcode(None, 0xd2).proposal(), Some(REFERENCE_TYPES));
+		assert_eq!(opcode(Some(0xfc), 0x12).proposal(), None);
+		assert_eq!(opcode(None, 0xff).proposal(), None);
+	}
+
+	#[test]
+	fn display() {
+		assert_eq!(opcode(None, 0xff).to_string(), "Unsupported opcode 0xff at offset 3");
+		assert_eq!(
+			UnsupportedOpcode { function: Some(2), ..opcode(None, 0x12) }.to_string(),
+			"Unsupported opcode 0x12 at offset 3 of function body 2 (tail call proposal)"
+		);
+		let hint = if cfg!(feature = "reference_types") {
+			""
+		} else {
+			", enable the `reference_types` feature"
+		};
+		assert_eq!(
+			opcode(None, 0xd2).to_string(),
+			format!("Unsupported opcode 0xd2 at offset 3 (reference types proposal{})", hint)
+		);
+	}
+}
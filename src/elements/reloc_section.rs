@@ -16,7 +16,7 @@ const TYPE_INDEX_LEB: u8 = 6;
 const GLOBAL_INDEX_LEB: u8 = 7;
 
 /// Relocation information.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct RelocSection {
 	/// Name of this section.
 	name: String,
@@ -111,7 +111,7 @@ impl Serialize for RelocSection {
 }
 
 /// Relocation entry.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum RelocationEntry {
 	/// Function index.
 	FunctionIndexLeb {
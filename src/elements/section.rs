@@ -1,21 +1,32 @@
+#[cfg(feature = "exceptions")]
+use super::TagType;
 use super::{
 	serialize, CountedList, CountedListWriter, CountedWriter, DataSegment, Deserialize,
 	ElementSegment, Error, ExportEntry, External, Func, FuncBody, GlobalEntry, ImportEntry,
-	MemoryType, Serialize, TableType, VarUint32, VarUint7,
+	Instructions, MemoryType, Serialize, TableType, VarUint32, VarUint7,
 };
 use crate::{elements, io};
 use alloc::{borrow::ToOwned, string::String, vec::Vec};
 
-use super::{name_section::NameSection, reloc_section::RelocSection, types::Type};
+use super::{
+	dylink_section::DylinkSection,
+	func::Padding,
+	limits::{DeserializeConfig, LebMode},
+	linking_section::LinkingSection,
+	name_section::NameSection,
+	producers_section::ProducersSection,
+	reloc_section::RelocSection,
+	types::Type,
+};
 
 #[cfg(feature = "reduced-stack-buffer")]
-const ENTRIES_BUFFER_LENGTH: usize = 256;
+pub(crate) const ENTRIES_BUFFER_LENGTH: usize = 256;
 
 #[cfg(not(feature = "reduced-stack-buffer"))]
-const ENTRIES_BUFFER_LENGTH: usize = 16384;
+pub(crate) const ENTRIES_BUFFER_LENGTH: usize = 16384;
 
 /// Section in the WebAssembly module.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Section {
 	/// Section is unparsed.
 	Unparsed {
@@ -36,6 +47,9 @@ pub enum Section {
 	Table(TableSection),
 	/// Memory definition section.
 	Memory(MemorySection),
+	/// Exception tags section.
+	#[cfg(feature = "exceptions")]
+	Tag(TagSection),
 	/// Global entries section.
 	Global(GlobalSection),
 	/// Export definitions.
@@ -60,12 +74,45 @@ pub enum Section {
 	/// Also note that currently there are serialization (but not de-serialization)
 	///   issues with this section (#198).
 	Reloc(RelocSection),
+	/// Linking metadata section.
+	///
+	/// Note that initially it is not parsed until `parse_linking` is called explicitly.
+	Linking(LinkingSection),
+	/// Tool identification section.
+	///
+	/// Note that initially it is not parsed until `parse_producers` is called explicitly.
+	Producers(ProducersSection),
+	/// Dynamic linking section of a side module.
+	///
+	/// Note that initially it is not parsed until `parse_dylink` is called explicitly.
+	Dylink(DylinkSection),
 }
 
 impl Deserialize for Section {
 	type Error = Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		Section::deserialize_with(reader, CodeDecoding::Eager)
+	}
+}
+
+/// How the instructions of function bodies are read.
+#[derive(Clone, Copy)]
+pub(crate) enum CodeDecoding<'a> {
+	/// Instructions are decoded.
+	Eager,
+	/// Instructions are kept undecoded.
+	Lazy,
+	/// Instructions are decoded within the caps of the config.
+	Limited(&'a DeserializeConfig),
+}
+
+impl Section {
+	/// Deserialize a section, reading function bodies as told by `code`.
+	pub(crate) fn deserialize_with<R: io::Read>(
+		reader: &mut R,
+		code: CodeDecoding,
+	) -> Result<Self, Error> {
 		let id = match VarUint7::deserialize(reader) {
 			// todo: be more selective detecting no more section
 			Err(_) => return Err(Error::UnexpectedEof),
@@ -88,7 +135,11 @@ impl Deserialize for Section {
 				Section::Start(start_idx.into())
 			},
 			9 => Section::Element(ElementSection::deserialize(reader)?),
-			10 => Section::Code(CodeSection::deserialize(reader)?),
+			10 => Section::Code(match code {
+				CodeDecoding::Eager => CodeSection::deserialize(reader)?,
+				CodeDecoding::Lazy => CodeSection::deserialize_lazy(reader)?,
+				CodeDecoding::Limited(config) => CodeSection::deserialize_limited(reader, config)?,
+			}),
 			11 => Section::Data(DataSection::deserialize(reader)?),
 			12 => {
 				let mut section_reader = SectionReader::new(reader)?;
@@ -96,6 +147,8 @@ impl Deserialize for Section {
 				section_reader.close()?;
 				Section::DataCount(count.into())
 			},
+			#[cfg(feature = "exceptions")]
+			13 => Section::Tag(TagSection::deserialize(reader)?),
 			invalid_id => return Err(Error::InvalidSectionId(invalid_id)),
 		})
 	}
@@ -134,6 +187,11 @@ impl Serialize for Section {
 				VarUint7::from(0x05).serialize(writer)?;
 				memory_section.serialize(writer)?;
 			},
+			#[cfg(feature = "exceptions")]
+			Section::Tag(tag_section) => {
+				VarUint7::from(0x0d).serialize(writer)?;
+				tag_section.serialize(writer)?;
+			},
 			Section::Global(global_section) => {
 				VarUint7::from(0x06).serialize(writer)?;
 				global_section.serialize(writer)?;
@@ -176,12 +234,78 @@ impl Serialize for Section {
 				VarUint7::from(0x00).serialize(writer)?;
 				reloc_section.serialize(writer)?;
 			},
+			Section::Linking(linking_section) => {
+				VarUint7::from(0x00).serialize(writer)?;
+				let custom = CustomSection {
+					name: "linking".to_owned(),
+					payload: serialize(linking_section)?,
+				};
+				custom.serialize(writer)?;
+			},
+			Section::Producers(producers_section) => {
+				VarUint7::from(0x00).serialize(writer)?;
+				let custom = CustomSection {
+					name: "producers".to_owned(),
+					payload: serialize(producers_section)?,
+				};
+				custom.serialize(writer)?;
+			},
+			Section::Dylink(dylink_section) => {
+				VarUint7::from(0x00).serialize(writer)?;
+				let custom = CustomSection {
+					name: dylink_section.name().to_owned(),
+					payload: serialize(dylink_section)?,
+				};
+				custom.serialize(writer)?;
+			},
 		}
 		Ok(())
 	}
 }
 
+/// Identity of a section within a module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SectionKey<'a> {
+	/// Section with the given id.
+	Id(u8),
+	/// Custom section with the given name.
+	Custom(&'a str),
+}
+
 impl Section {
+	/// Identity of the section: the name for custom sections (parsed ones included),
+	/// the section id otherwise.
+	pub fn key(&self) -> SectionKey<'_> {
+		match *self {
+			Section::Custom(ref custom_section) => SectionKey::Custom(custom_section.name()),
+			Section::Unparsed { id, .. } => SectionKey::Id(id),
+			Section::Type(_) => SectionKey::Id(0x01),
+			Section::Import(_) => SectionKey::Id(0x02),
+			Section::Function(_) => SectionKey::Id(0x03),
+			Section::Table(_) => SectionKey::Id(0x04),
+			Section::Memory(_) => SectionKey::Id(0x05),
+			#[cfg(feature = "exceptions")]
+			Section::Tag(_) => SectionKey::Id(0x0d),
+			Section::Global(_) => SectionKey::Id(0x06),
+			Section::Export(_) => SectionKey::Id(0x07),
+			Section::Start(_) => SectionKey::Id(0x08),
+			Section::Element(_) => SectionKey::Id(0x09),
+			Section::Code(_) => SectionKey::Id(0x0a),
+			Section::Data(_) => SectionKey::Id(0x0b),
+			Section::DataCount(_) => SectionKey::Id(0x0c),
+			Section::Name(_) => SectionKey::Custom("name"),
+			Section::Reloc(ref reloc_section) => SectionKey::Custom(reloc_section.name()),
+			Section::Linking(_) => SectionKey::Custom("linking"),
+			Section::Producers(_) => SectionKey::Custom("producers"),
+			Section::Dylink(ref dylink_section) => SectionKey::Custom(dylink_section.name()),
+		}
+	}
+
+	/// Whether this is a custom section (`id=0`), parsed or not.
+	pub fn is_custom(&self) -> bool {
+		matches!(self.key(), SectionKey::Custom(_) | SectionKey::Id(0x00))
+	}
+
 	pub(crate) fn order(&self) -> u8 {
 		match *self {
 			Section::Custom(_) => 0x00,
@@ -198,8 +322,25 @@ impl Section {
 			Section::DataCount(_) => 0x0a,
 			Section::Code(_) => 0x0b,
 			Section::Data(_) => 0x0c,
+			#[cfg(feature = "exceptions")]
+			Section::Tag(_) => 0x0d,
 			Section::Name(_) => 0x00,
 			Section::Reloc(_) => 0x00,
+			Section::Linking(_) => 0x00,
+			Section::Producers(_) => 0x00,
+			Section::Dylink(_) => 0x00,
+		}
+	}
+
+	/// Position of the section in the module, `0` for custom sections which can go anywhere.
+	///
+	/// This is [`Section::order`] for the MVP sections, with room for the tag section of the
+	/// exception handling proposal, which goes between the memory and global sections.
+	pub(crate) fn rank(&self) -> u8 {
+		match *self {
+			#[cfg(feature = "exceptions")]
+			Section::Tag(_) => 0x5 * 2 + 1,
+			_ => self.order() * 2,
 		}
 	}
 }
@@ -219,6 +360,19 @@ impl SectionReader {
 		Ok(SectionReader { cursor, declared_length })
 	}
 
+	/// Number of bytes read so far.
+	pub fn position(&self) -> usize {
+		self.cursor.position()
+	}
+
+	/// Consume the reader, returning the bytes which were not read yet.
+	pub fn into_remaining(self) -> Vec<u8> {
+		let position = self.cursor.position();
+		let mut buffer = self.cursor.into_inner();
+		buffer.drain(..position);
+		buffer
+	}
+
 	pub fn close(self) -> Result<(), io::Error> {
 		let cursor = self.cursor;
 		let buf_length = self.declared_length;
@@ -248,7 +402,7 @@ fn read_entries<R: io::Read, T: Deserialize<Error = elements::Error>>(
 }
 
 /// Custom section.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct CustomSection {
 	name: String,
 	payload: Vec<u8>,
@@ -289,7 +443,7 @@ impl Deserialize for CustomSection {
 		let buf = buffered_read!(ENTRIES_BUFFER_LENGTH, section_length, reader);
 		let mut cursor = io::Cursor::new(&buf[..]);
 		let name = String::deserialize(&mut cursor)?;
-		let payload = buf[cursor.position() as usize..].to_vec();
+		let payload = buf[cursor.position()..].to_vec();
 		Ok(CustomSection { name, payload })
 	}
 }
@@ -309,7 +463,7 @@ impl Serialize for CustomSection {
 }
 
 /// Section with type declarations.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct TypeSection(Vec<Type>);
 
 impl TypeSection {
@@ -343,8 +497,7 @@ impl Serialize for TypeSection {
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		let mut counted_writer = CountedWriter::new(writer);
 		let data = self.0;
-		let counted_list =
-			CountedListWriter::<Type, _>(data.len(), data.into_iter().map(Into::into));
+		let counted_list = CountedListWriter::<Type, _>(data.len(), data.into_iter());
 		counted_list.serialize(&mut counted_writer)?;
 		counted_writer.done()?;
 		Ok(())
@@ -352,7 +505,7 @@ impl Serialize for TypeSection {
 }
 
 /// Section of the imports definition.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct ImportSection(Vec<ImportEntry>);
 
 impl ImportSection {
@@ -402,8 +555,7 @@ impl Serialize for ImportSection {
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		let mut counted_writer = CountedWriter::new(writer);
 		let data = self.0;
-		let counted_list =
-			CountedListWriter::<ImportEntry, _>(data.len(), data.into_iter().map(Into::into));
+		let counted_list = CountedListWriter::<ImportEntry, _>(data.len(), data.into_iter());
 		counted_list.serialize(&mut counted_writer)?;
 		counted_writer.done()?;
 		Ok(())
@@ -411,7 +563,7 @@ impl Serialize for ImportSection {
 }
 
 /// Section with function signatures definition.
-#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FunctionSection(Vec<Func>);
 
 impl FunctionSection {
@@ -456,7 +608,7 @@ impl Serialize for FunctionSection {
 }
 
 /// Section with table definition (currently only one is allowed).
-#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TableSection(Vec<TableType>);
 
 impl TableSection {
@@ -490,8 +642,7 @@ impl Serialize for TableSection {
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		let mut counted_writer = CountedWriter::new(writer);
 		let data = self.0;
-		let counted_list =
-			CountedListWriter::<TableType, _>(data.len(), data.into_iter().map(Into::into));
+		let counted_list = CountedListWriter::<TableType, _>(data.len(), data.into_iter());
 		counted_list.serialize(&mut counted_writer)?;
 		counted_writer.done()?;
 		Ok(())
@@ -499,7 +650,7 @@ impl Serialize for TableSection {
 }
 
 /// Section with table definition (currently only one entry is allowed).
-#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MemorySection(Vec<MemoryType>);
 
 impl MemorySection {
@@ -533,8 +684,53 @@ impl Serialize for MemorySection {
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		let mut counted_writer = CountedWriter::new(writer);
 		let data = self.0;
-		let counted_list =
-			CountedListWriter::<MemoryType, _>(data.len(), data.into_iter().map(Into::into));
+		let counted_list = CountedListWriter::<MemoryType, _>(data.len(), data.into_iter());
+		counted_list.serialize(&mut counted_writer)?;
+		counted_writer.done()?;
+		Ok(())
+	}
+}
+
+/// Section with exception tags definition.
+#[cfg(feature = "exceptions")]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TagSection(Vec<TagType>);
+
+#[cfg(feature = "exceptions")]
+impl TagSection {
+	/// List of all tags in the section.
+	pub fn entries(&self) -> &[TagType] {
+		&self.0
+	}
+
+	/// New tag section with provided tags.
+	pub fn with_entries(entries: Vec<TagType>) -> Self {
+		TagSection(entries)
+	}
+
+	/// Mutable list of all tags in the section.
+	pub fn entries_mut(&mut self) -> &mut Vec<TagType> {
+		&mut self.0
+	}
+}
+
+#[cfg(feature = "exceptions")]
+impl Deserialize for TagSection {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		Ok(TagSection(read_entries(reader)?))
+	}
+}
+
+#[cfg(feature = "exceptions")]
+impl Serialize for TagSection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		let mut counted_writer = CountedWriter::new(writer);
+		let data = self.0;
+		let counted_list = CountedListWriter::<TagType, _>(data.len(), data.into_iter());
 		counted_list.serialize(&mut counted_writer)?;
 		counted_writer.done()?;
 		Ok(())
@@ -542,7 +738,7 @@ impl Serialize for MemorySection {
 }
 
 /// Globals definition section.
-#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct GlobalSection(Vec<GlobalEntry>);
 
 impl GlobalSection {
@@ -576,8 +772,7 @@ impl Serialize for GlobalSection {
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		let mut counted_writer = CountedWriter::new(writer);
 		let data = self.0;
-		let counted_list =
-			CountedListWriter::<GlobalEntry, _>(data.len(), data.into_iter().map(Into::into));
+		let counted_list = CountedListWriter::<GlobalEntry, _>(data.len(), data.into_iter());
 		counted_list.serialize(&mut counted_writer)?;
 		counted_writer.done()?;
 		Ok(())
@@ -585,7 +780,7 @@ impl Serialize for GlobalSection {
 }
 
 /// List of exports definition.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct ExportSection(Vec<ExportEntry>);
 
 impl ExportSection {
@@ -619,8 +814,7 @@ impl Serialize for ExportSection {
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		let mut counted_writer = CountedWriter::new(writer);
 		let data = self.0;
-		let counted_list =
-			CountedListWriter::<ExportEntry, _>(data.len(), data.into_iter().map(Into::into));
+		let counted_list = CountedListWriter::<ExportEntry, _>(data.len(), data.into_iter());
 		counted_list.serialize(&mut counted_writer)?;
 		counted_writer.done()?;
 		Ok(())
@@ -628,7 +822,7 @@ impl Serialize for ExportSection {
 }
 
 /// Section with function bodies of the module.
-#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CodeSection(Vec<FuncBody>);
 
 impl CodeSection {
@@ -646,13 +840,167 @@ impl CodeSection {
 	pub fn bodies_mut(&mut self) -> &mut Vec<FuncBody> {
 		&mut self.0
 	}
+
+	/// Deserialize the section without decoding the instructions of function bodies.
+	fn deserialize_lazy<R: io::Read>(reader: &mut R) -> Result<Self, Error> {
+		let mut section_reader = SectionReader::new(reader)?;
+		let count: usize = VarUint32::deserialize(&mut section_reader)?.into();
+		let mut bodies = Vec::new();
+		for _ in 0..count {
+			bodies.push(FuncBody::deserialize_lazy(&mut section_reader)?);
+		}
+		section_reader.close()?;
+		Ok(CodeSection(bodies))
+	}
+
+	/// Deserialize the section, failing as soon as a cap of the config is exceeded.
+	fn deserialize_limited<R: io::Read>(
+		reader: &mut R,
+		config: &DeserializeConfig,
+	) -> Result<Self, Error> {
+		let exceeded = |limit, function| Error::LimitExceeded { limit, function };
+		let mut section_reader = SectionReader::new(reader)?;
+		let count: u32 = VarUint32::deserialize(&mut section_reader)?.into();
+		if count > config.max_functions {
+			return Err(exceeded("max_functions", config.max_functions))
+		}
+
+		let mut bodies = Vec::new();
+		let mut remaining = config.max_module_instructions;
+		for index in 0..count {
+			let start = section_reader.position();
+			let size: u32 = VarUint32::deserialize(&mut section_reader)?.into();
+			let size_width = section_reader.position() - start;
+			if size > config.max_body_size {
+				return Err(exceeded("max_body_size", index))
+			}
+			let payload = read_payload(&mut section_reader, size as usize)?;
+			let mut body_reader = io::Cursor::new(&payload[..]);
+			let locals = FuncBody::deserialize_locals(&mut body_reader)?;
+
+			let max = config.max_function_instructions.min(remaining);
+			let instructions = Instructions::deserialize_at_most(&mut body_reader, max as usize)
+				.map_err(|err| in_function_body(err, index as usize))?
+				.ok_or_else(|| {
+					if max == config.max_function_instructions {
+						exceeded("max_function_instructions", index)
+					} else {
+						exceeded("max_module_instructions", index)
+					}
+				})?;
+			if body_reader.position() != payload.len() {
+				return Err(io::Error::InvalidData.into())
+			}
+			remaining -= instructions.elements().len() as u32;
+			let mut body = FuncBody::new(locals, instructions);
+			match config.leb_mode {
+				LebMode::Preserve =>
+					if let Some(padding) = Padding::find(&body, &payload, size_width)? {
+						body.set_padding(padding);
+					},
+				LebMode::Strict if Padding::is_padded(&body, size_width + payload.len())? =>
+					return Err(Error::NonMinimalEncoding(index)),
+				// Only marks the body as padded, it is normalized along with the module.
+				LebMode::Permissive if Padding::is_padded(&body, size_width + payload.len())? =>
+					body.set_padding(Padding::default()),
+				_ => {},
+			}
+			bodies.push(body);
+		}
+		section_reader.close()?;
+		Ok(CodeSection(bodies))
+	}
+}
+
+#[cfg(feature = "parallel")]
+impl CodeSection {
+	/// Deserialize the section decoding function bodies in parallel.
+	///
+	/// Bodies are length-prefixed, so the section is first split into the byte ranges
+	/// of the bodies and only then are the bodies decoded. Errors are reported the same
+	/// way as by the sequential path: the error of the body with the lowest index wins.
+	fn deserialize_parallel<R: io::Read>(reader: &mut R) -> Result<Self, Error> {
+		use rayon::prelude::*;
+
+		let payload = SectionReader::new(reader)?.into_remaining();
+		let mut cursor = io::Cursor::new(&payload[..]);
+		let count: usize = VarUint32::deserialize(&mut cursor)?.into();
+
+		let mut position = cursor.position();
+		let mut ranges = Vec::new();
+		let mut split_error = None;
+		for _ in 0..count {
+			let mut cursor = io::Cursor::new(&payload[position..]);
+			let length = match VarUint32::deserialize(&mut cursor) {
+				Ok(length) => u32::from(length) as usize,
+				Err(err) => {
+					split_error = Some(err);
+					break
+				},
+			};
+			let end = position + cursor.position() + length;
+			if end > payload.len() {
+				split_error = Some(io::Error::UnexpectedEof.into());
+				break
+			}
+			ranges.push(position..end);
+			position = end;
+		}
+
+		let bodies = ranges
+			.into_par_iter()
+			.enumerate()
+			.map(|(index, range)| {
+				FuncBody::deserialize(&mut io::Cursor::new(&payload[range]))
+					.map_err(|err| in_function_body(err, index))
+			})
+			.collect::<Vec<_>>()
+			.into_iter()
+			.collect::<Result<Vec<_>, _>>()?;
+
+		if let Some(err) = split_error {
+			return Err(err)
+		}
+		if position != payload.len() {
+			return Err(io::Error::InvalidData.into())
+		}
+		Ok(CodeSection(bodies))
+	}
+}
+
+/// Read `size` bytes, allocating as they are read.
+fn read_payload<R: io::Read>(reader: &mut R, size: usize) -> Result<Vec<u8>, Error> {
+	Ok(buffered_read!(ENTRIES_BUFFER_LENGTH, size, reader))
+}
+
+/// Attach the index of the function body to errors which can carry it.
+fn in_function_body(mut err: Error, index: usize) -> Error {
+	if let Error::UnsupportedOpcode(ref mut opcode) = err {
+		opcode.function = Some(index);
+	}
+	err
 }
 
 impl Deserialize for CodeSection {
 	type Error = Error;
 
+	#[cfg(not(feature = "parallel"))]
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
-		Ok(CodeSection(read_entries(reader)?))
+		let mut section_reader = SectionReader::new(reader)?;
+		let count: usize = VarUint32::deserialize(&mut section_reader)?.into();
+		let mut bodies = Vec::new();
+		for index in 0..count {
+			let body = FuncBody::deserialize(&mut section_reader)
+				.map_err(|err| in_function_body(err, index))?;
+			bodies.push(body);
+		}
+		section_reader.close()?;
+		Ok(CodeSection(bodies))
+	}
+
+	#[cfg(feature = "parallel")]
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		CodeSection::deserialize_parallel(reader)
 	}
 }
 
@@ -662,8 +1010,7 @@ impl Serialize for CodeSection {
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		let mut counted_writer = CountedWriter::new(writer);
 		let data = self.0;
-		let counted_list =
-			CountedListWriter::<FuncBody, _>(data.len(), data.into_iter().map(Into::into));
+		let counted_list = CountedListWriter::<FuncBody, _>(data.len(), data.into_iter());
 		counted_list.serialize(&mut counted_writer)?;
 		counted_writer.done()?;
 		Ok(())
@@ -671,7 +1018,7 @@ impl Serialize for CodeSection {
 }
 
 /// Element entries section.
-#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ElementSection(Vec<ElementSegment>);
 
 impl ElementSection {
@@ -705,8 +1052,7 @@ impl Serialize for ElementSection {
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		let mut counted_writer = CountedWriter::new(writer);
 		let data = self.0;
-		let counted_list =
-			CountedListWriter::<ElementSegment, _>(data.len(), data.into_iter().map(Into::into));
+		let counted_list = CountedListWriter::<ElementSegment, _>(data.len(), data.into_iter());
 		counted_list.serialize(&mut counted_writer)?;
 		counted_writer.done()?;
 		Ok(())
@@ -714,7 +1060,7 @@ impl Serialize for ElementSection {
 }
 
 /// Data entries definitions.
-#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DataSection(Vec<DataSegment>);
 
 impl DataSection {
@@ -748,8 +1094,7 @@ impl Serialize for DataSection {
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		let mut counted_writer = CountedWriter::new(writer);
 		let data = self.0;
-		let counted_list =
-			CountedListWriter::<DataSegment, _>(data.len(), data.into_iter().map(Into::into));
+		let counted_list = CountedListWriter::<DataSegment, _>(data.len(), data.into_iter());
 		counted_list.serialize(&mut counted_writer)?;
 		counted_writer.done()?;
 		Ok(())
@@ -762,7 +1107,7 @@ mod tests {
 	use super::{
 		super::{
 			deserialize_buffer, deserialize_file, serialize, BlockType, DataSegment,
-			ElementSegment, FuncBody, InitExpr, Instructions, Local, ValueType,
+			ElementSegment, Error, FuncBody, InitExpr, Instructions, Local, ValueType,
 		},
 		CodeSection, DataSection, ElementSection, Section, Type, TypeSection,
 	};
@@ -1011,7 +1356,7 @@ mod tests {
 			vec![Local::new(1, ValueType::I32)],
 			Instructions::new(vec![
 				Block(BlockType::Value(ValueType::I32)),
-				GetGlobal(0),
+				GetGlobal(0.into()),
 				End,
 				End,
 			]),
@@ -1037,6 +1382,134 @@ mod tests {
 		);
 	}
 
+	#[cfg(feature = "parallel")]
+	fn synthetic_bodies(count: u32) -> Vec<FuncBody> {
+		use super::super::Instruction::*;
+
+		(0..count)
+			.map(|i| {
+				let mut instructions = vec![I32Const(i as i32)];
+				for j in 0..i % 64 {
+					instructions.extend([I32Const(j as i32), I32Add]);
+				}
+				instructions.extend([Drop, End]);
+				FuncBody::new(
+					vec![Local::new(i % 3, ValueType::I64)],
+					Instructions::new(instructions),
+				)
+			})
+			.collect()
+	}
+
+	#[cfg(feature = "parallel")]
+	fn code_section_bytes(bodies: Vec<Vec<u8>>) -> Vec<u8> {
+		use super::super::VarUint32;
+
+		let mut payload = serialize(VarUint32::from(bodies.len())).expect("count to serialize");
+		for body in bodies {
+			payload.extend(body);
+		}
+		let mut section = serialize(VarUint32::from(payload.len())).expect("length to serialize");
+		section.extend(payload);
+		section
+	}
+
+	#[cfg(feature = "parallel")]
+	#[test]
+	fn code_section_parallel() {
+		use crate::io;
+
+		let bodies = synthetic_bodies(5000);
+		let buf = serialize(CodeSection::with_bodies(bodies.clone()))
+			.expect("Code section to be serialized");
+
+		let sequential = CodeSection(
+			super::read_entries(&mut io::Cursor::new(&buf[..]))
+				.expect("Code section to be deserialized sequentially"),
+		);
+		let parallel = CodeSection::deserialize_parallel(&mut io::Cursor::new(&buf[..]))
+			.expect("Code section to be deserialized in parallel");
+
+		assert_eq!(parallel.bodies(), &bodies[..]);
+		assert_eq!(parallel, sequential);
+	}
+
+	#[cfg(feature = "parallel")]
+	#[test]
+	fn code_section_parallel_first_error() {
+		use crate::io;
+
+		let mut bodies: Vec<Vec<u8>> = synthetic_bodies(5000)
+			.into_iter()
+			.map(|body| serialize(body).expect("Function body to be serialized"))
+			.collect();
+		// Replace `drop` with an unknown opcode.
+		let drop_position = bodies[1234].len() - 2;
+		bodies[1234][drop_position] = 0xff;
+		// Declare `u32::MAX` locals on top of the existing ones.
+		bodies[4321].splice(1..2, [0x02, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x7e]);
+		bodies[4321][0] += 6;
+		assert!(matches!(deserialize_buffer::<FuncBody>(&bodies[4321]), Err(Error::TooManyLocals)));
+		let buf = code_section_bytes(bodies);
+
+		let sequential = super::read_entries::<_, FuncBody>(&mut io::Cursor::new(&buf[..]))
+			.expect_err("Sequential deserialization should fail");
+		assert!(matches!(
+			sequential,
+			Error::UnsupportedOpcode(ref opcode) if opcode.opcode == 0xff
+		));
+
+		for _ in 0..16 {
+			let parallel = CodeSection::deserialize_parallel(&mut io::Cursor::new(&buf[..]))
+				.expect_err("Parallel deserialization should fail");
+			assert!(matches!(
+				parallel,
+				Error::UnsupportedOpcode(ref opcode)
+					if opcode.opcode == 0xff && opcode.function == Some(1234)
+			));
+		}
+	}
+
+	#[test]
+	fn code_section_unsupported_opcode() {
+		let payload = [
+			0x0b, // section length
+			0x02, // 2 function bodies
+			0x02, 0x00, 0x0b, // empty body
+			0x06, 0x00, // second body, no locals
+			0x41, 0x01, // i32.const 1
+			0xff, // unknown opcode
+			0x1a, 0x0b, // drop, end
+		];
+		match deserialize_buffer::<CodeSection>(&payload) {
+			Err(Error::UnsupportedOpcode(opcode)) => {
+				assert_eq!((opcode.prefix, opcode.opcode), (None, 0xff));
+				assert_eq!(opcode.offset, 2);
+				assert_eq!(opcode.function, Some(1));
+				assert_eq!(opcode.proposal(), None);
+			},
+			other => panic!("unexpected result: {:?}", other),
+		}
+	}
+
+	#[cfg(not(feature = "bulk"))]
+	#[test]
+	fn code_section_disabled_proposal() {
+		let payload = [
+			0x08, // section length
+			0x01, // 1 function body
+			0x06, 0x00, // no locals
+			0xfc, 0x0a, 0x00, 0x00, // memory.copy
+			0x0b, // end
+		];
+		let error = deserialize_buffer::<CodeSection>(&payload).expect_err("bulk is disabled");
+		assert_eq!(
+			error.to_string(),
+			"Unsupported opcode 0xfc 0x0a at offset 0 of function body 0 \
+			 (bulk memory operations proposal, enable the `bulk` feature)"
+		);
+	}
+
 	#[test]
 	fn start_section() {
 		let section: Section =
@@ -1,13 +1,31 @@
 use super::{CountedList, CountedListWriter, Deserialize, Error, InitExpr, Serialize, VarUint32};
 use crate::io;
 use alloc::vec::Vec;
+use core::hash::{Hash, Hasher};
 
 #[cfg(feature = "bulk")]
-const FLAG_MEMZERO: u32 = 0;
+use super::{TableElementType, Uint8, UnsupportedOpcode};
 #[cfg(feature = "bulk")]
-const FLAG_PASSIVE: u32 = 1;
+use alloc::boxed::Box;
 #[cfg(feature = "bulk")]
-const FLAG_MEM_NONZERO: u32 = 2;
+use core::ops::{Deref, DerefMut};
+
+#[cfg(feature = "bulk")]
+const FLAG_PASSIVE: u32 = 0b001;
+#[cfg(feature = "bulk")]
+const FLAG_EXPLICIT_INDEX: u32 = 0b010;
+#[cfg(feature = "bulk")]
+const FLAG_DECLARATIVE: u32 = FLAG_PASSIVE | FLAG_EXPLICIT_INDEX;
+#[cfg(feature = "bulk")]
+const FLAG_EXPRESSIONS: u32 = 0b100;
+
+/// Element kind of function references, in segments of function indices.
+#[cfg(feature = "bulk")]
+const ELEM_KIND_FUNCREF: u8 = 0x00;
+#[cfg(feature = "bulk")]
+const REF_FUNC: u8 = 0xd2;
+#[cfg(feature = "bulk")]
+const END: u8 = 0x0b;
 
 #[cfg(feature = "reduced-stack-buffer")]
 const VALUES_BUFFER_LENGTH: usize = 256;
@@ -16,14 +34,53 @@ const VALUES_BUFFER_LENGTH: usize = 256;
 const VALUES_BUFFER_LENGTH: usize = 16384;
 
 /// Entry in the element section.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct ElementSegment {
 	index: u32,
 	offset: Option<InitExpr>,
 	members: Vec<u32>,
 
 	#[cfg(feature = "bulk")]
-	passive: bool,
+	mode: SegmentMode,
+	/// Flags the segment was read with, to write it back in the same encoding.
+	#[cfg(feature = "bulk")]
+	flags: u32,
+}
+
+impl PartialEq for ElementSegment {
+	/// The encoding the segment was read with is left out.
+	fn eq(&self, other: &Self) -> bool {
+		#[cfg(feature = "bulk")]
+		if self.mode != other.mode {
+			return false
+		}
+		self.index == other.index && self.offset == other.offset && self.members == other.members
+	}
+}
+
+impl Eq for ElementSegment {}
+
+impl Hash for ElementSegment {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		#[cfg(feature = "bulk")]
+		self.mode.hash(state);
+		self.index.hash(state);
+		self.offset.hash(state);
+		self.members.hash(state);
+	}
+}
+
+/// How a segment is used when the module is instantiated.
+#[cfg(feature = "bulk")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SegmentMode {
+	/// Copied into its table or memory at the offset of the segment.
+	Active,
+	/// Only copied by `table.init` and `memory.init`.
+	Passive,
+	/// Never copied, only declares the functions referenced by `ref.func`. Element segments
+	/// only.
+	Declarative,
 }
 
 impl ElementSegment {
@@ -35,7 +92,9 @@ impl ElementSegment {
 			members,
 
 			#[cfg(feature = "bulk")]
-			passive: false,
+			mode: SegmentMode::Active,
+			#[cfg(feature = "bulk")]
+			flags: 0,
 		}
 	}
 
@@ -71,19 +130,91 @@ impl ElementSegment {
 
 #[cfg(feature = "bulk")]
 impl ElementSegment {
-	/// Whether or not this table segment is "passive"
-	pub fn passive(&self) -> bool {
-		self.passive
+	/// How the segment is used.
+	pub fn mode(&self) -> SegmentMode {
+		self.mode
+	}
+
+	/// Set how the segment is used.
+	///
+	/// Only active segments have an offset, it is ignored in the other modes.
+	pub fn set_mode(&mut self, mode: SegmentMode) {
+		self.mode = mode;
 	}
 
 	/// Whether or not this table segment is "passive"
-	pub fn passive_mut(&mut self) -> &mut bool {
-		&mut self.passive
+	pub fn passive(&self) -> bool {
+		self.mode == SegmentMode::Passive
 	}
 
 	/// Set whether or not this table segment is "passive"
 	pub fn set_passive(&mut self, passive: bool) {
-		self.passive = passive;
+		self.mode = if passive { SegmentMode::Passive } else { SegmentMode::Active };
+	}
+
+	/// Whether or not this table segment is "passive" (mutable).
+	#[deprecated(since = "0.45.2", note = "use `mode` and `set_mode`")]
+	pub fn passive_mut(&mut self) -> PassiveMut<'_> {
+		PassiveMut::new(&mut self.mode)
+	}
+
+	/// Flags encoding the segment, those it was read with as long as they fit its mode and
+	/// table.
+	fn flags(&self) -> u32 {
+		let explicit_index =
+			self.index != 0 || self.flags & FLAG_DECLARATIVE == FLAG_EXPLICIT_INDEX;
+		let mode = match self.mode {
+			SegmentMode::Active if explicit_index => FLAG_EXPLICIT_INDEX,
+			SegmentMode::Active => 0,
+			SegmentMode::Passive => FLAG_PASSIVE,
+			SegmentMode::Declarative => FLAG_DECLARATIVE,
+		};
+		mode | (self.flags & FLAG_EXPRESSIONS)
+	}
+}
+
+/// Whether a segment is passive, written back to its mode when dropped, see
+/// [`ElementSegment::passive_mut`] and [`DataSegment::passive_mut`].
+///
+/// Clearing it makes a passive segment active, other modes are kept.
+#[cfg(feature = "bulk")]
+pub struct PassiveMut<'a> {
+	mode: &'a mut SegmentMode,
+	passive: bool,
+}
+
+#[cfg(feature = "bulk")]
+impl<'a> PassiveMut<'a> {
+	fn new(mode: &'a mut SegmentMode) -> Self {
+		let passive = *mode == SegmentMode::Passive;
+		PassiveMut { mode, passive }
+	}
+}
+
+#[cfg(feature = "bulk")]
+impl Deref for PassiveMut<'_> {
+	type Target = bool;
+
+	fn deref(&self) -> &bool {
+		&self.passive
+	}
+}
+
+#[cfg(feature = "bulk")]
+impl DerefMut for PassiveMut<'_> {
+	fn deref_mut(&mut self) -> &mut bool {
+		&mut self.passive
+	}
+}
+
+#[cfg(feature = "bulk")]
+impl Drop for PassiveMut<'_> {
+	fn drop(&mut self) {
+		if self.passive {
+			*self.mode = SegmentMode::Passive;
+		} else if *self.mode == SegmentMode::Passive {
+			*self.mode = SegmentMode::Active;
+		}
 	}
 }
 
@@ -103,52 +234,127 @@ impl Deserialize for ElementSegment {
 		Ok(ElementSegment { index, offset: Some(offset), members })
 	}
 
+	/// Decodes the eight encodings of the bulk memory proposal, where the table index was
+	/// repurposed for flags.
+	///
+	/// Bit 0 marks non-active segments, which are declarative if bit 1 is set too. Otherwise
+	/// bit 1 marks an explicit table index. Bit 2 marks initializers given as expressions,
+	/// which can only be `ref.func` as this crate doesn't support the reference types proposal.
 	#[cfg(feature = "bulk")]
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
-		// This piece of data was treated as `index` [of the table], but was repurposed
-		// for flags in bulk-memory operations proposal.
 		let flags: u32 = VarUint32::deserialize(reader)?.into();
-		let index = if flags == FLAG_MEMZERO || flags == FLAG_PASSIVE {
-			0u32
-		} else if flags == FLAG_MEM_NONZERO {
+		if flags > (FLAG_DECLARATIVE | FLAG_EXPRESSIONS) {
+			return Err(Error::InvalidSegmentFlags(flags))
+		}
+		let mode = match flags & FLAG_DECLARATIVE {
+			FLAG_DECLARATIVE => SegmentMode::Declarative,
+			FLAG_PASSIVE => SegmentMode::Passive,
+			_ => SegmentMode::Active,
+		};
+		let index = if flags & FLAG_DECLARATIVE == FLAG_EXPLICIT_INDEX {
 			VarUint32::deserialize(reader)?.into()
 		} else {
-			return Err(Error::InvalidSegmentFlags(flags))
+			0
+		};
+		let offset = match mode {
+			SegmentMode::Active => Some(InitExpr::deserialize(reader)?),
+			_ => None,
 		};
-		let offset =
-			if flags == FLAG_PASSIVE { None } else { Some(InitExpr::deserialize(reader)?) };
 
-		let members: Vec<u32> = CountedList::<VarUint32>::deserialize(reader)?
-			.into_inner()
-			.into_iter()
-			.map(Into::into)
-			.collect();
+		let expressions = flags & FLAG_EXPRESSIONS != 0;
+		// Segments of table 0 in the MVP encoding implicitly hold function references.
+		if flags & (FLAG_PASSIVE | FLAG_EXPLICIT_INDEX) != 0 {
+			if expressions {
+				TableElementType::deserialize(reader)?;
+			} else {
+				let kind: u8 = Uint8::deserialize(reader)?.into();
+				if kind != ELEM_KIND_FUNCREF {
+					return Err(Error::UnknownTableElementType(kind as i8))
+				}
+			}
+		}
 
-		Ok(ElementSegment { index, offset, members, passive: flags == FLAG_PASSIVE })
+		let members = if expressions {
+			let count: usize = VarUint32::deserialize(reader)?.into();
+			let mut members = Vec::new();
+			for _ in 0..count {
+				members.push(deserialize_ref_func(reader)?);
+			}
+			members
+		} else {
+			CountedList::<VarUint32>::deserialize(reader)?
+				.into_inner()
+				.into_iter()
+				.map(Into::into)
+				.collect()
+		};
+
+		Ok(ElementSegment { index, offset, members, mode, flags })
+	}
+}
+
+/// Function index of a `ref.func` initializer expression.
+#[cfg(feature = "bulk")]
+fn deserialize_ref_func<R: io::Read>(reader: &mut R) -> Result<u32, Error> {
+	let opcode: u8 = Uint8::deserialize(reader)?.into();
+	if opcode != REF_FUNC {
+		return Err(Error::UnsupportedOpcode(Box::new(UnsupportedOpcode {
+			prefix: None,
+			opcode: opcode.into(),
+			offset: 0,
+			function: None,
+		})))
 	}
+	let index = VarUint32::deserialize(reader)?.into();
+	let end: u8 = Uint8::deserialize(reader)?.into();
+	if end != END {
+		return Err(Error::InvalidConstantExpression)
+	}
+	Ok(index)
 }
 
 impl Serialize for ElementSegment {
 	type Error = Error;
 
+	/// Uses the encoding the segment was read with, as long as it fits the mode and table of
+	/// the segment, and the shortest encoding otherwise.
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		#[cfg(feature = "bulk")]
 		{
-			if self.passive {
-				VarUint32::from(FLAG_PASSIVE).serialize(writer)?;
-			} else if self.index != 0 {
-				VarUint32::from(FLAG_MEM_NONZERO).serialize(writer)?;
+			let flags = self.flags();
+			VarUint32::from(flags).serialize(writer)?;
+			if flags & FLAG_DECLARATIVE == FLAG_EXPLICIT_INDEX {
 				VarUint32::from(self.index).serialize(writer)?;
-			} else {
-				VarUint32::from(FLAG_MEMZERO).serialize(writer)?;
+			}
+			if self.mode == SegmentMode::Active {
+				self.offset.ok_or(Error::InvalidSegmentMode)?.serialize(writer)?;
+			}
+			let expressions = flags & FLAG_EXPRESSIONS != 0;
+			if flags & FLAG_DECLARATIVE != 0 {
+				if expressions {
+					TableElementType::AnyFunc.serialize(writer)?;
+				} else {
+					Uint8::from(ELEM_KIND_FUNCREF).serialize(writer)?;
+				}
+			}
+			if expressions {
+				VarUint32::from(self.members.len()).serialize(writer)?;
+				for member in self.members {
+					Uint8::from(REF_FUNC).serialize(writer)?;
+					VarUint32::from(member).serialize(writer)?;
+					Uint8::from(END).serialize(writer)?;
+				}
+				return Ok(())
 			}
 		}
 		#[cfg(not(feature = "bulk"))]
-		VarUint32::from(self.index).serialize(writer)?;
-
-		if let Some(offset) = self.offset {
-			offset.serialize(writer)?;
+		{
+			VarUint32::from(self.index).serialize(writer)?;
+			if let Some(offset) = self.offset {
+				offset.serialize(writer)?;
+			}
 		}
+
 		let data = self.members;
 		let counted_list =
 			CountedListWriter::<VarUint32, _>(data.len(), data.into_iter().map(Into::into));
@@ -158,14 +364,40 @@ impl Serialize for ElementSegment {
 }
 
 /// Data segment definition.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct DataSegment {
 	index: u32,
 	offset: Option<InitExpr>,
 	value: Vec<u8>,
 
 	#[cfg(feature = "bulk")]
-	passive: bool,
+	mode: SegmentMode,
+	/// Flags the segment was read with, to write it back in the same encoding.
+	#[cfg(feature = "bulk")]
+	flags: u32,
+}
+
+impl PartialEq for DataSegment {
+	/// The encoding the segment was read with is left out.
+	fn eq(&self, other: &Self) -> bool {
+		#[cfg(feature = "bulk")]
+		if self.mode != other.mode {
+			return false
+		}
+		self.index == other.index && self.offset == other.offset && self.value == other.value
+	}
+}
+
+impl Eq for DataSegment {}
+
+impl Hash for DataSegment {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		#[cfg(feature = "bulk")]
+		self.mode.hash(state);
+		self.index.hash(state);
+		self.offset.hash(state);
+		self.value.hash(state);
+	}
 }
 
 impl DataSegment {
@@ -177,7 +409,9 @@ impl DataSegment {
 			value,
 
 			#[cfg(feature = "bulk")]
-			passive: false,
+			mode: SegmentMode::Active,
+			#[cfg(feature = "bulk")]
+			flags: 0,
 		}
 	}
 
@@ -213,19 +447,33 @@ impl DataSegment {
 
 #[cfg(feature = "bulk")]
 impl DataSegment {
-	/// Whether or not this data segment is "passive".
-	pub fn passive(&self) -> bool {
-		self.passive
+	/// How the segment is used.
+	pub fn mode(&self) -> SegmentMode {
+		self.mode
 	}
 
-	/// Whether or not this data segment is "passive" (mutable).
-	pub fn passive_mut(&mut self) -> &mut bool {
-		&mut self.passive
+	/// Set how the segment is used.
+	///
+	/// Only active segments have an offset, it is ignored for passive ones. Data segments
+	/// can't be declarative, and fail to serialize with [`Error::InvalidSegmentMode`].
+	pub fn set_mode(&mut self, mode: SegmentMode) {
+		self.mode = mode;
+	}
+
+	/// Whether or not this data segment is "passive".
+	pub fn passive(&self) -> bool {
+		self.mode == SegmentMode::Passive
 	}
 
 	/// Set whether or not this table segment is "passive"
 	pub fn set_passive(&mut self, passive: bool) {
-		self.passive = passive;
+		self.mode = if passive { SegmentMode::Passive } else { SegmentMode::Active };
+	}
+
+	/// Whether or not this data segment is "passive" (mutable).
+	#[deprecated(since = "0.45.2", note = "use `mode` and `set_mode`")]
+	pub fn passive_mut(&mut self) -> PassiveMut<'_> {
+		PassiveMut::new(&mut self.mode)
 	}
 }
 
@@ -245,19 +493,20 @@ impl Deserialize for DataSegment {
 	#[cfg(feature = "bulk")]
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
 		let flags: u32 = VarUint32::deserialize(reader)?.into();
-		let index = if flags == FLAG_MEMZERO || flags == FLAG_PASSIVE {
-			0u32
-		} else if flags == FLAG_MEM_NONZERO {
-			VarUint32::deserialize(reader)?.into()
+		let index = match flags {
+			0 | FLAG_PASSIVE => 0,
+			FLAG_EXPLICIT_INDEX => VarUint32::deserialize(reader)?.into(),
+			_ => return Err(Error::InvalidSegmentFlags(flags)),
+		};
+		let (mode, offset) = if flags == FLAG_PASSIVE {
+			(SegmentMode::Passive, None)
 		} else {
-			return Err(Error::InvalidSegmentFlags(flags))
+			(SegmentMode::Active, Some(InitExpr::deserialize(reader)?))
 		};
-		let offset =
-			if flags == FLAG_PASSIVE { None } else { Some(InitExpr::deserialize(reader)?) };
 		let value_len = u32::from(VarUint32::deserialize(reader)?) as usize;
 		let value = buffered_read!(VALUES_BUFFER_LENGTH, value_len, reader);
 
-		Ok(DataSegment { index, offset, value, passive: flags == FLAG_PASSIVE })
+		Ok(DataSegment { index, offset, value, mode, flags })
 	}
 }
 
@@ -266,21 +515,25 @@ impl Serialize for DataSegment {
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		#[cfg(feature = "bulk")]
-		{
-			if self.passive {
-				VarUint32::from(FLAG_PASSIVE).serialize(writer)?;
-			} else if self.index != 0 {
-				VarUint32::from(FLAG_MEM_NONZERO).serialize(writer)?;
+		match self.mode {
+			SegmentMode::Active if self.index == 0 && self.flags != FLAG_EXPLICIT_INDEX => {
+				VarUint32::from(0u32).serialize(writer)?;
+				self.offset.ok_or(Error::InvalidSegmentMode)?.serialize(writer)?;
+			},
+			SegmentMode::Active => {
+				VarUint32::from(FLAG_EXPLICIT_INDEX).serialize(writer)?;
 				VarUint32::from(self.index).serialize(writer)?;
-			} else {
-				VarUint32::from(FLAG_MEMZERO).serialize(writer)?;
-			}
+				self.offset.ok_or(Error::InvalidSegmentMode)?.serialize(writer)?;
+			},
+			SegmentMode::Passive => VarUint32::from(FLAG_PASSIVE).serialize(writer)?,
+			SegmentMode::Declarative => return Err(Error::InvalidSegmentMode),
 		}
 		#[cfg(not(feature = "bulk"))]
-		VarUint32::from(self.index).serialize(writer)?;
-
-		if let Some(offset) = self.offset {
-			offset.serialize(writer)?;
+		{
+			VarUint32::from(self.index).serialize(writer)?;
+			if let Some(offset) = self.offset {
+				offset.serialize(writer)?;
+			}
 		}
 
 		let value = self.value;
@@ -289,3 +542,128 @@ impl Serialize for DataSegment {
 		Ok(())
 	}
 }
+
+#[cfg(all(test, feature = "bulk"))]
+mod tests {
+	use super::{
+		super::{deserialize_buffer, serialize, Error, InitExpr, Instruction},
+		DataSegment, ElementSegment, SegmentMode,
+	};
+
+	fn offset() -> Option<InitExpr> {
+		Some(InitExpr::new(vec![Instruction::I32Const(1), Instruction::End]))
+	}
+
+	/// Encoding, then mode, table and members.
+	type ElementFixture = (&'static [u8], SegmentMode, u32, &'static [u32]);
+
+	/// Deserialize an element segment and serialize it back.
+	fn element(bytes: &[u8]) -> (ElementSegment, Vec<u8>) {
+		let segment: ElementSegment = deserialize_buffer(bytes).expect("segment to deserialize");
+		let reencoded = serialize(segment.clone()).expect("segment to serialize");
+		assert_eq!(deserialize_buffer::<ElementSegment>(&reencoded).unwrap(), segment);
+		(segment, reencoded)
+	}
+
+	#[test]
+	fn element_segment_flags() {
+		use self::SegmentMode::*;
+
+		let fixtures: &[ElementFixture] = &[
+			(&[0, 0x41, 1, 0x0b, 2, 3, 4], Active, 0, &[3, 4]),
+			(&[1, 0x00, 1, 5], Passive, 0, &[5]),
+			(&[2, 1, 0x41, 1, 0x0b, 0x00, 1, 6], Active, 1, &[6]),
+			(&[2, 0, 0x41, 1, 0x0b, 0x00, 1, 6], Active, 0, &[6]),
+			(&[3, 0x00, 1, 7], Declarative, 0, &[7]),
+			(&[4, 0x41, 1, 0x0b, 1, 0xd2, 3, 0x0b], Active, 0, &[3]),
+			(&[5, 0x70, 1, 0xd2, 5, 0x0b], Passive, 0, &[5]),
+			(&[6, 1, 0x41, 1, 0x0b, 0x70, 1, 0xd2, 6, 0x0b], Active, 1, &[6]),
+			(&[7, 0x70, 2, 0xd2, 7, 0x0b, 0xd2, 8, 0x0b], Declarative, 0, &[7, 8]),
+		];
+		for &(bytes, mode, index, members) in fixtures {
+			let (segment, reencoded) = element(bytes);
+			assert_eq!(segment.mode(), mode, "flags {}", bytes[0]);
+			assert_eq!(segment.index(), index, "flags {}", bytes[0]);
+			assert_eq!(segment.members(), members, "flags {}", bytes[0]);
+			let offset = if mode == Active { offset() } else { None };
+			assert_eq!(*segment.offset(), offset, "flags {}", bytes[0]);
+			assert_eq!(reencoded, bytes, "flags {}", bytes[0]);
+		}
+	}
+
+	#[test]
+	fn element_segment_encoding() {
+		// Segments are equal whatever their encoding, new ones use the shortest.
+		let (segment, _) = element(&[4, 0x41, 1, 0x0b, 1, 0xd2, 3, 0x0b]);
+		let new = ElementSegment::new(0, offset(), vec![3]);
+		assert_eq!(segment, new);
+		assert_eq!(serialize(new).unwrap(), [0, 0x41, 1, 0x0b, 1, 3]);
+
+		// The explicit table index is dropped along with the offset, expressions are kept.
+		let (mut segment, _) = element(&[6, 0, 0x41, 1, 0x0b, 0x70, 1, 0xd2, 6, 0x0b]);
+		segment.set_mode(SegmentMode::Passive);
+		assert_eq!(serialize(segment).unwrap(), [5, 0x70, 1, 0xd2, 6, 0x0b]);
+	}
+
+	#[test]
+	#[allow(deprecated)]
+	fn passive_mut() {
+		let mut segment = ElementSegment::new(0, offset(), vec![1]);
+		assert!(!*segment.passive_mut());
+		*segment.passive_mut() = true;
+		assert_eq!(segment.mode(), SegmentMode::Passive);
+
+		let mut segment = DataSegment::new(0, offset(), vec![1]);
+		*segment.passive_mut() = true;
+		assert!(segment.passive());
+		*segment.passive_mut() = false;
+		assert_eq!(segment.mode(), SegmentMode::Active);
+	}
+
+	#[test]
+	fn invalid_element_segments() {
+		let result = deserialize_buffer::<ElementSegment>(&[8, 0x00, 0]);
+		assert!(matches!(result, Err(Error::InvalidSegmentFlags(8))));
+		let result = deserialize_buffer::<ElementSegment>(&[1, 0x01, 0]);
+		assert!(matches!(result, Err(Error::UnknownTableElementType(1))));
+		// `ref.null` needs the reference types proposal.
+		match deserialize_buffer::<ElementSegment>(&[5, 0x70, 1, 0xd0, 0x70, 0x0b]) {
+			Err(Error::UnsupportedOpcode(opcode)) => {
+				assert_eq!(opcode.opcode, 0xd0);
+				assert_eq!(opcode.proposal().unwrap().name, "reference types");
+			},
+			result => panic!("unexpected result {:?}", result),
+		}
+
+		let segment = ElementSegment::new(0, None, vec![1]);
+		assert!(matches!(serialize(segment), Err(Error::InvalidSegmentMode)));
+		let mut segment = ElementSegment::new(0, offset(), vec![1]);
+		segment.set_mode(SegmentMode::Declarative);
+		assert_eq!(serialize(segment).unwrap(), [3, 0x00, 1, 1]);
+	}
+
+	#[test]
+	fn data_segment_flags() {
+		let fixtures: &[(&[u8], SegmentMode, u32)] = &[
+			(&[0, 0x41, 1, 0x0b, 1, 0xaa], SegmentMode::Active, 0),
+			(&[1, 1, 0xbb], SegmentMode::Passive, 0),
+			(&[2, 1, 0x41, 1, 0x0b, 1, 0xcc], SegmentMode::Active, 1),
+			(&[2, 0, 0x41, 1, 0x0b, 1, 0xdd], SegmentMode::Active, 0),
+		];
+		for &(bytes, mode, index) in fixtures {
+			let segment: DataSegment = deserialize_buffer(bytes).expect("segment to deserialize");
+			assert_eq!(segment.mode(), mode);
+			assert_eq!(segment.index(), index);
+			assert_eq!(segment.value().len(), 1);
+			assert_eq!(serialize(segment).unwrap(), bytes);
+		}
+
+		let result = deserialize_buffer::<DataSegment>(&[3, 0x00, 0]);
+		assert!(matches!(result, Err(Error::InvalidSegmentFlags(3))));
+		let mut segment = DataSegment::new(0, None, vec![1]);
+		segment.set_mode(SegmentMode::Declarative);
+		assert!(matches!(serialize(segment.clone()), Err(Error::InvalidSegmentMode)));
+		segment.set_passive(true);
+		assert_eq!(serialize(segment).unwrap(), [1, 1, 1]);
+	}
+}
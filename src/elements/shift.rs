@@ -0,0 +1,237 @@
+use super::{Error, IndexMap, Instruction, Internal, Module, Section};
+use core::mem;
+
+/// Shift the references to the functions from `inserted_at` on by `count`, after functions
+/// were inserted in the function index space, or removed from it if `count` is negative.
+///
+/// Calls, exports, element segments, the start function and the function, local and label
+/// names of a parsed name section are updated. Names of removed functions are dropped, while
+/// any other reference to a removed function fails with [`Error::RemovedFunctionReference`] and
+/// leaves the module unchanged. Lazily deserialized bodies are decoded first.
+///
+/// Imported functions come first in the index space, so adding a function import shifts all
+/// the functions defined in the module:
+///
+/// ```
+/// use parity_wasm::{builder, elements};
+///
+/// let mut module = builder::module()
+///     .function().signature().build().body().build().build()
+///     .export().field("main").internal().func(0).build()
+///     .build();
+/// let entry = elements::ImportEntry::new(
+///     "env".into(),
+///     "log".into(),
+///     elements::External::Function(0.into()),
+/// );
+/// module.insert_section(elements::Section::Import(elements::ImportSection::with_entries(
+///     vec![entry],
+/// ))).unwrap();
+/// elements::shift_function_indices(&mut module, 0, 1).unwrap();
+/// assert_eq!(
+///     *module.export_section().unwrap().entries()[0].internal(),
+///     elements::Internal::Function(1.into()),
+/// );
+/// ```
+pub fn shift_function_indices(
+	module: &mut Module,
+	inserted_at: u32,
+	count: i32,
+) -> Result<(), Error> {
+	for section in module.sections_mut() {
+		if let Section::Code(ref mut code) = *section {
+			for body in code.bodies_mut() {
+				body.decode()?;
+			}
+		}
+	}
+
+	let shift = |index: u32| -> Result<Option<u32>, Error> {
+		if index < inserted_at {
+			return Ok(Some(index))
+		}
+		if count < 0 && index - inserted_at < count.unsigned_abs() {
+			return Ok(None)
+		}
+		let shifted = i64::from(index) + i64::from(count);
+		u32::try_from(shifted).map(Some).map_err(|_| Error::TooManyFunctions)
+	};
+
+	// Check every reference before changing any.
+	let mut result = Ok(());
+	visit_references(module, &mut |index| {
+		if result.is_ok() {
+			result = match shift(*index) {
+				Ok(Some(_)) => Ok(()),
+				Ok(None) => Err(Error::RemovedFunctionReference(*index)),
+				Err(err) => Err(err),
+			};
+		}
+	});
+	result?;
+	visit_references(module, &mut |index| {
+		*index = shift(*index).ok().flatten().expect("references were checked");
+	});
+
+	for section in module.sections_mut() {
+		if let Section::Name(ref mut names) = *section {
+			if let Some(functions) = names.functions_mut() {
+				shift_keys(functions.names_mut(), shift);
+			}
+			if let Some(locals) = names.locals_mut() {
+				shift_keys(locals.local_names_mut(), shift);
+			}
+			if let Some(labels) = names.labels_mut() {
+				shift_keys(labels, shift);
+			}
+		}
+	}
+	Ok(())
+}
+
+fn visit_references(module: &mut Module, visit: &mut dyn FnMut(&mut u32)) {
+	for section in module.sections_mut() {
+		match *section {
+			Section::Code(ref mut code) =>
+				for body in code.bodies_mut() {
+					for instruction in body.code_mut().elements_mut() {
+						if let Instruction::Call(ref mut index) = *instruction {
+							visit(&mut index.0);
+						}
+					}
+				},
+			Section::Export(ref mut exports) =>
+				for entry in exports.entries_mut() {
+					if let Internal::Function(ref mut index) = *entry.internal_mut() {
+						visit(&mut index.0);
+					}
+				},
+			Section::Element(ref mut elements) =>
+				for segment in elements.entries_mut() {
+					segment.members_mut().iter_mut().for_each(&mut *visit);
+				},
+			Section::Start(ref mut index) => visit(index),
+			_ => {},
+		}
+	}
+}
+
+fn shift_keys<T, F>(map: &mut IndexMap<T>, shift: F)
+where
+	F: Fn(u32) -> Result<Option<u32>, Error>,
+{
+	*map = mem::replace(map, IndexMap::with_capacity(0))
+		.into_iter()
+		.filter_map(|(index, value)| Some((shift(index).ok()??, value)))
+		.collect();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{
+			serialize, BlockType, Error, External, FunctionNameSubsection, ImportEntry,
+			Instruction, Internal, LocalNameSubsection, Module, NameMap, NameSection, Section,
+		},
+		shift_function_indices,
+	};
+	use crate::builder;
+
+	/// Imported function 0, function 1 calling both functions from a block and function 2
+	/// exported, in the table and started.
+	fn module() -> Module {
+		let mut module = builder::module()
+			.import()
+			.path("env", "f")
+			.external()
+			.func(0)
+			.build()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.code()
+			.block(BlockType::NoResult, |code| code.call(0).call(2))
+			.end()
+			.build()
+			.build()
+			.build()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.build()
+			.build()
+			.export()
+			.field("main")
+			.internal()
+			.func(2)
+			.build()
+			.table()
+			.with_min(2)
+			.with_element(0, vec![1, 2])
+			.build()
+			.build();
+		module.set_start_section(2);
+
+		let mut functions = FunctionNameSubsection::default();
+		for (index, name) in ["f", "a", "b"].iter().enumerate() {
+			functions.names_mut().insert(index as u32, name.to_string());
+		}
+		let mut locals = LocalNameSubsection::default();
+		let mut names = NameMap::default();
+		names.insert(0, "x".into());
+		locals.local_names_mut().insert(2, names);
+		module
+			.insert_section(Section::Name(NameSection::new(None, Some(functions), Some(locals))))
+			.unwrap();
+		module
+	}
+
+	#[test]
+	fn insert_and_remove_import() {
+		let mut module = module();
+		let original = serialize(module.clone()).unwrap();
+
+		let import = ImportEntry::new("env".into(), "g".into(), External::Function(0.into()));
+		module.import_section_mut().unwrap().entries_mut().push(import);
+		shift_function_indices(&mut module, 1, 1).unwrap();
+
+		let body = &module.code_section().unwrap().bodies()[0];
+		assert_eq!(
+			body.code().elements()[1..3],
+			[Instruction::Call(0.into()), Instruction::Call(3.into())]
+		);
+		assert_eq!(
+			*module.export_section().unwrap().entries()[0].internal(),
+			Internal::Function(3.into())
+		);
+		assert_eq!(module.elements_section().unwrap().entries()[0].members(), [2, 3]);
+		assert_eq!(module.start_section(), Some(3));
+		let names = module.names_section().unwrap();
+		let functions = names.functions().unwrap().names();
+		assert_eq!(functions.get(0).map(String::as_str), Some("f"));
+		assert_eq!(functions.get(1), None);
+		assert_eq!(functions.get(3).map(String::as_str), Some("b"));
+		assert!(names.locals().unwrap().local_names().get(3).is_some());
+
+		module.import_section_mut().unwrap().entries_mut().pop();
+		shift_function_indices(&mut module, 1, -1).unwrap();
+		assert_eq!(serialize(module).unwrap(), original);
+	}
+
+	#[test]
+	fn removed_function_reference() {
+		let mut module = module();
+		let original = module.clone();
+		assert!(matches!(
+			shift_function_indices(&mut module, 0, -1),
+			Err(Error::RemovedFunctionReference(0))
+		));
+		assert!(matches!(
+			shift_function_indices(&mut module, 1, -2),
+			Err(Error::RemovedFunctionReference(2))
+		));
+		assert_eq!(module, original);
+	}
+}
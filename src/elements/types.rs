@@ -1,5 +1,6 @@
 use super::{
-	CountedList, CountedListWriter, Deserialize, Error, Serialize, VarInt32, VarInt7, VarUint7,
+	CountedList, CountedListWriter, Deserialize, Error, Serialize, Uint8, VarInt64, VarInt7,
+	VarUint7,
 };
 use crate::io;
 use alloc::vec::Vec;
@@ -110,24 +111,41 @@ pub enum BlockType {
 impl Deserialize for BlockType {
 	type Error = Error;
 
+	/// Block types are encoded as a signed 33-bit LEB128 number. The empty and value types
+	/// are single negative bytes, longer encodings of them are rejected, while type indices
+	/// are non-negative.
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
-		let val = VarInt32::deserialize(reader)?;
+		let mut val = 0i64;
+		let mut shift = 0;
+		loop {
+			let byte: u8 = Uint8::deserialize(reader)?.into();
+			val |= i64::from(byte & 0x7f) << shift;
+			shift += 7;
+			if byte & 0x80 == 0 {
+				if byte & 0x40 != 0 {
+					val |= -1i64 << shift;
+				}
+				break
+			}
+			if shift >= 35 {
+				return Err(Error::InvalidVarInt32)
+			}
+		}
+		if !(-(1 << 32)..1 << 32).contains(&val) {
+			return Err(Error::InvalidVarInt32)
+		}
 
-		match val.into() {
-			-0x40 => Ok(BlockType::NoResult),
-			-0x01 => Ok(BlockType::Value(ValueType::I32)),
-			-0x02 => Ok(BlockType::Value(ValueType::I64)),
-			-0x03 => Ok(BlockType::Value(ValueType::F32)),
-			-0x04 => Ok(BlockType::Value(ValueType::F64)),
+		match val {
+			-0x40 if shift == 7 => Ok(BlockType::NoResult),
+			-0x01 if shift == 7 => Ok(BlockType::Value(ValueType::I32)),
+			-0x02 if shift == 7 => Ok(BlockType::Value(ValueType::I64)),
+			-0x03 if shift == 7 => Ok(BlockType::Value(ValueType::F32)),
+			-0x04 if shift == 7 => Ok(BlockType::Value(ValueType::F64)),
 			#[cfg(feature = "simd")]
-			-0x05 => Ok(BlockType::Value(ValueType::V128)),
+			-0x05 if shift == 7 => Ok(BlockType::Value(ValueType::V128)),
 			#[cfg(feature = "multi_value")]
-			idx => {
-				let idx = idx.try_into().map_err(|_| Error::UnknownBlockType(idx))?;
-				Ok(BlockType::TypeIndex(idx))
-			},
-			#[cfg(not(feature = "multi_value"))]
-			_ => Err(Error::UnknownBlockType(val.into())),
+			idx if idx >= 0 => Ok(BlockType::TypeIndex(idx as u32)),
+			_ => Err(Error::UnknownBlockType(val as i32)),
 		}
 	}
 }
@@ -136,7 +154,7 @@ impl Serialize for BlockType {
 	type Error = Error;
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
-		let val: VarInt32 = match self {
+		let val: VarInt64 = match self {
 			BlockType::NoResult => -0x40,
 			BlockType::Value(ValueType::I32) => -0x01,
 			BlockType::Value(ValueType::I64) => -0x02,
@@ -145,7 +163,7 @@ impl Serialize for BlockType {
 			#[cfg(feature = "simd")]
 			BlockType::Value(ValueType::V128) => -0x05,
 			#[cfg(feature = "multi_value")]
-			BlockType::TypeIndex(idx) => idx as i32,
+			BlockType::TypeIndex(idx) => i64::from(idx),
 		}
 		.into();
 		val.serialize(writer)?;
@@ -192,6 +210,72 @@ impl FunctionType {
 	pub fn results_mut(&mut self) -> &mut Vec<ValueType> {
 		&mut self.results
 	}
+	/// Builder of a function type, adding params and results one by one.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use parity_wasm::elements::{FunctionType, ValueType::*};
+	///
+	/// let func_type = FunctionType::builder().param(I32).param(I64).result(I32).build();
+	/// assert_eq!(func_type, FunctionType::new(vec![I32, I64], vec![I32]));
+	/// assert_eq!(func_type.to_string(), "(i32, i64) -> i32");
+	/// ```
+	pub fn builder() -> FunctionTypeBuilder {
+		FunctionTypeBuilder(FunctionType::default())
+	}
+}
+
+impl<'a> From<(&'a [ValueType], &'a [ValueType])> for FunctionType {
+	fn from((params, results): (&'a [ValueType], &'a [ValueType])) -> Self {
+		FunctionType::new(params.to_vec(), results.to_vec())
+	}
+}
+
+/// Formats as `(params) -> result`, results are parenthesized unless there's exactly one.
+impl fmt::Display for FunctionType {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fn list(f: &mut fmt::Formatter, types: &[ValueType]) -> fmt::Result {
+			write!(f, "(")?;
+			for (index, value_type) in types.iter().enumerate() {
+				if index > 0 {
+					write!(f, ", ")?;
+				}
+				write!(f, "{}", value_type)?;
+			}
+			write!(f, ")")
+		}
+
+		list(f, &self.params)?;
+		write!(f, " -> ")?;
+		match self.results[..] {
+			[result] => write!(f, "{}", result),
+			ref results => list(f, results),
+		}
+	}
+}
+
+/// Builder of a [`FunctionType`], see [`FunctionType::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct FunctionTypeBuilder(FunctionType);
+
+impl FunctionTypeBuilder {
+	/// Add a parameter.
+	pub fn param(mut self, value_type: ValueType) -> Self {
+		self.0.params.push(value_type);
+		self
+	}
+
+	/// Add a result.
+	pub fn result(mut self, value_type: ValueType) -> Self {
+		self.0.results.push(value_type);
+		self
+	}
+
+	/// Finish the function type.
+	pub fn build(self) -> FunctionType {
+		self.0
+	}
 }
 
 impl Deserialize for FunctionType {
@@ -224,16 +308,12 @@ impl Serialize for FunctionType {
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		VarUint7::from(self.form).serialize(writer)?;
 
-		let params_counted_list = CountedListWriter::<ValueType, _>(
-			self.params.len(),
-			self.params.into_iter().map(Into::into),
-		);
+		let params_counted_list =
+			CountedListWriter::<ValueType, _>(self.params.len(), self.params.into_iter());
 		params_counted_list.serialize(writer)?;
 
-		let results_counted_list = CountedListWriter::<ValueType, _>(
-			self.results.len(),
-			self.results.into_iter().map(Into::into),
-		);
+		let results_counted_list =
+			CountedListWriter::<ValueType, _>(self.results.len(), self.results.into_iter());
 		results_counted_list.serialize(writer)?;
 
 		Ok(())
@@ -241,7 +321,7 @@ impl Serialize for FunctionType {
 }
 
 /// Table element type.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum TableElementType {
 	/// A reference to a function with any signature.
 	AnyFunc,
@@ -272,3 +352,97 @@ impl Serialize for TableElementType {
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{deserialize_buffer, serialize, Error},
+		BlockType, FunctionType,
+		ValueType::*,
+	};
+	use core::hash::{Hash, Hasher};
+	use std::collections::hash_map::DefaultHasher;
+
+	fn hash(func_type: &FunctionType) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		func_type.hash(&mut hasher);
+		hasher.finish()
+	}
+
+	#[test]
+	fn function_type_builder() {
+		let built = FunctionType::builder().param(F32).result(I64).result(F64).build();
+		let manual = FunctionType::new(vec![F32], vec![I64, F64]);
+		let converted = FunctionType::from((&[F32][..], &[I64, F64][..]));
+		assert_eq!(built, manual);
+		assert_eq!(converted, manual);
+		assert_eq!(hash(&built), hash(&converted));
+		assert_eq!(FunctionType::builder().build(), FunctionType::default());
+	}
+
+	#[test]
+	fn function_type_display() {
+		assert_eq!(FunctionType::default().to_string(), "() -> ()");
+		assert_eq!(FunctionType::new(vec![I32, I64], vec![I32]).to_string(), "(i32, i64) -> i32");
+		assert_eq!(FunctionType::new(vec![F32], vec![F64, I32]).to_string(), "(f32) -> (f64, i32)");
+	}
+
+	#[test]
+	fn block_types() {
+		let block_types = [
+			(BlockType::NoResult, 0x40),
+			(BlockType::Value(I32), 0x7f),
+			(BlockType::Value(I64), 0x7e),
+			(BlockType::Value(F32), 0x7d),
+			(BlockType::Value(F64), 0x7c),
+			#[cfg(feature = "simd")]
+			(BlockType::Value(V128), 0x7b),
+		];
+		for (block_type, byte) in block_types {
+			assert_eq!(serialize(block_type).expect("block type to encode"), [byte]);
+			assert_eq!(deserialize_buffer::<BlockType>(&[byte]).unwrap(), block_type);
+		}
+
+		// Overlong encodings of the single byte forms.
+		assert!(matches!(
+			deserialize_buffer::<BlockType>(&[0xc0, 0x7f]),
+			Err(Error::UnknownBlockType(-0x40))
+		));
+		assert!(matches!(
+			deserialize_buffer::<BlockType>(&[0xff, 0x7f]),
+			Err(Error::UnknownBlockType(-0x01))
+		));
+		assert!(matches!(
+			deserialize_buffer::<BlockType>(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00]),
+			Err(Error::InvalidVarInt32)
+		));
+	}
+
+	#[cfg(feature = "multi_value")]
+	#[test]
+	fn type_index_block_type() {
+		let encoded = serialize(BlockType::TypeIndex(200)).expect("block type to encode");
+		assert_eq!(encoded, [0xc8, 0x01]);
+		assert_eq!(deserialize_buffer::<BlockType>(&encoded).unwrap(), BlockType::TypeIndex(200));
+
+		let encoded = serialize(BlockType::TypeIndex(u32::MAX)).expect("block type to encode");
+		assert_eq!(encoded, [0xff, 0xff, 0xff, 0xff, 0x0f]);
+		assert_eq!(
+			deserialize_buffer::<BlockType>(&encoded).unwrap(),
+			BlockType::TypeIndex(u32::MAX)
+		);
+		assert!(matches!(
+			deserialize_buffer::<BlockType>(&[0x80, 0x80, 0x80, 0x80, 0x10]),
+			Err(Error::InvalidVarInt32)
+		));
+	}
+
+	#[cfg(not(feature = "multi_value"))]
+	#[test]
+	fn type_index_block_type() {
+		assert!(matches!(
+			deserialize_buffer::<BlockType>(&[0xc8, 0x01]),
+			Err(Error::UnknownBlockType(200))
+		));
+	}
+}
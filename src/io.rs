@@ -18,6 +18,7 @@ pub enum Error {
 	/// Invalid data is encountered.
 	InvalidData,
 
+	/// Error of the underlying `std` reader or writer.
 	#[cfg(feature = "std")]
 	Io(std::io::Error),
 }
@@ -25,6 +26,7 @@ pub enum Error {
 /// IO specific Result.
 pub type Result<T> = core::result::Result<T, Error>;
 
+/// Sink of bytes, implemented for `std::io::Write` types with the `std` feature.
 pub trait Write {
 	/// Write a buffer of data into this write.
 	///
@@ -32,6 +34,7 @@ pub trait Write {
 	fn write(&mut self, buf: &[u8]) -> Result<()>;
 }
 
+/// Source of bytes, implemented for `std::io::Read` types with the `std` feature.
 pub trait Read {
 	/// Read a data from this read to a buffer.
 	///
@@ -46,13 +49,20 @@ pub struct Cursor<T> {
 }
 
 impl<T> Cursor<T> {
+	/// New cursor at the start of `inner`.
 	pub fn new(inner: T) -> Cursor<T> {
 		Cursor { inner, pos: 0 }
 	}
 
+	/// Number of bytes read so far.
 	pub fn position(&self) -> usize {
 		self.pos
 	}
+
+	/// Consume the cursor, returning the underlying data.
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
 }
 
 impl<T: AsRef<[u8]>> Read for Cursor<T> {
@@ -7,7 +7,10 @@ extern crate alloc;
 
 pub mod builder;
 pub mod elements;
-mod io;
+pub mod io;
+pub mod lint;
+pub mod optimize;
+pub mod transform;
 
 pub use elements::{deserialize_buffer, peek_size, serialize, Error as SerializationError};
 
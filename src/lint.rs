@@ -0,0 +1,440 @@
+//! Checks of a module against a policy, reported as warnings rather than errors.
+
+use crate::elements::{
+	External, FuncIdx, Instruction, Internal, Module, NameMap, NameSection, OpcodeId, Section,
+	SectionKey, TableIdx, Type, ValueType,
+};
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::{fmt, ops::RangeInclusive};
+
+/// What [`check`] warns about. Every rule is disabled by default.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LintConfig {
+	/// Largest allowed size of the code of a function body, in bytes.
+	pub max_function_size: Option<usize>,
+	/// Warn about `memory.grow`.
+	pub deny_grow_memory: bool,
+	/// Warn about functions using floating point values.
+	pub deny_floats: bool,
+	/// Warn about a start function.
+	pub deny_start: bool,
+	/// Module names imports may come from.
+	pub allowed_import_modules: Option<Vec<String>>,
+	/// Names the module may export.
+	pub expected_exports: Option<Vec<String>>,
+	/// Warn about custom sections, parsed ones included.
+	pub deny_custom_sections: bool,
+	/// Largest allowed initial size of a table, imported or not.
+	pub max_table_size: Option<u32>,
+}
+
+/// Rule a [`LintWarning`] is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LintRule {
+	/// Function body larger than [`LintConfig::max_function_size`].
+	FunctionSize,
+	/// Use of `memory.grow`.
+	GrowMemory,
+	/// Floating point values in a function.
+	Floats,
+	/// Start function.
+	Start,
+	/// Import from a module not in [`LintConfig::allowed_import_modules`].
+	ImportModule,
+	/// Export not in [`LintConfig::expected_exports`].
+	UnexpectedExport,
+	/// Custom section.
+	CustomSection,
+	/// Table larger than [`LintConfig::max_table_size`].
+	TableSize,
+}
+
+impl LintRule {
+	/// Identifier of the rule.
+	pub fn id(&self) -> &'static str {
+		match *self {
+			LintRule::FunctionSize => "function-size",
+			LintRule::GrowMemory => "grow-memory",
+			LintRule::Floats => "floats",
+			LintRule::Start => "start",
+			LintRule::ImportModule => "import-module",
+			LintRule::UnexpectedExport => "unexpected-export",
+			LintRule::CustomSection => "custom-section",
+			LintRule::TableSize => "table-size",
+		}
+	}
+}
+
+/// Part of the module a [`LintWarning`] is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LintLocation {
+	/// Section at the given position in [`Module::sections`].
+	Section(usize),
+	/// Function, by index in the function index space.
+	Function(FuncIdx),
+	/// Entry of the import section.
+	Import(usize),
+	/// Entry of the export section.
+	Export(usize),
+	/// Table, by index in the table index space.
+	Table(TableIdx),
+}
+
+/// Violation of a rule of the [`LintConfig`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LintWarning {
+	/// Rule which is violated.
+	pub rule: LintRule,
+	/// Where the rule is violated.
+	pub location: LintLocation,
+	/// Description of the violation.
+	pub message: String,
+}
+
+impl fmt::Display for LintWarning {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}: {}", self.rule.id(), self.message)
+	}
+}
+
+/// Opcodes of the instructions operating on floating point values, SIMD ones excluded.
+const FLOAT_OPCODES: &[(Option<u8>, RangeInclusive<u32>)] = &[
+	// Loads, stores and constants.
+	(None, 0x2a..=0x2b),
+	(None, 0x38..=0x39),
+	(None, 0x43..=0x44),
+	// Comparisons.
+	(None, 0x5b..=0x66),
+	// Arithmetic.
+	(None, 0x8b..=0xa6),
+	// Conversions from floats to integers, skipping the integer extensions.
+	(None, 0xa8..=0xab),
+	(None, 0xae..=0xbf),
+	// Saturating conversions.
+	(Some(0xfc), 0x00..=0x07),
+];
+
+fn is_float(instruction: &Instruction) -> bool {
+	let (prefix, opcode) = match instruction.code() {
+		OpcodeId::Single(opcode) => (None, u32::from(opcode)),
+		OpcodeId::Prefixed(prefix, opcode) => (Some(prefix), opcode),
+	};
+	FLOAT_OPCODES
+		.iter()
+		.any(|(float_prefix, opcodes)| *float_prefix == prefix && opcodes.contains(&opcode))
+}
+
+fn is_float_type(value_type: &ValueType) -> bool {
+	matches!(value_type, ValueType::F32 | ValueType::F64)
+}
+
+/// Kind and index of an entity, followed by its name in the name section if it has one.
+fn describe(kind: &str, index: u32, names: Option<&NameMap>) -> String {
+	match names.and_then(|names| names.get(index)) {
+		Some(name) => format!("{} {} `{}`", kind, index, name),
+		None => format!("{} {}", kind, index),
+	}
+}
+
+/// Check the module against the rules of `config`.
+///
+/// Warnings about functions come first, in index order, followed by the ones about sections,
+/// imports, exports and tables. Function sizes are the sizes of the encoded instructions, locals
+/// excluded, and functions which can't be encoded are left out of the size rule. Lazily deserialized bodies are decoded on the side for the instruction rules, and
+/// skipped by them if they can't be decoded. Messages include the names of functions, tables
+/// and memories if the name section was parsed, see [`Module::parse_names`].
+pub fn check(module: &Module, config: &LintConfig) -> Vec<LintWarning> {
+	let mut warnings = Vec::new();
+	let mut warn = |rule, location, message| {
+		warnings.push(LintWarning { rule, location, message });
+	};
+
+	let names = module.names_section();
+	let function_names = names.and_then(NameSection::functions).map(|names| names.names());
+	let imported_functions = module.import_section().map_or(0, |section| section.functions());
+	let bodies = module.code_section().map_or(&[][..], |section| section.bodies());
+	let functions = module.function_section().map_or(&[][..], |section| section.entries());
+	for (defined, body) in bodies.iter().enumerate() {
+		let index = FuncIdx((imported_functions + defined) as u32);
+		let location = LintLocation::Function(index);
+		let function = describe("function", index.0, function_names);
+		let size = match body.raw_code() {
+			Some(raw) => Ok(raw.len()),
+			None => body.code().serialized_size(),
+		};
+		if let (Some(max), Ok(size)) = (config.max_function_size, size) {
+			if size > max {
+				let message = format!("{} is {} bytes long, over {}", function, size, max);
+				warn(LintRule::FunctionSize, location, message);
+			}
+		}
+
+		if !config.deny_grow_memory && !config.deny_floats {
+			continue
+		}
+		let instructions = match body.try_code() {
+			Ok(code) => code.elements(),
+			Err(_) => continue,
+		};
+		let grown = instructions.iter().find_map(|instruction| match *instruction {
+			Instruction::GrowMemory(memory) => Some(u32::from(memory)),
+			_ => None,
+		});
+		if let (true, Some(memory)) = (config.deny_grow_memory, grown) {
+			let memory_names = names.and_then(NameSection::memories);
+			let memory = match memory_names.and_then(|names| names.get(memory)) {
+				Some(name) => format!("memory {} `{}`", memory, name),
+				None => "the memory".to_owned(),
+			};
+			let message = format!("{} grows {}", function, memory);
+			warn(LintRule::GrowMemory, location, message);
+		}
+		if config.deny_floats {
+			let signature = functions
+				.get(defined)
+				.and_then(|func| module.type_section()?.types().get(func.type_ref() as usize));
+			let float_signature = signature.map_or(false, |Type::Function(signature)| {
+				signature.params().iter().chain(signature.results()).any(is_float_type)
+			});
+			if float_signature ||
+				body.locals().iter().any(|local| is_float_type(&local.value_type())) ||
+				instructions.iter().any(is_float)
+			{
+				let message = format!("{} uses floating point values", function);
+				warn(LintRule::Floats, location, message);
+			}
+		}
+	}
+
+	for (position, section) in module.sections().iter().enumerate() {
+		let location = LintLocation::Section(position);
+		match (section, section.key()) {
+			(Section::Start(index), _) if config.deny_start => {
+				let function = describe("function", *index, function_names);
+				let message = format!("{} is called on instantiation", function);
+				warn(LintRule::Start, location, message);
+			},
+			(_, SectionKey::Custom(name)) if config.deny_custom_sections => {
+				let message = format!("custom section `{}`", name);
+				warn(LintRule::CustomSection, location, message);
+			},
+			_ => {},
+		}
+	}
+
+	let imports = module.import_section().map_or(&[][..], |section| section.entries());
+	if let Some(ref allowed) = config.allowed_import_modules {
+		for (index, entry) in imports.iter().enumerate() {
+			if !allowed.iter().any(|module| module == entry.module()) {
+				let message =
+					format!("`{}` is imported from module `{}`", entry.field(), entry.module());
+				warn(LintRule::ImportModule, LintLocation::Import(index), message);
+			}
+		}
+	}
+
+	if let Some(ref expected) = config.expected_exports {
+		let exports = module.export_section().map_or(&[][..], |section| section.entries());
+		for (index, entry) in exports.iter().enumerate() {
+			if !expected.iter().any(|name| name == entry.field()) {
+				let kind = match *entry.internal() {
+					Internal::Function(_) => "function",
+					Internal::Table(_) => "table",
+					Internal::Memory(_) => "memory",
+					Internal::Global(_) => "global",
+					#[cfg(feature = "exceptions")]
+					Internal::Tag(_) => "tag",
+				};
+				let message = format!("unexpected {} export `{}`", kind, entry.field());
+				warn(LintRule::UnexpectedExport, LintLocation::Export(index), message);
+			}
+		}
+	}
+
+	if let Some(max) = config.max_table_size {
+		let imported = imports.iter().filter_map(|entry| match *entry.external() {
+			External::Table(ref table) => Some(table),
+			_ => None,
+		});
+		let defined = module.table_section().map_or(&[][..], |section| section.entries());
+		let table_names = names.and_then(NameSection::tables);
+		for (index, table) in imported.chain(defined).enumerate() {
+			let size = table.limits().initial();
+			if size > max {
+				let table = describe("table", index as u32, table_names);
+				let message = format!("{} has {} elements, over {}", table, size, max);
+				warn(LintRule::TableSize, LintLocation::Table(TableIdx(index as u32)), message);
+			}
+		}
+	}
+
+	warnings
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{check, LintConfig, LintLocation, LintRule};
+	use crate::{
+		builder,
+		elements::{
+			FuncIdx, FunctionNameSubsection, Instruction, Module, NameMap, NameSection, Section,
+			SectionKey, TableIdx, ValueType,
+		},
+	};
+
+	/// Imports from `env` and `wasi_snapshot_preview1`, a function of 6 bytes of code with a float
+	/// local and `memory.grow`, then a function of 3 bytes, exported and started.
+	fn module() -> Module {
+		let mut module = builder::module()
+			.import()
+			.path("env", "f")
+			.external()
+			.func(0)
+			.build()
+			.import()
+			.path("wasi_snapshot_preview1", "fd_write")
+			.external()
+			.func(0)
+			.build()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.with_local(1, ValueType::F64)
+			.code()
+			.with_instruction(Instruction::I32Const(1))
+			.with_instruction(Instruction::GrowMemory(0))
+			.drop()
+			.end()
+			.build()
+			.build()
+			.build()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.code()
+			.nop()
+			.nop()
+			.end()
+			.build()
+			.build()
+			.build()
+			.export()
+			.field("main")
+			.internal()
+			.func(3)
+			.build()
+			.table()
+			.with_min(4)
+			.build()
+			.build();
+		module.set_start_section(3);
+		module.set_custom_section("meta", vec![]);
+		module
+	}
+
+	fn rules(module: &Module, config: &LintConfig) -> Vec<(LintRule, LintLocation)> {
+		check(module, config).into_iter().map(|w| (w.rule, w.location)).collect()
+	}
+
+	#[test]
+	fn default_config() {
+		assert!(check(&module(), &LintConfig::default()).is_empty());
+	}
+
+	#[test]
+	fn import_modules() {
+		let config =
+			LintConfig { allowed_import_modules: Some(vec!["env".into()]), ..Default::default() };
+		let warnings = check(&module(), &config);
+		assert_eq!(warnings.len(), 1);
+		assert_eq!(warnings[0].location, LintLocation::Import(1));
+		assert_eq!(
+			warnings[0].to_string(),
+			"import-module: `fd_write` is imported from module `wasi_snapshot_preview1`"
+		);
+	}
+
+	#[test]
+	fn thresholds() {
+		let module = module();
+		let config = |function, table| LintConfig {
+			max_function_size: Some(function),
+			max_table_size: Some(table),
+			..Default::default()
+		};
+		assert!(rules(&module, &config(6, 4)).is_empty());
+		assert_eq!(
+			rules(&module, &config(5, 3)),
+			[
+				(LintRule::FunctionSize, LintLocation::Function(FuncIdx(2))),
+				(LintRule::TableSize, LintLocation::Table(TableIdx(0))),
+			]
+		);
+		assert_eq!(rules(&module, &config(2, 4)).len(), 2);
+	}
+
+	#[test]
+	fn other_rules() {
+		let config = LintConfig {
+			deny_grow_memory: true,
+			deny_floats: true,
+			deny_start: true,
+			expected_exports: Some(vec![]),
+			deny_custom_sections: true,
+			..Default::default()
+		};
+		let module = module();
+		let position = |key| module.sections().iter().position(|s| s.key() == key).unwrap();
+		let start = position(SectionKey::Id(0x08));
+		let custom = position(SectionKey::Custom("meta"));
+		assert_eq!(
+			rules(&module, &config),
+			[
+				(LintRule::GrowMemory, LintLocation::Function(FuncIdx(2))),
+				(LintRule::Floats, LintLocation::Function(FuncIdx(2))),
+				(LintRule::Start, LintLocation::Section(start)),
+				(LintRule::CustomSection, LintLocation::Section(custom)),
+				(LintRule::UnexpectedExport, LintLocation::Export(0)),
+			]
+		);
+
+		let bytes = module.clone().into_bytes().unwrap();
+		let lazy = Module::deserialize_lazy(&bytes).unwrap();
+		assert_eq!(rules(&lazy, &config), rules(&module, &config));
+	}
+
+	#[test]
+	fn names() {
+		let mut module = module();
+		let map = |names: &[(u32, &str)]| {
+			names
+				.iter()
+				.map(|&(index, name)| (index, name.to_string()))
+				.collect::<NameMap>()
+		};
+		let mut functions = FunctionNameSubsection::default();
+		*functions.names_mut() = map(&[(2, "grow"), (3, "main")]);
+		let mut names = NameSection::new(None, Some(functions), None);
+		*names.tables_mut() = Some(map(&[(0, "functions")]));
+		*names.memories_mut() = Some(map(&[(0, "heap")]));
+		module.insert_section(Section::Name(names)).unwrap();
+
+		let config = LintConfig {
+			deny_grow_memory: true,
+			deny_start: true,
+			max_table_size: Some(3),
+			..Default::default()
+		};
+		let warnings: Vec<_> = check(&module, &config).iter().map(|w| w.to_string()).collect();
+		assert_eq!(
+			warnings,
+			[
+				"grow-memory: function 2 `grow` grows memory 0 `heap`",
+				"start: function 3 `main` is called on instantiation",
+				"table-size: table 0 `functions` has 4 elements, over 3",
+			]
+		);
+	}
+}
@@ -0,0 +1,488 @@
+//! Simple optimizations of function bodies.
+
+use crate::{
+	elements::{
+		BlockType, ConstValue, Cursor, Error, FuncBody, FunctionType, ImportCountType, Instruction,
+		Module, Type,
+	},
+	transform::rewrite_with,
+};
+use alloc::vec::Vec;
+
+/// Peephole optimization of the function body.
+///
+/// Removes `nop`s, constants and `get_local`s which are immediately dropped, and folds
+/// `add`, `sub`, `mul`, `and`, `or` and `xor` of two integer constants into a single
+/// constant (with wrapping semantics). Only adjacent instructions are combined, so
+/// nothing is ever moved across block boundaries.
+///
+/// Returns the number of removed instructions. A body deserialized lazily is decoded first.
+///
+/// # Panics
+///
+/// Panics if the body was deserialized lazily and its instructions are malformed, see
+/// [`FuncBody::decode`].
+pub fn peephole(body: &mut FuncBody) -> usize {
+	const BALANCED: &str = "combined instructions don't open or close blocks";
+
+	let code = body.code_mut().elements_mut();
+	let original_len = code.len();
+
+	let mut cursor = Cursor::new(code);
+	while let Some(instruction) = cursor.current() {
+		let (count, folded) = match (instruction, cursor.peek(1), cursor.peek(2)) {
+			(Instruction::Nop, _, _) => (1, None),
+			(push, Some(Instruction::Drop), _) if is_pure_push(push) => (2, None),
+			(lhs, Some(rhs), Some(operator)) => match fold(lhs, rhs, operator) {
+				Some(folded) => (3, Some(folded)),
+				None => (0, None),
+			},
+			_ => (0, None),
+		};
+		if count == 0 {
+			cursor.advance();
+			continue
+		}
+		if let Some(folded) = folded {
+			cursor.insert(&[folded]).expect(BALANCED);
+			cursor.remove(count).expect(BALANCED);
+			cursor.retreat();
+		} else {
+			cursor.remove(count).expect(BALANCED);
+		}
+		// The two instructions before may combine with the ones now following them.
+		cursor.retreat();
+		cursor.retreat();
+	}
+
+	original_len - code.len()
+}
+
+/// Whether the instruction only pushes a value, without any other effect.
+fn is_pure_push(instruction: &Instruction) -> bool {
+	matches!(
+		instruction,
+		Instruction::I32Const(_) |
+			Instruction::I64Const(_) |
+			Instruction::F32Const(_) |
+			Instruction::F64Const(_) |
+			Instruction::GetLocal(_)
+	)
+}
+
+/// Constant computed by `instruction` if its operands are the constants `lhs` and `rhs`.
+fn fold(lhs: &Instruction, rhs: &Instruction, instruction: &Instruction) -> Option<Instruction> {
+	use Instruction::*;
+
+	let folded = match (lhs.const_value()?, rhs.const_value()?, instruction) {
+		(ConstValue::I32(a), ConstValue::I32(b), I32Add) => I32Const(a.wrapping_add(b)),
+		(ConstValue::I32(a), ConstValue::I32(b), I32Sub) => I32Const(a.wrapping_sub(b)),
+		(ConstValue::I32(a), ConstValue::I32(b), I32Mul) => I32Const(a.wrapping_mul(b)),
+		(ConstValue::I32(a), ConstValue::I32(b), I32And) => I32Const(a & b),
+		(ConstValue::I32(a), ConstValue::I32(b), I32Or) => I32Const(a | b),
+		(ConstValue::I32(a), ConstValue::I32(b), I32Xor) => I32Const(a ^ b),
+		(ConstValue::I64(a), ConstValue::I64(b), I64Add) => I64Const(a.wrapping_add(b)),
+		(ConstValue::I64(a), ConstValue::I64(b), I64Sub) => I64Const(a.wrapping_sub(b)),
+		(ConstValue::I64(a), ConstValue::I64(b), I64Mul) => I64Const(a.wrapping_mul(b)),
+		(ConstValue::I64(a), ConstValue::I64(b), I64And) => I64Const(a & b),
+		(ConstValue::I64(a), ConstValue::I64(b), I64Or) => I64Const(a | b),
+		(ConstValue::I64(a), ConstValue::I64(b), I64Xor) => I64Const(a ^ b),
+		_ => return None,
+	};
+	Some(folded)
+}
+
+/// Inline calls of small functions into their callers.
+///
+/// A function is inlined if its code has at most `max_size` instructions (its final `end`
+/// excluded), it calls no other function, declares no locals besides its parameters and has at
+/// most one result. At each call site the arguments are stored into new locals appended to the
+/// caller, and the callee code is wrapped into a block, with `return`s turned into branches out
+/// of it.
+///
+/// Inlined functions are kept, together with their exports and table entries, so the index
+/// spaces don't change. Unreferenced functions have to be removed separately if desired.
+///
+/// Returns the number of inlined calls, lazily deserialized bodies are decoded first. Functions
+/// without a signature are left as they are, and so are calls needing more locals than a
+/// function can have.
+pub fn inline_small_functions(module: &mut Module, max_size: usize) -> Result<usize, Error> {
+	let imported = module.import_count(ImportCountType::Function) as u32;
+	let types = module.type_section().map_or(&[][..], |section| section.types());
+	let signatures: Vec<Option<FunctionType>> = module
+		.function_section()
+		.map_or(&[][..], |section| section.entries())
+		.iter()
+		.map(|func| {
+			types
+				.get(func.type_ref() as usize)
+				.map(|Type::Function(func_type)| func_type.clone())
+		})
+		.collect();
+
+	if let Some(section) = module.code_section_mut() {
+		for body in section.bodies_mut() {
+			body.decode()?;
+		}
+	}
+	let bodies = module.code_section().map_or(&[][..], |section| section.bodies());
+	let inlinable: Vec<Option<(&FunctionType, Vec<Instruction>)>> = bodies
+		.iter()
+		.zip(&signatures)
+		.map(|(body, signature)| {
+			let signature = signature.as_ref()?;
+			let code = body.code().elements();
+			let inline = body.locals().iter().all(|local| local.count() == 0) &&
+				signature.results().len() <= 1 &&
+				code.len() <= max_size.saturating_add(1) &&
+				code.iter().all(|instruction| match *instruction {
+					Instruction::Call(_) | Instruction::CallIndirect(..) => false,
+					Instruction::GetLocal(index) |
+					Instruction::SetLocal(index) |
+					Instruction::TeeLocal(index) => (index.index() as usize) < signature.params().len(),
+					_ => true,
+				});
+			if inline {
+				Some((signature, code.to_vec()))
+			} else {
+				None
+			}
+		})
+		.collect();
+
+	let mut inlined = 0;
+	rewrite_with(module, true, |context, code| {
+		let mut cursor = Cursor::new(code);
+		while let Some(instruction) = cursor.current() {
+			let callee = match *instruction {
+				Instruction::Call(index) => index
+					.index()
+					.checked_sub(imported)
+					.and_then(|defined| inlinable.get(defined as usize))
+					.and_then(Option::as_ref),
+				_ => None,
+			};
+			match callee {
+				Some((callee_signature, callee_code)) => {
+					let base = match context.add_locals(callee_signature.params()) {
+						Ok(base) => base.index(),
+						Err(_) => {
+							cursor.advance();
+							continue
+						},
+					};
+					let mut expanded = Vec::new();
+					expand(&mut expanded, callee_signature, callee_code, base);
+					cursor.remove(1)?;
+					cursor.insert(&expanded)?;
+					inlined += 1;
+				},
+				None => cursor.advance(),
+			}
+		}
+		Ok::<_, Error>(())
+	})?;
+	Ok(inlined)
+}
+
+/// Append the code of an inlined function, which has its parameters in the locals from `base`.
+fn expand(
+	code: &mut Vec<Instruction>,
+	signature: &FunctionType,
+	callee: &[Instruction],
+	base: u32,
+) {
+	for index in (0..signature.params().len() as u32).rev() {
+		code.push(Instruction::SetLocal((base + index).into()));
+	}
+	let block_type = match *signature.results() {
+		[result] => BlockType::Value(result),
+		_ => BlockType::NoResult,
+	};
+	code.push(Instruction::Block(block_type));
+
+	// Depth of the wrapping block from the current instruction, the final `end` closes it.
+	let mut depth = 0u32;
+	for instruction in callee {
+		code.push(match *instruction {
+			Instruction::GetLocal(index) => Instruction::GetLocal((base + index.index()).into()),
+			Instruction::SetLocal(index) => Instruction::SetLocal((base + index.index()).into()),
+			Instruction::TeeLocal(index) => Instruction::TeeLocal((base + index.index()).into()),
+			Instruction::Return => Instruction::Br(depth.into()),
+			ref other => other.clone(),
+		});
+		if instruction.closes_block() {
+			depth = depth.saturating_sub(1);
+		} else if instruction.is_block() {
+			depth += 1;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{inline_small_functions, peephole};
+	use crate::elements::{
+		deserialize_buffer, serialize, BlockType, CodeSection, External, Func, FuncBody,
+		FunctionSection, FunctionType, ImportEntry, ImportSection, Instruction, Instruction::*,
+		Instructions, Local, Module, Section, Type, TypeSection, ValueType,
+	};
+
+	fn optimized(code: Vec<Instruction>) -> (usize, Vec<Instruction>) {
+		let mut body = FuncBody::new(vec![], Instructions::new(code));
+		let removed = peephole(&mut body);
+		(removed, body.code().elements().to_vec())
+	}
+
+	#[test]
+	fn folds_constants() {
+		assert_eq!(
+			optimized(vec![I32Const(2), I32Const(3), I32Add, I32Const(4), I32Mul, End]),
+			(4, vec![I32Const(20), End])
+		);
+		assert_eq!(
+			optimized(vec![I32Const(i32::MAX), I32Const(1), I32Add, End]),
+			(2, vec![I32Const(i32::MIN), End])
+		);
+		assert_eq!(
+			optimized(vec![I64Const(1), I64Const(3), I64Sub, I64Const(6), I64Xor, End]),
+			(4, vec![I64Const(-8), End])
+		);
+		// A folded constant is folded again with the one before it.
+		assert_eq!(
+			optimized(vec![I32Const(1), I32Const(2), I32Const(3), I32Add, I32Sub, End]),
+			(4, vec![I32Const(-4), End])
+		);
+		// Mixed widths are left alone.
+		assert_eq!(
+			optimized(vec![I64Const(1), I32Const(3), I32Add, End]),
+			(0, vec![I64Const(1), I32Const(3), I32Add, End])
+		);
+	}
+
+	#[test]
+	fn removes_dead_values() {
+		assert_eq!(
+			optimized(vec![Nop, I32Const(7), Drop, GetLocal(0.into()), Drop, Nop, End]),
+			(6, vec![End])
+		);
+		// Pairs removed in the middle expose the surrounding instructions to each other.
+		assert_eq!(
+			optimized(vec![I32Const(1), I32Const(5), I32Const(2), Drop, I32Add, End]),
+			(4, vec![I32Const(6), End])
+		);
+		assert_eq!(
+			optimized(vec![GetLocal(0.into()), I32Const(1), Drop, Drop, End]),
+			(4, vec![End])
+		);
+		// Other instructions have effects (or may trap) and are kept.
+		assert_eq!(
+			optimized(vec![GetGlobal(0.into()), Drop, I32Const(1), I32Const(0), I32DivS, End]),
+			(0, vec![GetGlobal(0.into()), Drop, I32Const(1), I32Const(0), I32DivS, End])
+		);
+	}
+
+	#[test]
+	fn does_not_fold_across_blocks() {
+		let code = vec![
+			I32Const(1),
+			Block(BlockType::Value(ValueType::I32)),
+			I32Const(2),
+			End,
+			I32Add,
+			Drop,
+			End,
+		];
+		assert_eq!(optimized(code.clone()), (0, code));
+	}
+
+	#[test]
+	fn optimize_all() {
+		let module = crate::builder::module()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.code()
+			.nop()
+			.i32_const(100)
+			.i32_const(200)
+			.i32_mul()
+			.drop()
+			.end()
+			.build()
+			.build()
+			.build()
+			.build();
+		let original_size = module.clone().into_bytes().expect("module to serialize").len();
+
+		let mut lazy = Module::deserialize_lazy(module.into_bytes().expect("module to serialize"))
+			.expect("module to deserialize");
+		assert_eq!(lazy.optimize_all().expect("bodies to decode"), 5);
+
+		let body = &lazy.code_section().expect("module has code").bodies()[0];
+		assert_eq!(body.code().elements(), &[End]);
+		// `nop`, two constants of 3 bytes each, `i32.mul` and `drop`.
+		assert_eq!(lazy.into_bytes().expect("module to serialize").len(), original_size - 9);
+	}
+
+	/// Parameters, result, locals and code of a function.
+	type Function = (Vec<ValueType>, Option<ValueType>, Vec<Local>, Vec<Instruction>);
+
+	/// Module importing a `() -> ()` function, with the given functions defined after it.
+	fn module(functions: Vec<Function>) -> Module {
+		let mut types = vec![Type::Function(FunctionType::new(vec![], vec![]))];
+		let mut entries = Vec::new();
+		let mut bodies = Vec::new();
+		for (params, result, locals, code) in functions {
+			entries.push(Func::new(types.len() as u32));
+			types.push(Type::Function(FunctionType::new(params, result.into_iter().collect())));
+			bodies.push(FuncBody::new(locals, Instructions::new(code)));
+		}
+		let import = ImportEntry::new("env".into(), "f".into(), External::Function(0.into()));
+		Module::new(vec![
+			Section::Type(TypeSection::with_types(types)),
+			Section::Import(ImportSection::with_entries(vec![import])),
+			Section::Function(FunctionSection::with_entries(entries)),
+			Section::Code(CodeSection::with_bodies(bodies)),
+		])
+	}
+
+	fn helpers() -> Module {
+		let i32 = ValueType::I32;
+		module(vec![
+			// 1: add(a, b)
+			(
+				vec![i32, i32],
+				Some(i32),
+				vec![],
+				vec![GetLocal(0.into()), GetLocal(1.into()), I32Add, End],
+			),
+			// 2: abs(a), returning early
+			(
+				vec![i32],
+				Some(i32),
+				vec![],
+				vec![
+					GetLocal(0.into()),
+					I32Const(0),
+					I32LtS,
+					If(BlockType::NoResult),
+					I32Const(0),
+					GetLocal(0.into()),
+					I32Sub,
+					Return,
+					End,
+					GetLocal(0.into()),
+					End,
+				],
+			),
+			// 3: declares a local
+			(
+				vec![i32],
+				Some(i32),
+				vec![Local::new(1, i32)],
+				vec![GetLocal(0.into()), TeeLocal(1.into()), End],
+			),
+			// 4: calls the import
+			(vec![], None, vec![], vec![Call(0.into()), End]),
+			// 5: abs(add(a, 2))
+			(
+				vec![i32],
+				Some(i32),
+				vec![Local::new(1, ValueType::I64)],
+				vec![
+					GetLocal(0.into()),
+					I32Const(2),
+					Call(1.into()),
+					Call(2.into()),
+					Call(3.into()),
+					Call(4.into()),
+					End,
+				],
+			),
+		])
+	}
+
+	fn caller(module: &Module) -> &FuncBody {
+		&module.code_section().expect("module has code").bodies()[4]
+	}
+
+	#[test]
+	fn inlines_small_functions() {
+		let mut module = helpers();
+		assert_eq!(inline_small_functions(&mut module, 10).expect("bodies to decode"), 2);
+		let caller = caller(&module);
+		assert_eq!(
+			caller.locals(),
+			&[Local::new(1, ValueType::I64), Local::new(3, ValueType::I32)]
+		);
+		assert_eq!(
+			caller.code().elements(),
+			&[
+				GetLocal(0.into()),
+				I32Const(2),
+				SetLocal(3.into()),
+				SetLocal(2.into()),
+				Block(BlockType::Value(ValueType::I32)),
+				GetLocal(2.into()),
+				GetLocal(3.into()),
+				I32Add,
+				End,
+				SetLocal(4.into()),
+				Block(BlockType::Value(ValueType::I32)),
+				GetLocal(4.into()),
+				I32Const(0),
+				I32LtS,
+				If(BlockType::NoResult),
+				I32Const(0),
+				GetLocal(4.into()),
+				I32Sub,
+				Br(1.into()),
+				End,
+				GetLocal(4.into()),
+				End,
+				Call(3.into()),
+				Call(4.into()),
+				End,
+			]
+		);
+		// Inlined functions themselves are kept.
+		assert_eq!(module.code_section().expect("module has code").bodies().len(), 5);
+
+		let bytes = serialize(module.clone()).expect("module to serialize");
+		let deserialized: Module = deserialize_buffer(&bytes).expect("module to deserialize");
+		assert_eq!(deserialized, module);
+	}
+
+	#[test]
+	fn inline_size_threshold() {
+		let mut module = helpers();
+		assert_eq!(inline_small_functions(&mut module, 9).expect("bodies to decode"), 1);
+		let code = caller(&module).code().elements();
+		assert_eq!(code[9..], [Call(2.into()), Call(3.into()), Call(4.into()), End]);
+
+		let mut module = helpers();
+		assert_eq!(inline_small_functions(&mut module, 0).expect("bodies to decode"), 0);
+		assert_eq!(module, helpers());
+	}
+
+	#[test]
+	fn inline_skips_functions() {
+		// The caller has no signature.
+		let mut module = helpers();
+		module.function_section_mut().expect("module has functions").entries_mut()[4] =
+			Func::new(99);
+		let original = module.clone();
+		assert_eq!(inline_small_functions(&mut module, 10).expect("bodies to decode"), 0);
+		assert_eq!(module, original);
+
+		// Only one more local fits, enough for `abs` but not for `add`.
+		let mut module = helpers();
+		let body = &mut module.code_section_mut().expect("module has code").bodies_mut()[4];
+		*body.locals_mut() = vec![Local::new(u32::MAX - 2, ValueType::I64)];
+		assert_eq!(inline_small_functions(&mut module, 10).expect("bodies to decode"), 1);
+		let code = caller(&module).code().elements();
+		assert_eq!(code[2..4], [Call(1.into()), SetLocal((u32::MAX - 1).into())]);
+	}
+}
@@ -0,0 +1,321 @@
+//! Rewriting of the function bodies of a module.
+
+use crate::elements::{
+	Error, External, FuncIdx, FunctionType, GlobalIdx, GlobalType, Instruction, Local, LocalIdx,
+	Module, Type, TypeIdx, ValueType,
+};
+use alloc::vec::Vec;
+
+/// Function being rewritten by [`rewrite_functions`], with the index spaces of its module.
+pub struct FuncContext<'a> {
+	index: FuncIdx,
+	signature: &'a FunctionType,
+	locals: &'a [Local],
+	added: Vec<ValueType>,
+	types: &'a [FunctionType],
+	functions: &'a [TypeIdx],
+	globals: &'a [GlobalType],
+}
+
+impl<'a> FuncContext<'a> {
+	/// Index of the function in the function index space.
+	pub fn index(&self) -> FuncIdx {
+		self.index
+	}
+
+	/// Signature of the function.
+	pub fn signature(&self) -> &'a FunctionType {
+		self.signature
+	}
+
+	/// Type of the local, parameters and added locals included, `None` if there's no such
+	/// local.
+	pub fn local_type(&self, index: LocalIdx) -> Option<ValueType> {
+		let params = self.signature.params();
+		let index = index.index() as usize;
+		if let Some(&param) = params.get(index) {
+			return Some(param)
+		}
+		let mut remaining = (index - params.len()) as u64;
+		for local in self.locals {
+			if remaining < u64::from(local.count()) {
+				return Some(local.value_type())
+			}
+			remaining -= u64::from(local.count());
+		}
+		self.added.get(remaining as usize).copied()
+	}
+
+	/// Declare a new local of the given type, after all the existing ones.
+	///
+	/// Indices of the existing locals don't change, so their names in the name section stay
+	/// valid. Fails with [`Error::TooManyLocals`] if the function would have more than
+	/// `u32::MAX` locals.
+	pub fn add_local(&mut self, value_type: ValueType) -> Result<LocalIdx, Error> {
+		self.add_locals(&[value_type])
+	}
+
+	/// Declare new locals of the given types, see [`FuncContext::add_local`], and return the
+	/// index of the first one.
+	///
+	/// None of them are added if the function would have too many locals.
+	pub fn add_locals(&mut self, value_types: &[ValueType]) -> Result<LocalIdx, Error> {
+		let declared = self.locals.iter().map(|local| u64::from(local.count())).sum::<u64>();
+		let index = self.signature.params().len() as u64 + declared + self.added.len() as u64;
+		if index + value_types.len() as u64 > u64::from(u32::MAX) {
+			return Err(Error::TooManyLocals)
+		}
+		self.added.extend_from_slice(value_types);
+		Ok(LocalIdx(index as u32))
+	}
+
+	/// Signature in the type section.
+	pub fn resolve_type(&self, index: TypeIdx) -> Option<&'a FunctionType> {
+		self.types.get(index.index() as usize)
+	}
+
+	/// Signature of a function of the module, imported or not.
+	pub fn function_signature(&self, index: FuncIdx) -> Option<&'a FunctionType> {
+		self.resolve_type(*self.functions.get(index.index() as usize)?)
+	}
+
+	/// Type of a global of the module, imported or not.
+	pub fn global_type(&self, index: GlobalIdx) -> Option<&'a GlobalType> {
+		self.globals.get(index.index() as usize)
+	}
+}
+
+/// Rewrite the code of every function defined in the module.
+///
+/// `rewrite` is called once per function, in index order, with its context and a copy of its
+/// instructions. Once every function is rewritten, the copies replace the code and locals added
+/// with [`FuncContext::add_local`] are declared, so the module is left unchanged on errors.
+/// Lazily deserialized bodies are decoded first.
+///
+/// Fails with [`Error::UnresolvedType`] if the type of a function isn't in the type section, and
+/// with [`Error::InconsistentCode`] if there are more bodies than functions.
+pub fn rewrite_functions<F, E>(module: &mut Module, rewrite: F) -> Result<(), E>
+where
+	F: FnMut(&mut FuncContext<'_>, &mut Vec<Instruction>) -> Result<(), E>,
+	E: From<Error>,
+{
+	rewrite_with(module, false, rewrite)
+}
+
+/// [`rewrite_functions`], leaving the bodies without a signature unchanged rather than failing
+/// if `skip_unresolved` is set.
+pub(crate) fn rewrite_with<F, E>(
+	module: &mut Module,
+	skip_unresolved: bool,
+	mut rewrite: F,
+) -> Result<(), E>
+where
+	F: FnMut(&mut FuncContext<'_>, &mut Vec<Instruction>) -> Result<(), E>,
+	E: From<Error>,
+{
+	let types: Vec<FunctionType> = module
+		.type_section()
+		.map_or(&[][..], |section| section.types())
+		.iter()
+		.map(|Type::Function(func_type)| func_type.clone())
+		.collect();
+	let imports = module.import_section().map_or(&[][..], |section| section.entries());
+	let mut functions: Vec<TypeIdx> = imports
+		.iter()
+		.filter_map(|entry| match *entry.external() {
+			External::Function(type_ref) => Some(type_ref),
+			_ => None,
+		})
+		.collect();
+	let imported = functions.len();
+	functions.extend(
+		module
+			.function_section()
+			.map_or(&[][..], |section| section.entries())
+			.iter()
+			.map(|func| TypeIdx(func.type_ref())),
+	);
+	let mut globals: Vec<GlobalType> = imports
+		.iter()
+		.filter_map(|entry| match *entry.external() {
+			External::Global(global_type) => Some(global_type),
+			_ => None,
+		})
+		.collect();
+	globals.extend(
+		module
+			.global_section()
+			.map_or(&[][..], |section| section.entries())
+			.iter()
+			.map(|global| *global.global_type()),
+	);
+
+	let bodies = match module.code_section_mut() {
+		Some(section) => section.bodies_mut(),
+		None => return Ok(()),
+	};
+	let mut rewritten = Vec::with_capacity(bodies.len());
+	for (defined, body) in bodies.iter_mut().enumerate() {
+		let index = imported + defined;
+		let signature = functions.get(index).ok_or(Error::InconsistentCode).and_then(|type_ref| {
+			types
+				.get(type_ref.index() as usize)
+				.ok_or(Error::UnresolvedType(type_ref.index()))
+		});
+		let signature = match signature {
+			Ok(signature) => signature,
+			Err(_) if skip_unresolved => {
+				rewritten.push(None);
+				continue
+			},
+			Err(err) => return Err(err.into()),
+		};
+
+		let mut code = body.decode()?.elements().to_vec();
+		let mut context = FuncContext {
+			index: FuncIdx(index as u32),
+			signature,
+			locals: body.locals(),
+			added: Vec::new(),
+			types: &types,
+			functions: &functions,
+			globals: &globals,
+		};
+		rewrite(&mut context, &mut code)?;
+		rewritten.push(Some((code, context.added)));
+	}
+
+	for (body, rewritten) in bodies.iter_mut().zip(rewritten) {
+		if let Some((code, added)) = rewritten {
+			*body.code_mut().elements_mut() = code;
+			for value_type in added {
+				push_local(body.locals_mut(), value_type);
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Declare one more local, merged into the last declaration if it has the same type.
+fn push_local(locals: &mut Vec<Local>, value_type: ValueType) {
+	match locals.last_mut() {
+		Some(last) if last.value_type() == value_type && last.count() < u32::MAX =>
+			*last = Local::new(last.count() + 1, value_type),
+		_ => locals.push(Local::new(1, value_type)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::rewrite_functions;
+	use crate::{
+		builder,
+		elements::{Error, FuncIdx, GlobalIdx, Instruction::*, Local, LocalIdx, Module, ValueType},
+	};
+
+	/// Imported global and function, then a function with a parameter and a local.
+	fn module() -> Module {
+		builder::module()
+			.import()
+			.path("env", "g")
+			.external()
+			.global(ValueType::I64, false)
+			.build()
+			.import()
+			.path("env", "f")
+			.external()
+			.func(0)
+			.build()
+			.function()
+			.signature()
+			.with_param(ValueType::I32)
+			.build()
+			.body()
+			.with_local(1, ValueType::F32)
+			.code()
+			.call(0)
+			.end()
+			.build()
+			.build()
+			.build()
+			.build()
+	}
+
+	#[test]
+	fn function_context() {
+		let mut module = module();
+		let mut calls = 0;
+		rewrite_functions(&mut module, |context, code| {
+			calls += 1;
+			assert_eq!(context.index(), FuncIdx(1));
+			assert_eq!(context.signature().params(), [ValueType::I32]);
+			assert_eq!(context.local_type(LocalIdx(0)), Some(ValueType::I32));
+			assert_eq!(context.local_type(LocalIdx(1)), Some(ValueType::F32));
+			assert_eq!(context.local_type(LocalIdx(2)), None);
+			assert_eq!(context.function_signature(FuncIdx(0)), Some(context.signature()));
+			assert_eq!(
+				context.global_type(GlobalIdx(0)).map(|g| g.content_type()),
+				Some(ValueType::I64)
+			);
+			assert_eq!(context.global_type(GlobalIdx(1)), None);
+
+			let local = context.add_local(ValueType::I64)?;
+			assert_eq!(local, LocalIdx(2));
+			assert_eq!(context.local_type(local), Some(ValueType::I64));
+			assert_eq!(context.add_locals(&[ValueType::I64])?, LocalIdx(3));
+			code.insert(0, GetGlobal(GlobalIdx(0)));
+			code.insert(1, SetLocal(local));
+			Ok::<_, Error>(())
+		})
+		.unwrap();
+		assert_eq!(calls, 1);
+
+		let body = &module.code_section().unwrap().bodies()[0];
+		assert_eq!(body.locals(), [Local::new(1, ValueType::F32), Local::new(2, ValueType::I64)]);
+		assert_eq!(
+			body.code().elements(),
+			[GetGlobal(GlobalIdx(0)), SetLocal(LocalIdx(2)), Call(FuncIdx(0)), End]
+		);
+	}
+
+	#[test]
+	fn rewrite_errors() {
+		#[derive(Debug, PartialEq)]
+		enum RewriteError {
+			Module,
+			Rejected,
+		}
+
+		impl From<Error> for RewriteError {
+			fn from(_: Error) -> Self {
+				RewriteError::Module
+			}
+		}
+
+		// Errors of the rewrite are returned, and its changes are dropped.
+		let mut module = module();
+		let original = module.clone();
+		let result = rewrite_functions(&mut module, |context, code| {
+			context.add_local(ValueType::I32)?;
+			code.clear();
+			Err(RewriteError::Rejected)
+		});
+		assert_eq!(result, Err(RewriteError::Rejected));
+		assert_eq!(module, original);
+
+		// The function has a parameter, so there is no room left for another local.
+		let mut full = module.clone();
+		let body = &mut full.code_section_mut().unwrap().bodies_mut()[0];
+		*body.locals_mut() = vec![Local::new(u32::MAX - 1, ValueType::F32)];
+		let result = rewrite_functions(&mut full, |context, _| {
+			assert_eq!(context.add_locals(&[])?, LocalIdx(u32::MAX));
+			context.add_locals(&[ValueType::I32, ValueType::I32])?;
+			Ok(())
+		});
+		assert_eq!(result, Err(RewriteError::Module));
+
+		module.type_section_mut().unwrap().types_mut().clear();
+		let result = rewrite_functions(&mut module, |_, _| Ok(()));
+		assert_eq!(result, Err(RewriteError::Module));
+	}
+}